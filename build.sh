@@ -64,6 +64,16 @@ build_arch() {
 
     (cd "$output_parent" && zip -r "$package_path" "$output_basename" >/dev/null)
 
+    # Builds that embed keys/app-release.asc refuse archives without a valid detached signature.
+    if [[ -n "${SIGNING_KEY:-}" ]]; then
+        rm -f "$package_path.asc"
+        gpg --batch --yes --local-user "$SIGNING_KEY" --digest-algo SHA256 \
+            --armor --detach-sign --output "$package_path.asc" "$package_path"
+        echo "Signed $package_name with $SIGNING_KEY"
+    else
+        echo "warning: SIGNING_KEY not set; $package_name is unsigned and cannot be installed by builds that embed a release key" >&2
+    fi
+
     echo "Built $arch_label artifact in $output_dir"
     echo "Packaged archive available at $package_path"
 }
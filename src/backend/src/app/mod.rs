@@ -4,7 +4,12 @@ pub mod protocol;
 mod realtime;
 mod status_builder;
 
-pub use protocol::{ControlRequest, GuiAddressToggleRequest};
+pub use protocol::{
+    ControlRequest, GuiAddressToggleRequest, InstallTriggerRequest, SyncthingUpdateInstallRequest,
+    UpdateDownloadRequest,
+};
+
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use serde::Serialize;
@@ -13,9 +18,11 @@ use tokio::task::JoinHandle;
 use tracing::error;
 
 use crate::config::Config;
-use crate::deployment::{Installer, Updater};
+use crate::deployment::{CancelToken, InstallCheckpoint, Installer, Updater};
 use crate::syncthing_client::{SyncthingClient, SyncthingUpgradeCheck};
-use crate::types::MonitorError;
+use crate::types::{MonitorError, RateLimit};
+use crate::utils::filesystem::RootMounts;
+use crate::utils::{system_runner, SharedRunner};
 use appload_client::{AppLoadBackend, BackendReplier, Message};
 
 use self::protocol::*;
@@ -25,6 +32,14 @@ pub struct InstallerFlowState {
     pub in_progress: bool,
     pub progress_message: Option<String>,
     pub error: Option<String>,
+    /// Present while the download phase can still be aborted.
+    pub cancel: Option<CancelToken>,
+    pub cancelled: bool,
+    /// Set when the last failure was the release server's rate limit.
+    pub rate_limit: Option<RateLimit>,
+    pub checkpoints: Vec<InstallCheckpoint>,
+    /// Whether the automatic restore of a lost service ran since start.
+    pub restore_attempted: bool,
 }
 
 #[derive(Debug, Default)]
@@ -33,8 +48,15 @@ pub struct AppUpdateFlowState {
     pub progress_message: Option<String>,
     pub error: Option<String>,
     pub pending_update_url: Option<String>,
+    pub pending_signature_url: Option<String>,
     pub pending_restart: bool,
     pub restart_seconds_remaining: Option<u32>,
+    /// Present while the download phase can still be aborted.
+    pub cancel: Option<CancelToken>,
+    pub cancelled: bool,
+    pub rolled_back: bool,
+    /// Set when the last check failed because of the release server's rate limit.
+    pub rate_limit: Option<RateLimit>,
 }
 
 #[derive(Debug, Default)]
@@ -49,6 +71,10 @@ pub struct SyncthingUpdateFlowState {
 pub struct Backend {
     pub client: Option<SyncthingClient>,
     pub config: Config,
+    /// Runs `mount`, `systemctl` and `uname` for the backend and installer.
+    pub runner: SharedRunner,
+    /// Shared by every operation that writes below `/`.
+    pub mounts: Arc<RootMounts>,
     pub installer: Installer,
     pub installer_state: InstallerFlowState,
     pub updater: Updater,
@@ -56,29 +82,49 @@ pub struct Backend {
     pub syncthing_update_state: SyncthingUpdateFlowState,
     pub realtime_task: Option<JoinHandle<()>>,
     pub systemd_monitor_task: Option<JoinHandle<()>>,
+    pub journal_follow_task: Option<JoinHandle<()>>,
 }
 
 impl Backend {
     pub async fn new(config: Config) -> Self {
         let client = SyncthingClient::discover(&config).await.ok();
-        let installer = Installer::new(config.clone());
-        let updater = Updater::new();
+        let runner = system_runner();
+        let mounts = RootMounts::new(runner.clone());
+        mounts.recover_after_crash().await;
+        let installer = Installer::new(config.clone(), runner.clone(), mounts.clone());
+        let updater = Updater::new(&config);
+        let mut update_state = AppUpdateFlowState::default();
+        if let Some(notice) = Updater::take_rollback_notice().await {
+            if notice.automatic {
+                update_state.error = Some(notice.describe());
+            } else {
+                update_state.progress_message = Some(notice.describe());
+            }
+        }
         Self {
             client,
             config,
+            runner,
+            mounts,
             installer,
             installer_state: InstallerFlowState::default(),
             updater,
-            update_state: AppUpdateFlowState::default(),
+            update_state,
             syncthing_update_state: SyncthingUpdateFlowState::default(),
             realtime_task: None,
             systemd_monitor_task: None,
+            journal_follow_task: None,
         }
     }
 
     pub async fn send_status(&mut self, functionality: &BackendReplier<Self>, reason: &str) {
-        let snapshot =
-            status_builder::build_status_payload(&self.config, &mut self.client, reason).await;
+        let snapshot = status_builder::build_status_payload(
+            &self.config,
+            self.runner.as_ref(),
+            &mut self.client,
+            reason,
+        )
+        .await;
         if let Err(err) = self
             .send_json_message(functionality, MSG_STATUS_UPDATE, &snapshot)
             .await
@@ -127,13 +173,24 @@ impl Backend {
     }
 }
 
+impl Drop for Backend {
+    fn drop(&mut self) {
+        self.stop_journal_follow();
+    }
+}
+
 #[async_trait]
 impl AppLoadBackend for Backend {
     async fn handle_message(&mut self, functionality: &BackendReplier<Self>, message: Message) {
         match message.msg_type {
             MSG_SYSTEM_NEW_COORDINATOR => {
+                // A follow belongs to the previous frontend's log viewer.
+                self.stop_journal_follow();
                 self.ensure_realtime_updates(functionality);
+                self.confirm_update_healthy().await;
+                self.restore_lost_service(functionality).await;
                 self.send_install_status(functionality).await;
+                self.send_update_status(functionality).await;
                 self.send_syncthing_update_status(functionality).await;
                 self.send_status(functionality, "frontend-connected").await;
             }
@@ -146,6 +203,14 @@ impl AppLoadBackend for Backend {
                 }
             }
             MSG_INSTALL_TRIGGER => {
+                let request = match serde_json::from_str::<InstallTriggerRequest>(&message.contents)
+                {
+                    Ok(request) => request,
+                    Err(err) => {
+                        self.send_error(functionality, &format!("Invalid install payload: {err}"));
+                        return;
+                    }
+                };
                 if self.config.disable_syncthing_installer {
                     self.installer_state.error = Some(
                         "Installer disabled via config. Please install Syncthing manually."
@@ -159,7 +224,42 @@ impl AppLoadBackend for Backend {
                         Some("Installer is already running...".to_string());
                     self.send_install_status(functionality).await;
                 } else {
-                    self.run_installer(functionality).await;
+                    self.start_installer(functionality, request.version, request.local)
+                        .await;
+                }
+            }
+            MSG_INSTALL_CANCEL_REQUEST => {
+                self.handle_install_cancel(functionality).await;
+            }
+            MSG_INSTALL_VERSIONS_REQUEST => {
+                self.handle_install_versions(functionality).await;
+            }
+            MSG_UNINSTALL_REQUEST => {
+                match serde_json::from_str::<UninstallRequest>(&message.contents) {
+                    Ok(req) => self.start_uninstall(functionality, req.wipe_config).await,
+                    Err(err) => {
+                        self.send_error(functionality, &format!("Invalid uninstall payload: {err}"))
+                    }
+                }
+            }
+            MSG_REPAIR_SERVICE_REQUEST => {
+                self.start_service_repair(functionality).await;
+            }
+            MSG_JOURNAL_REQUEST => {
+                match serde_json::from_str::<JournalRequest>(&message.contents) {
+                    Ok(req) => self.handle_journal_request(functionality, req).await,
+                    Err(err) => {
+                        self.send_error(functionality, &format!("Invalid journal payload: {err}"))
+                    }
+                }
+            }
+            MSG_JOURNAL_FOLLOW_REQUEST => {
+                match serde_json::from_str::<JournalFollowRequest>(&message.contents) {
+                    Ok(req) => self.handle_journal_follow(functionality, req),
+                    Err(err) => self.send_error(
+                        functionality,
+                        &format!("Invalid journal follow payload: {err}"),
+                    ),
                 }
             }
             MSG_GUI_ADDRESS_TOGGLE => {
@@ -178,16 +278,44 @@ impl AppLoadBackend for Backend {
                 self.handle_update_check(functionality).await;
             }
             MSG_UPDATE_DOWNLOAD_REQUEST => {
-                self.handle_update_download(functionality).await;
+                match serde_json::from_str::<UpdateDownloadRequest>(&message.contents) {
+                    Ok(req) if req.local => self.handle_local_update(functionality).await,
+                    Ok(req) => {
+                        self.handle_update_download(functionality, req.version)
+                            .await
+                    }
+                    Err(err) => self.send_error(
+                        functionality,
+                        &format!("Invalid update download payload: {err}"),
+                    ),
+                }
+            }
+            MSG_UPDATE_VERSIONS_REQUEST => {
+                self.handle_update_versions(functionality).await;
             }
             MSG_UPDATE_RESTART_REQUEST => {
                 self.handle_update_restart_request(functionality).await;
             }
+            MSG_UPDATE_CANCEL_REQUEST => {
+                self.handle_update_cancel(functionality).await;
+            }
+            MSG_UPDATE_ROLLBACK_REQUEST => {
+                self.handle_update_rollback(functionality).await;
+            }
             MSG_SYNCTHING_UPDATE_CHECK_REQUEST => {
                 self.handle_syncthing_update_check(functionality).await;
             }
             MSG_SYNCTHING_UPDATE_INSTALL_REQUEST => {
-                self.handle_syncthing_update_install(functionality).await;
+                match serde_json::from_str::<SyncthingUpdateInstallRequest>(&message.contents) {
+                    Ok(req) => {
+                        self.handle_syncthing_update_install(functionality, req.confirm_major)
+                            .await
+                    }
+                    Err(err) => self.send_error(
+                        functionality,
+                        &format!("Invalid Syncthing update payload: {err}"),
+                    ),
+                }
             }
             other => {
                 self.send_error(functionality, &format!("Unknown message type {other}"));
@@ -1,13 +1,15 @@
 use tokio::sync::mpsc;
-use tracing::error;
+use tracing::{error, info, warn};
 
-use crate::deployment::InstallerStatus;
+use crate::deployment::{
+    CancelToken, CheckpointState, InstallCheckpoint, Installer, InstallerStatus, SmokeTestStep,
+};
 use crate::types::MonitorError;
 use appload_client::BackendReplier;
 
-use super::super::protocol::MSG_INSTALL_STATUS;
+use super::super::protocol::{MSG_INSTALL_STATUS, MSG_INSTALL_VERSIONS_RESULT};
 use super::super::Backend;
-use super::progress_runner::ProgressTarget;
+use super::progress_runner::{run_with_download_progress, ProgressTarget};
 
 impl Backend {
     pub async fn send_install_status(&self, functionality: &BackendReplier<Self>) {
@@ -23,6 +25,16 @@ impl Backend {
     pub async fn build_install_status(&self) -> InstallerStatus {
         let binary_present = self.installer.binary_present().await;
         let service_installed = self.installer.service_installed().await;
+        let (unit_drift, profile_issues) = if service_installed && !self.installer_state.in_progress
+        {
+            let drift = self.installer.unit_drift().await.unwrap_or_else(|err| {
+                warn!(error = ?err, "Failed to compare the installed systemd unit");
+                Vec::new()
+            });
+            (drift, self.installer.profile_issues().await)
+        } else {
+            (Vec::new(), Vec::new())
+        };
         InstallerStatus {
             binary_present,
             service_installed,
@@ -30,85 +42,449 @@ impl Backend {
             progress_message: self.installer_state.progress_message.clone(),
             error: self.installer_state.error.clone(),
             installer_disabled: self.config.disable_syncthing_installer,
+            cancellable: self.installer_state.cancel.is_some(),
+            cancelled: self.installer_state.cancelled,
+            local_archive: self
+                .installer
+                .local_archive()
+                .await
+                .map(|archive| archive.file_name()),
+            rate_limited: self.installer_state.rate_limit.is_some(),
+            rate_limited_until: self
+                .installer_state
+                .rate_limit
+                .and_then(|limit| limit.until_label()),
+            checkpoints: self.installer_state.checkpoints.clone(),
+            unit_drift,
+            profile_issues,
+            service_lost: !service_installed && self.installer.service_lost().await,
         }
     }
 
-    pub async fn run_installer(&mut self, functionality: &BackendReplier<Self>) {
+    pub async fn handle_install_versions(&mut self, functionality: &BackendReplier<Self>) {
+        match self.installer.list_versions().await {
+            Ok(versions) => {
+                if let Err(err) = self
+                    .send_json_message(functionality, MSG_INSTALL_VERSIONS_RESULT, &versions)
+                    .await
+                {
+                    error!(error = ?err, "Failed to send available Syncthing versions");
+                }
+            }
+            Err(err) => {
+                if let Some(limit) = err.rate_limit() {
+                    self.installer_state.rate_limit = Some(limit);
+                    self.send_install_status(functionality).await;
+                }
+                self.send_error(
+                    functionality,
+                    &format!("Failed to list Syncthing versions: {err}"),
+                );
+            }
+        }
+    }
+
+    /// Starts the installer in the background so cancel requests can still be handled.
+    ///
+    /// An explicit `version` is always downloaded, replacing any installed binary.
+    /// With `local`, the binary comes from the sideload directory instead.
+    pub async fn start_installer(
+        &mut self,
+        functionality: &BackendReplier<Self>,
+        version: Option<String>,
+        local: bool,
+    ) {
+        if let Some(requested) = &version {
+            if semver::Version::parse(requested.trim().trim_start_matches('v')).is_err() {
+                self.send_error(
+                    functionality,
+                    &format!("Invalid Syncthing version '{requested}'"),
+                );
+                return;
+            }
+        }
+
+        let source = match (local, version) {
+            (true, _) => InstallSource::Local,
+            (false, Some(version)) => InstallSource::Version(version),
+            (false, None) => InstallSource::Default,
+        };
+        let download_label = match source
+            .version()
+            .or(self.config.pinned_syncthing_version.as_deref())
+        {
+            Some(target) => format!("Downloading Syncthing v{}", target.trim_start_matches('v')),
+            None => "Downloading latest Syncthing release".to_string(),
+        };
+        let cancel = CancelToken::new();
         self.installer_state.in_progress = true;
         self.installer_state.error = None;
+        self.installer_state.cancelled = false;
+        self.installer_state.rate_limit = None;
+        self.installer_state.checkpoints.clear();
+        self.installer_state.cancel = Some(cancel.clone());
         self.installer_state.progress_message =
             Some("Checking Syncthing installation...".to_string());
         self.send_install_status(functionality).await;
 
-        if !self.installer.binary_present().await {
-            self.installer_state.progress_message =
-                Some("Downloading latest Syncthing release...".to_string());
-            self.send_install_status(functionality).await;
-            let (progress_tx, progress_rx) = mpsc::channel(16);
-            let installer = self.installer.clone();
-            let download_future =
-                Box::pin(async move { installer.download_latest_binary(Some(progress_tx)).await });
-
-            match self
-                .run_with_download_progress(
-                    functionality,
-                    download_future,
-                    progress_rx,
-                    ProgressTarget::Installer,
-                    "Downloading latest Syncthing release",
-                    None,
-                )
-                .await
-            {
-                Ok(()) => {}
+        let replier = functionality.clone();
+        let installer = self.installer.clone();
+        tokio::spawn(async move {
+            let result = run_installer(&replier, &installer, source, &download_label, cancel).await;
+            let mut backend = replier.backend.lock().await;
+            match result {
+                Ok(()) => backend.finish_installer_with_success(&replier).await,
+                Err(MonitorError::Cancelled) => backend.finish_installer_cancelled(&replier).await,
+                Err(err) => backend.finish_installer_with_error(err, &replier).await,
+            }
+        });
+    }
+
+    /// Removes the Syncthing service and binary in the background, and with
+    /// `wipe_config` also its configuration and database.
+    pub async fn start_uninstall(
+        &mut self,
+        functionality: &BackendReplier<Self>,
+        wipe_config: bool,
+    ) {
+        if self.installer_state.in_progress || self.syncthing_update_state.in_progress {
+            self.send_error(functionality, "Syncthing is being installed or updated");
+            return;
+        }
+
+        self.installer_state.in_progress = true;
+        self.installer_state.error = None;
+        self.installer_state.cancelled = false;
+        self.installer_state.rate_limit = None;
+        self.installer_state.checkpoints.clear();
+        self.installer_state.cancel = None;
+        self.installer_state.progress_message = Some("Stopping Syncthing...".to_string());
+        self.send_install_status(functionality).await;
+
+        let replier = functionality.clone();
+        let installer = self.installer.clone();
+        tokio::spawn(async move {
+            let result = run_uninstall(&replier, &installer, wipe_config).await;
+            let mut backend = replier.backend.lock().await;
+            backend.installer_state.in_progress = false;
+            match result {
+                Ok(()) => {
+                    info!(wipe_config, "Uninstalled Syncthing");
+                    backend.installer_state.progress_message = Some(if wipe_config {
+                        "Syncthing and its configuration were removed.".to_string()
+                    } else {
+                        "Syncthing was removed. Its configuration was kept.".to_string()
+                    });
+                }
                 Err(err) => {
-                    self.finish_installer_with_error(err, functionality).await;
-                    return;
+                    error!(error = ?err, "Uninstall failed");
+                    backend.installer_state.error = Some(format!("Uninstall failed: {}", err));
+                    backend.installer_state.progress_message = None;
                 }
             }
+            backend.send_install_status(&replier).await;
+            backend.send_status(&replier, "uninstall").await;
+        });
+    }
+
+    /// Rewrites a drifted systemd unit in the background, or restores a unit an
+    /// OS update removed. Neither downloads anything, so this also works with
+    /// the installer disabled.
+    pub async fn start_service_repair(&mut self, functionality: &BackendReplier<Self>) {
+        if self.installer_state.in_progress || self.syncthing_update_state.in_progress {
+            self.send_error(functionality, "Syncthing is being installed or updated");
+            return;
         }
 
-        self.installer_state.progress_message =
-            Some("Binary ready. Preparing systemd service...".to_string());
+        let restore = self.installer.service_lost().await;
+        self.installer_state.in_progress = true;
+        self.installer_state.error = None;
+        self.installer_state.cancelled = false;
+        self.installer_state.cancel = None;
+        self.installer_state.checkpoints.clear();
+        self.installer_state.progress_message = Some(if restore {
+            "Restoring systemd service...".to_string()
+        } else {
+            "Repairing systemd service...".to_string()
+        });
         self.send_install_status(functionality).await;
 
-        let service_installed = self.installer.service_installed().await;
-
-        if !service_installed {
-            self.installer_state.progress_message =
-                Some("Creating and enabling systemd service...".to_string());
-            self.send_install_status(functionality).await;
-            if let Err(err) = self.installer.install_service().await {
-                self.finish_installer_with_error(err, functionality).await;
-                return;
+        let replier = functionality.clone();
+        let installer = self.installer.clone();
+        tokio::spawn(async move {
+            let result = if restore {
+                restore_service(&replier, &installer).await
+            } else {
+                installer.repair_service().await
+            };
+            let mut backend = replier.backend.lock().await;
+            backend.installer_state.in_progress = false;
+            match result {
+                Ok(()) => {
+                    info!(restore, "Repaired systemd unit");
+                    backend.installer_state.progress_message = Some(if restore {
+                        "Systemd service restored.".to_string()
+                    } else {
+                        "Systemd service repaired.".to_string()
+                    });
+                }
+                Err(err) => {
+                    error!(error = ?err, restore, "Service repair failed");
+                    backend.installer_state.error = Some(if restore {
+                        format!("Service restore failed: {}", err)
+                    } else {
+                        format!("Service repair failed: {}", err)
+                    });
+                    backend.installer_state.progress_message = None;
+                }
             }
-        } else {
-            self.installer_state.progress_message =
-                Some("Restarting existing Syncthing service...".to_string());
-            self.send_install_status(functionality).await;
-            if let Err(err) = self.installer.restart_service().await {
-                self.finish_installer_with_error(err, functionality).await;
-                return;
+            backend.send_install_status(&replier).await;
+            backend.send_status(&replier, "repair").await;
+        });
+    }
+
+    /// Restores the service once per start when an OS update removed it.
+    pub async fn restore_lost_service(&mut self, functionality: &BackendReplier<Self>) {
+        if self.installer_state.restore_attempted
+            || !self.config.restore_service_after_os_update
+            || self.installer_state.in_progress
+        {
+            return;
+        }
+        self.installer_state.restore_attempted = true;
+        if self.installer.service_lost().await {
+            info!("Syncthing service is missing, probably after an OS update; restoring it");
+            self.start_service_repair(functionality).await;
+        }
+    }
+
+    pub async fn handle_install_cancel(&mut self, functionality: &BackendReplier<Self>) {
+        match &self.installer_state.cancel {
+            Some(cancel) if self.installer_state.in_progress => {
+                cancel.cancel();
+                self.installer_state.progress_message = Some("Cancelling download...".to_string());
+                self.send_install_status(functionality).await;
             }
+            _ => self.send_error(functionality, "No installer download to cancel"),
         }
+    }
 
+    async fn finish_installer_with_success(&mut self, functionality: &BackendReplier<Self>) {
         self.installer_state.progress_message =
             Some("Syncthing installed successfully.".to_string());
         self.installer_state.in_progress = false;
         self.installer_state.error = None;
+        self.installer_state.cancel = None;
         self.send_install_status(functionality).await;
         self.send_status(functionality, "installer").await;
     }
 
+    async fn finish_installer_cancelled(&mut self, functionality: &BackendReplier<Self>) {
+        self.installer_state.in_progress = false;
+        self.installer_state.error = None;
+        self.installer_state.cancel = None;
+        self.installer_state.cancelled = true;
+        self.installer_state.progress_message = Some("Download cancelled.".to_string());
+        self.send_install_status(functionality).await;
+    }
+
     pub async fn finish_installer_with_error(
         &mut self,
         err: MonitorError,
         functionality: &BackendReplier<Self>,
     ) {
         self.installer_state.in_progress = false;
+        self.installer_state.cancel = None;
+        self.installer_state.rate_limit = err.rate_limit();
         self.installer_state.error = Some(err.to_string());
         self.installer_state.progress_message =
             Some("Installer failed. See error for details.".to_string());
         self.send_install_status(functionality).await;
     }
 }
+
+enum InstallSource {
+    /// Download the pinned or newest version when the binary is missing.
+    Default,
+    /// Download this version even if a binary is installed.
+    Version(String),
+    /// Install the archive from the sideload directory.
+    Local,
+}
+
+impl InstallSource {
+    fn version(&self) -> Option<&str> {
+        match self {
+            InstallSource::Version(version) => Some(version),
+            _ => None,
+        }
+    }
+}
+
+async fn run_installer(
+    functionality: &BackendReplier<Backend>,
+    installer: &Installer,
+    source: InstallSource,
+    download_label: &str,
+    cancel: CancelToken,
+) -> Result<(), MonitorError> {
+    if matches!(source, InstallSource::Local) {
+        functionality.backend.lock().await.installer_state.cancel = None;
+        report_progress(functionality, "Installing Syncthing from local archive...").await;
+        let installed = installer.install_local_binary().await?;
+        info!(version = %installed, "Installed sideloaded Syncthing binary");
+    } else if source.version().is_some() || !installer.binary_present().await {
+        let version = source.version().map(str::to_string);
+        report_progress(functionality, &format!("{download_label}...")).await;
+        let (progress_tx, progress_rx) = mpsc::channel(16);
+        let download_installer = installer.clone();
+        let download_future = Box::pin(async move {
+            download_installer
+                .download_binary(version.as_deref(), Some(progress_tx), Some(cancel))
+                .await
+        });
+
+        let installed = run_with_download_progress(
+            functionality,
+            download_future,
+            progress_rx,
+            ProgressTarget::Installer,
+            download_label,
+            None,
+        )
+        .await?;
+        info!(version = %installed, "Installed Syncthing binary");
+    } else {
+        functionality.backend.lock().await.installer_state.cancel = None;
+    }
+
+    let provision = installer.needs_provisioning().await;
+    if provision {
+        report_progress(functionality, "Generating Syncthing configuration...").await;
+        installer.generate_config().await?;
+    }
+
+    report_progress(functionality, "Binary ready. Preparing systemd service...").await;
+
+    if !installer.service_installed().await {
+        report_progress(functionality, "Creating and enabling systemd service...").await;
+        installer.install_service().await?;
+    } else {
+        report_progress(functionality, "Restarting existing Syncthing service...").await;
+        installer.restart_service().await?;
+    }
+
+    report_progress(functionality, "Verifying the installation...").await;
+    run_smoke_test(functionality, installer).await?;
+
+    if provision {
+        report_progress(functionality, "Applying device settings...").await;
+        installer.provision_instance().await?;
+    }
+    Ok(())
+}
+
+/// Reinstalls the unit lost with an OS update and checks that it runs.
+async fn restore_service(
+    functionality: &BackendReplier<Backend>,
+    installer: &Installer,
+) -> Result<(), MonitorError> {
+    installer.install_service().await?;
+    report_progress(functionality, "Verifying the restored service...").await;
+    run_smoke_test(functionality, installer).await
+}
+
+/// Confirms the installed Syncthing works, publishing each step as a checkpoint.
+async fn run_smoke_test(
+    functionality: &BackendReplier<Backend>,
+    installer: &Installer,
+) -> Result<(), MonitorError> {
+    functionality
+        .backend
+        .lock()
+        .await
+        .installer_state
+        .checkpoints = SmokeTestStep::ALL
+        .into_iter()
+        .map(InstallCheckpoint::pending)
+        .collect();
+
+    for step in SmokeTestStep::ALL {
+        update_checkpoint(functionality, step, CheckpointState::Running, None).await;
+        let result = match step {
+            SmokeTestStep::BinaryRuns => installer.check_binary_runs().await.map(Some),
+            SmokeTestStep::ServiceRunning => {
+                installer.wait_for_service_running().await.map(|_| None)
+            }
+            SmokeTestStep::ConfigGenerated => installer.wait_for_config_xml().await.map(|_| None),
+            SmokeTestStep::ApiReachable => installer.wait_for_api().await.map(Some),
+        };
+        match result {
+            Ok(detail) => {
+                let detail = detail.map(|version| format!("v{}", version));
+                update_checkpoint(functionality, step, CheckpointState::Passed, detail).await;
+            }
+            Err(err) => {
+                update_checkpoint(
+                    functionality,
+                    step,
+                    CheckpointState::Failed,
+                    Some(err.to_string()),
+                )
+                .await;
+                return Err(MonitorError::Syncthing(format!(
+                    "installed, but the check \"{}\" failed: {}",
+                    step.label(),
+                    err
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn update_checkpoint(
+    functionality: &BackendReplier<Backend>,
+    step: SmokeTestStep,
+    state: CheckpointState,
+    detail: Option<String>,
+) {
+    let mut backend = functionality.backend.lock().await;
+    if let Some(checkpoint) = backend
+        .installer_state
+        .checkpoints
+        .iter_mut()
+        .find(|checkpoint| checkpoint.step == step)
+    {
+        checkpoint.state = state;
+        checkpoint.detail = detail;
+    }
+    backend.send_install_status(functionality).await;
+}
+
+async fn run_uninstall(
+    functionality: &BackendReplier<Backend>,
+    installer: &Installer,
+    wipe_config: bool,
+) -> Result<(), MonitorError> {
+    installer.stop_and_disable_service().await?;
+    report_progress(functionality, "Removing systemd service...").await;
+    installer.remove_service_unit().await?;
+    report_progress(functionality, "Removing Syncthing binary...").await;
+    installer.remove_binary().await?;
+    if wipe_config {
+        report_progress(
+            functionality,
+            "Deleting Syncthing configuration and database...",
+        )
+        .await;
+        installer.wipe_syncthing_state().await?;
+    }
+    Ok(())
+}
+
+async fn report_progress(functionality: &BackendReplier<Backend>, message: &str) {
+    let mut backend = functionality.backend.lock().await;
+    backend.installer_state.progress_message = Some(message.to_string());
+    backend.send_install_status(functionality).await;
+}
@@ -0,0 +1,93 @@
+use tokio::sync::mpsc;
+use tracing::{error, warn};
+
+use crate::systemd::{follow_journal, read_journal_page, JOURNAL_PAGE_SIZE};
+use appload_client::BackendReplier;
+
+use super::super::protocol::{
+    JournalFollowRequest, JournalRequest, MSG_JOURNAL_ENTRY, MSG_JOURNAL_PAGE,
+};
+use super::super::Backend;
+
+impl Backend {
+    pub async fn handle_journal_request(
+        &self,
+        functionality: &BackendReplier<Self>,
+        req: JournalRequest,
+    ) {
+        match read_journal_page(
+            self.runner.as_ref(),
+            &self.config.systemd_service_name,
+            req.before.as_deref(),
+            &req.filter,
+            JOURNAL_PAGE_SIZE,
+        )
+        .await
+        {
+            Ok(page) => {
+                if let Err(err) = self
+                    .send_json_message(functionality, MSG_JOURNAL_PAGE, &page)
+                    .await
+                {
+                    error!(error = ?err, "Failed to send journal page");
+                }
+            }
+            Err(err) => {
+                self.send_error(functionality, &format!("Failed to read the journal: {err}"))
+            }
+        }
+    }
+
+    /// Replaces any running follow with one for the requested filter, or just
+    /// stops it.
+    pub fn handle_journal_follow(
+        &mut self,
+        functionality: &BackendReplier<Self>,
+        req: JournalFollowRequest,
+    ) {
+        self.stop_journal_follow();
+        if !req.enabled {
+            return;
+        }
+
+        let service_name = self.config.systemd_service_name.clone();
+        let replier = functionality.clone();
+        self.journal_follow_task = Some(tokio::spawn(async move {
+            let (entry_tx, mut entry_rx) = mpsc::unbounded_channel();
+            let follow = follow_journal(
+                &service_name,
+                req.after.as_deref(),
+                &req.filter,
+                move |entry| {
+                    let _ = entry_tx.send(entry);
+                },
+            );
+            let forward = async {
+                while let Some(entry) = entry_rx.recv().await {
+                    let backend = replier.backend.lock().await;
+                    if let Err(err) = backend
+                        .send_json_message(&replier, MSG_JOURNAL_ENTRY, &entry)
+                        .await
+                    {
+                        error!(error = ?err, "Failed to send journal entry");
+                    }
+                }
+            };
+
+            let (result, ()) = tokio::join!(follow, forward);
+            if let Err(err) = result {
+                warn!(error = ?err, "Following the journal failed");
+                let backend = replier.backend.lock().await;
+                backend.send_error(&replier, &format!("Stopped following the journal: {err}"));
+            }
+        }));
+    }
+
+    /// Stops `journalctl --follow`, e.g. when the frontend that asked for it
+    /// is gone.
+    pub fn stop_journal_follow(&mut self) {
+        if let Some(task) = self.journal_follow_task.take() {
+            task.abort();
+        }
+    }
+}
@@ -1,4 +1,5 @@
 mod installer_ops;
+mod journal_ops;
 mod progress_runner;
 mod syncthing_ops;
 mod syncthing_update_ops;
@@ -14,73 +14,92 @@ use super::super::Backend;
 pub enum ProgressTarget {
     Installer,
     AppUpdate,
+    SyncthingUpdate,
 }
 
 type ProgressFuture<T> = Pin<Box<dyn Future<Output = Result<T, MonitorError>> + Send>>;
 
-impl Backend {
-    pub async fn run_with_download_progress<T>(
-        &mut self,
-        functionality: &BackendReplier<Self>,
-        mut operation: ProgressFuture<T>,
-        mut progress_rx: mpsc::Receiver<DownloadProgress>,
-        target: ProgressTarget,
-        progress_prefix: &str,
-        completion_message: Option<&str>,
-    ) -> Result<T, MonitorError> {
-        let mut operation_result: Option<Result<T, MonitorError>> = None;
-        let mut channel_open = true;
-        let mut completion_reported = false;
-        let mut last_percent_reported: Option<u8> = None;
-        let mut last_bytes_reported: u64 = 0;
+/// Drives a download operation while relaying its progress to the frontend.
+///
+/// Runs outside the backend lock so cancel requests can be handled meanwhile;
+/// the lock is only taken to publish status updates.
+pub async fn run_with_download_progress<T>(
+    functionality: &BackendReplier<Backend>,
+    mut operation: ProgressFuture<T>,
+    mut progress_rx: mpsc::Receiver<DownloadProgress>,
+    target: ProgressTarget,
+    progress_prefix: &str,
+    completion_message: Option<&str>,
+) -> Result<T, MonitorError> {
+    let mut operation_result: Option<Result<T, MonitorError>> = None;
+    let mut channel_open = true;
+    let mut completion_reported = false;
+    let mut last_percent_reported: Option<u8> = None;
+    let mut last_bytes_reported: u64 = 0;
 
-        while operation_result.is_none() || channel_open {
-            tokio::select! {
-                result = &mut operation, if operation_result.is_none() => {
-                    operation_result = Some(result);
-                }
-                progress = progress_rx.recv(), if channel_open => {
-                    match progress {
-                        Some(progress) => {
-                            if should_emit_download_progress(
-                                &progress,
-                                &mut last_percent_reported,
-                                &mut last_bytes_reported,
-                            ) {
-                                self.set_progress_message(
-                                    &target,
-                                    render_download_progress_message(progress_prefix, &progress),
-                                );
-                                self.send_progress_status(functionality, &target).await;
-                            }
+    while operation_result.is_none() || channel_open {
+        tokio::select! {
+            result = &mut operation, if operation_result.is_none() => {
+                operation_result = Some(result);
+            }
+            progress = progress_rx.recv(), if channel_open => {
+                match progress {
+                    Some(progress) => {
+                        if should_emit_download_progress(
+                            &progress,
+                            &mut last_percent_reported,
+                            &mut last_bytes_reported,
+                        ) {
+                            let mut backend = functionality.backend.lock().await;
+                            backend.set_progress_message(
+                                &target,
+                                render_download_progress_message(progress_prefix, &progress),
+                            );
+                            backend.send_progress_status(functionality, &target).await;
                         }
-                        None => {
-                            channel_open = false;
-                            if !completion_reported && operation_result.is_none() {
-                                if let Some(message) = completion_message {
-                                    completion_reported = true;
-                                    self.set_progress_message(&target, message.to_string());
-                                    self.send_progress_status(functionality, &target).await;
-                                }
+                    }
+                    None => {
+                        channel_open = false;
+                        // The download is over; whatever follows must run to completion.
+                        let mut backend = functionality.backend.lock().await;
+                        backend.clear_cancel(&target);
+                        if !completion_reported && operation_result.is_none() {
+                            if let Some(message) = completion_message {
+                                completion_reported = true;
+                                backend.set_progress_message(&target, message.to_string());
                             }
                         }
+                        backend.send_progress_status(functionality, &target).await;
                     }
                 }
             }
         }
+    }
 
-        match operation_result {
-            Some(result) => result,
-            None => Err(MonitorError::Config(
-                "Download operation ended without a result".to_string(),
-            )),
-        }
+    match operation_result {
+        Some(result) => result,
+        None => Err(MonitorError::Config(
+            "Download operation ended without a result".to_string(),
+        )),
     }
+}
 
+impl Backend {
     fn set_progress_message(&mut self, target: &ProgressTarget, message: String) {
         match target {
             ProgressTarget::Installer => self.installer_state.progress_message = Some(message),
             ProgressTarget::AppUpdate => self.update_state.progress_message = Some(message),
+            ProgressTarget::SyncthingUpdate => {
+                self.syncthing_update_state.progress_message = Some(message)
+            }
+        }
+    }
+
+    fn clear_cancel(&mut self, target: &ProgressTarget) {
+        match target {
+            ProgressTarget::Installer => self.installer_state.cancel = None,
+            ProgressTarget::AppUpdate => self.update_state.cancel = None,
+            ProgressTarget::SyncthingUpdate => {}
         }
     }
 
@@ -92,6 +111,9 @@ impl Backend {
         match target {
             ProgressTarget::Installer => self.send_install_status(functionality).await,
             ProgressTarget::AppUpdate => self.send_update_status(functionality).await,
+            ProgressTarget::SyncthingUpdate => {
+                self.send_syncthing_update_status(functionality).await
+            }
         }
     }
 }
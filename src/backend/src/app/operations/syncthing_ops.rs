@@ -42,7 +42,7 @@ impl Backend {
         }
 
         // Fall back to systemd control (or for non-restart actions)
-        match control_service(&self.config, req.action).await {
+        match control_service(&self.config, self.runner.as_ref(), &self.mounts, req.action).await {
             Ok(result) => {
                 let payload = json!({
                     "ok": true,
@@ -1,11 +1,25 @@
-use tracing::error;
+use std::time::Duration;
 
-use crate::deployment::SyncthingUpdateStatus;
-use crate::syncthing_client::SyncthingClient;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Instant};
+use tracing::{error, info, warn};
+
+use crate::config::{Config, ServiceProfile, SyncthingUpgradeMethod};
+use crate::deployment::{Installer, SyncthingUpdateStatus};
+use crate::syncthing_client::{
+    probe_version, SyncthingClient, SyncthingUpgradeCheck, UpgradeWatch, WatchState,
+    UPGRADE_POLL_INTERVAL,
+};
+use crate::types::MonitorError;
 use appload_client::BackendReplier;
 
 use super::super::protocol::{MSG_SYNCTHING_UPDATE_CHECK_RESULT, MSG_SYNCTHING_UPDATE_STATUS};
 use super::super::Backend;
+use super::progress_runner::{run_with_download_progress, ProgressTarget};
+
+/// Syncthing downloads the release itself before restarting, so this allows for
+/// a slow connection.
+const SELF_UPGRADE_TIMEOUT: Duration = Duration::from_secs(300);
 
 impl Backend {
     pub async fn handle_syncthing_update_check(&mut self, functionality: &BackendReplier<Self>) {
@@ -13,6 +27,11 @@ impl Backend {
             self.send_error(functionality, "Syncthing update already in progress");
             return;
         }
+        if let Some(message) = self.builtin_upgrade_blocked_message() {
+            self.syncthing_update_state.error = Some(message);
+            self.send_syncthing_update_status(functionality).await;
+            return;
+        }
 
         self.syncthing_update_state.in_progress = true;
         self.syncthing_update_state.progress_message =
@@ -21,9 +40,14 @@ impl Backend {
         self.syncthing_update_state.upgrade_started = false;
         self.send_syncthing_update_status(functionality).await;
 
-        let result = match SyncthingClient::discover(&self.config).await {
-            Ok(mut client) => client.check_upgrade().await,
-            Err(err) => Err(err),
+        let result = match self.config.syncthing_upgrade_method {
+            SyncthingUpgradeMethod::Builtin => {
+                match SyncthingClient::discover(&self.config).await {
+                    Ok(mut client) => client.check_upgrade().await,
+                    Err(err) => Err(err),
+                }
+            }
+            SyncthingUpgradeMethod::App => self.installer.check_binary_upgrade().await,
         };
 
         match result {
@@ -51,21 +75,40 @@ impl Backend {
         }
     }
 
-    pub async fn handle_syncthing_update_install(&mut self, functionality: &BackendReplier<Self>) {
+    /// Installs the update found by the last check.
+    ///
+    /// A major upgrade needs `confirm_major` and is refused unless the current
+    /// binary and state could be snapshotted for a rollback.
+    pub async fn handle_syncthing_update_install(
+        &mut self,
+        functionality: &BackendReplier<Self>,
+        confirm_major: bool,
+    ) {
         if self.syncthing_update_state.in_progress {
             self.send_error(functionality, "Syncthing update already in progress");
             return;
         }
+        if let Some(message) = self.builtin_upgrade_blocked_message() {
+            self.send_error(functionality, &message);
+            return;
+        }
 
-        let update_available = self
+        let check = self
             .syncthing_update_state
             .check_result
-            .as_ref()
-            .map(|check| check.newer)
-            .unwrap_or(false);
-        if !update_available {
+            .clone()
+            .filter(|check| check.newer);
+        let Some(check) = check else {
             self.send_error(functionality, "No Syncthing update available to install");
             return;
+        };
+        if check.major_newer && !confirm_major {
+            self.syncthing_update_state.error = Some(format!(
+                "Syncthing {} is a major upgrade that migrates the database. Confirm to continue.",
+                check.latest
+            ));
+            self.send_syncthing_update_status(functionality).await;
+            return;
         }
 
         self.syncthing_update_state.in_progress = true;
@@ -75,32 +118,48 @@ impl Backend {
             Some("Starting Syncthing upgrade...".to_string());
         self.send_syncthing_update_status(functionality).await;
 
-        let result = match SyncthingClient::discover(&self.config).await {
-            Ok(mut client) => client.perform_upgrade().await,
-            Err(err) => Err(err),
-        };
-
-        match result {
-            Ok(()) => {
-                self.syncthing_update_state.check_result = None;
-                self.syncthing_update_state.in_progress = false;
-                self.syncthing_update_state.error = None;
-                self.syncthing_update_state.upgrade_started = true;
-                self.syncthing_update_state.progress_message =
-                    Some("Syncthing upgrade started. Waiting for service restart...".to_string());
-                self.send_syncthing_update_status(functionality).await;
-                self.send_status(functionality, "syncthing-upgrade-started")
-                    .await;
-            }
-            Err(err) => {
-                self.syncthing_update_state.in_progress = false;
-                self.syncthing_update_state.error =
-                    Some(format!("Failed to install Syncthing update: {}", err));
-                self.syncthing_update_state.upgrade_started = false;
-                self.syncthing_update_state.progress_message = None;
-                self.send_syncthing_update_status(functionality).await;
+        let replier = functionality.clone();
+        let installer = self.installer.clone();
+        let config = self.config.clone();
+        tokio::spawn(async move {
+            let result = run_syncthing_upgrade(&replier, &installer, &config, &check).await;
+            let mut backend = replier.backend.lock().await;
+            backend.syncthing_update_state.in_progress = false;
+            match result {
+                Ok(installed) => {
+                    info!(version = %installed, "Syncthing upgrade confirmed");
+                    backend.syncthing_update_state.check_result = None;
+                    backend.syncthing_update_state.error = None;
+                    backend.syncthing_update_state.progress_message =
+                        Some(format!("Syncthing v{} is running.", installed));
+                }
+                Err(err) => {
+                    warn!(error = ?err, "Syncthing upgrade did not complete");
+                    backend.syncthing_update_state.error =
+                        Some(format!("Failed to install Syncthing update: {}", err));
+                    backend.syncthing_update_state.progress_message = None;
+                }
             }
+            backend.send_syncthing_update_status(&replier).await;
+            backend.send_status(&replier, "syncthing-upgrade").await;
+        });
+    }
+
+    /// Why Syncthing's own upgrade cannot be used, if it cannot.
+    fn builtin_upgrade_blocked_message(&self) -> Option<String> {
+        if self.config.syncthing_upgrade_method == SyncthingUpgradeMethod::App {
+            return None;
         }
+        if self.config.service_profile == ServiceProfile::Hardened {
+            return Some(
+                "The hardened service profile keeps Syncthing from replacing its own binary; set syncthing_upgrade_method to \"app\" to upgrade.".to_string(),
+            );
+        }
+        self.config.pinned_syncthing_version.as_ref().map(|version| {
+            format!(
+                "Syncthing is pinned to v{version} in config.json; remove pinned_syncthing_version or set syncthing_upgrade_method to \"app\" to upgrade."
+            )
+        })
     }
 
     pub async fn send_syncthing_update_status(&self, functionality: &BackendReplier<Self>) {
@@ -121,3 +180,126 @@ impl Backend {
         }
     }
 }
+
+/// Applies the checked update with the configured method. Returns the version
+/// the API reports afterwards.
+async fn run_syncthing_upgrade(
+    functionality: &BackendReplier<Backend>,
+    installer: &Installer,
+    config: &Config,
+    check: &SyncthingUpgradeCheck,
+) -> Result<String, MonitorError> {
+    let snapshot = if check.major_newer {
+        report_progress(
+            functionality,
+            "Backing up Syncthing state before the major upgrade...",
+        )
+        .await;
+        let snapshot = installer
+            .prepare_major_upgrade(check.running.trim_start_matches('v'))
+            .await
+            .map_err(|err| {
+                MonitorError::Config(format!(
+                    "major upgrade refused because it could not be rolled back: {}",
+                    err
+                ))
+            })?;
+        Some(snapshot)
+    } else {
+        None
+    };
+
+    match config.syncthing_upgrade_method {
+        SyncthingUpgradeMethod::App => {
+            let version = check.latest.trim_start_matches('v').to_string();
+            let download_label = format!("Downloading Syncthing v{}", version);
+            report_progress(functionality, &format!("{}...", download_label)).await;
+            let (progress_tx, progress_rx) = mpsc::channel(16);
+            let upgrade_installer = installer.clone();
+            let upgrade_future = Box::pin(async move {
+                upgrade_installer
+                    .upgrade_binary(&version, snapshot.as_ref(), Some(progress_tx), None)
+                    .await
+            });
+            run_with_download_progress(
+                functionality,
+                upgrade_future,
+                progress_rx,
+                ProgressTarget::SyncthingUpdate,
+                &download_label,
+                Some("Restarting Syncthing and waiting for it to come back..."),
+            )
+            .await
+        }
+        SyncthingUpgradeMethod::Builtin => {
+            SyncthingClient::discover(config)
+                .await?
+                .perform_upgrade()
+                .await?;
+            {
+                let mut backend = functionality.backend.lock().await;
+                backend.syncthing_update_state.check_result = None;
+                backend.syncthing_update_state.upgrade_started = true;
+                backend.syncthing_update_state.progress_message =
+                    Some("Syncthing upgrade started. Waiting for service restart...".to_string());
+                backend.send_syncthing_update_status(functionality).await;
+                backend
+                    .send_status(functionality, "syncthing-upgrade-started")
+                    .await;
+            }
+
+            let watched = watch_self_upgrade(functionality, config, &check.latest).await;
+            match (watched, snapshot) {
+                (Ok(version), Some(snapshot)) => {
+                    installer.discard_major_upgrade_snapshot(&snapshot).await;
+                    Ok(version)
+                }
+                (Ok(version), None) => Ok(version),
+                (Err(err), Some(snapshot)) => {
+                    report_progress(functionality, "Restoring the previous Syncthing version...")
+                        .await;
+                    installer.roll_back_major_upgrade(&snapshot).await?;
+                    Err(MonitorError::Syncthing(format!(
+                        "{}; Syncthing v{} and its state were restored",
+                        err, snapshot.version
+                    )))
+                }
+                (Err(err), None) => Err(err),
+            }
+        }
+    }
+}
+
+/// Follows Syncthing's self-upgrade until the API reports `version`.
+async fn watch_self_upgrade(
+    functionality: &BackendReplier<Backend>,
+    config: &Config,
+    version: &str,
+) -> Result<String, MonitorError> {
+    let mut watch = UpgradeWatch::new(version);
+    let deadline = Instant::now() + SELF_UPGRADE_TIMEOUT;
+    let mut last_state = WatchState::WaitingForRestart;
+    while Instant::now() < deadline {
+        sleep(UPGRADE_POLL_INTERVAL).await;
+        let running = probe_version(config).await;
+        let state = watch.observe(running.as_deref());
+        if state == WatchState::Confirmed {
+            return Ok(watch.expected().to_string());
+        }
+        if state != last_state {
+            last_state = state;
+            report_progress(
+                functionality,
+                &format!("Syncthing is restarting into v{}...", watch.expected()),
+            )
+            .await;
+        }
+    }
+    Err(watch.timed_out(SELF_UPGRADE_TIMEOUT))
+}
+
+async fn report_progress(functionality: &BackendReplier<Backend>, message: &str) {
+    let mut backend = functionality.backend.lock().await;
+    backend.syncthing_update_state.progress_message = Some(message.to_string());
+    backend.send_syncthing_update_status(functionality).await;
+}
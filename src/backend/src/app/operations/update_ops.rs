@@ -1,15 +1,19 @@
+use std::path::Path;
+
 use tokio::sync::mpsc;
 use tokio::time::{sleep, Duration};
 use tracing::{error, warn};
 
-use crate::deployment::UpdateStatus;
+use crate::deployment::{CancelToken, UpdateStatus, Updater};
+use crate::types::MonitorError;
 use appload_client::BackendReplier;
 
 use super::super::protocol::{
-    MSG_UPDATE_CHECK_RESULT, MSG_UPDATE_DOWNLOAD_STATUS, UPDATE_RESTART_DELAY_SECS,
+    MSG_UPDATE_CHECK_RESULT, MSG_UPDATE_DOWNLOAD_STATUS, MSG_UPDATE_VERSIONS_RESULT,
+    UPDATE_RESTART_DELAY_SECS,
 };
 use super::super::Backend;
-use super::progress_runner::ProgressTarget;
+use super::progress_runner::{run_with_download_progress, ProgressTarget};
 
 impl Backend {
     pub async fn handle_update_check(&mut self, functionality: &BackendReplier<Self>) {
@@ -21,11 +25,20 @@ impl Backend {
         self.update_state.in_progress = true;
         self.update_state.progress_message = Some("Checking for updates...".to_string());
         self.update_state.error = None;
+        self.update_state.cancelled = false;
         self.send_update_status(functionality).await;
 
-        match self.updater.check_for_updates().await {
+        match self
+            .updater
+            .check_for_updates(
+                self.config.release_channel,
+                self.config.pinned_app_version.as_deref(),
+            )
+            .await
+        {
             Ok(result) => {
                 self.update_state.pending_update_url = result.download_url.clone();
+                self.update_state.pending_signature_url = result.signature_url.clone();
                 self.update_state.in_progress = false;
                 self.update_state.progress_message = None;
 
@@ -39,6 +52,7 @@ impl Backend {
             }
             Err(err) => {
                 self.update_state.in_progress = false;
+                self.update_state.rate_limit = err.rate_limit();
                 self.update_state.error = Some(format!("Failed to check for updates: {}", err));
                 self.update_state.progress_message = None;
                 self.send_update_status(functionality).await;
@@ -46,12 +60,54 @@ impl Backend {
         }
     }
 
-    pub async fn handle_update_download(&mut self, functionality: &BackendReplier<Self>) {
+    pub async fn handle_update_versions(&mut self, functionality: &BackendReplier<Self>) {
+        match self
+            .updater
+            .list_versions(
+                self.config.release_channel,
+                self.config.pinned_app_version.as_deref(),
+            )
+            .await
+        {
+            Ok(versions) => {
+                if let Err(err) = self
+                    .send_json_message(functionality, MSG_UPDATE_VERSIONS_RESULT, &versions)
+                    .await
+                {
+                    error!(error = ?err, "Failed to send available app versions");
+                }
+            }
+            Err(err) => {
+                if let Some(limit) = err.rate_limit() {
+                    self.update_state.rate_limit = Some(limit);
+                    self.send_update_status(functionality).await;
+                }
+                self.send_error(
+                    functionality,
+                    &format!("Failed to list app versions: {err}"),
+                );
+            }
+        }
+    }
+
+    /// Downloads and installs the update found by the last check, or with an
+    /// explicit `version` that app release, which may be older than this one.
+    pub async fn handle_update_download(
+        &mut self,
+        functionality: &BackendReplier<Self>,
+        version: Option<String>,
+    ) {
         if self.update_state.in_progress {
             self.send_error(functionality, "Update already in progress");
             return;
         }
+        if let Some(version) = version {
+            if !self.target_update_version(functionality, &version).await {
+                return;
+            }
+        }
 
+        let signature_url = self.update_state.pending_signature_url.clone();
         let download_url = match &self.update_state.pending_update_url {
             Some(url) => url.clone(),
             None => {
@@ -60,54 +116,213 @@ impl Backend {
             }
         };
 
+        let cancel = CancelToken::new();
         self.update_state.in_progress = true;
         self.update_state.error = None;
         self.update_state.progress_message = Some("Downloading update...".to_string());
         self.update_state.pending_restart = false;
         self.update_state.restart_seconds_remaining = None;
+        self.update_state.cancelled = false;
+        self.update_state.cancel = Some(cancel.clone());
         self.send_update_status(functionality).await;
 
-        let (progress_tx, progress_rx) = mpsc::channel(16);
+        let replier = functionality.clone();
         let updater = self.updater.clone();
-        let update_future = Box::pin(async move {
-            updater
-                .download_and_apply_update(&download_url, Some(progress_tx))
-                .await
-        });
+        tokio::spawn(async move {
+            let (progress_tx, progress_rx) = mpsc::channel(16);
+            let update_future = Box::pin(async move {
+                updater
+                    .download_and_apply_update(
+                        &download_url,
+                        signature_url.as_deref(),
+                        Some(progress_tx),
+                        Some(cancel),
+                    )
+                    .await
+            });
 
-        match self
-            .run_with_download_progress(
-                functionality,
+            let result = run_with_download_progress(
+                &replier,
                 update_future,
                 progress_rx,
                 ProgressTarget::AppUpdate,
                 "Downloading update",
                 Some("Installing update files..."),
             )
+            .await;
+
+            let mut backend = replier.backend.lock().await;
+            backend.update_state.cancel = None;
+            match result {
+                Ok(()) => {
+                    backend
+                        .begin_restart_countdown(
+                            &replier,
+                            "Update installed. Restarting shortly...",
+                        )
+                        .await;
+                }
+                Err(MonitorError::Cancelled) => {
+                    backend.update_state.in_progress = false;
+                    backend.update_state.cancelled = true;
+                    backend.update_state.progress_message =
+                        Some("Update download cancelled.".to_string());
+                    backend.send_update_status(&replier).await;
+                }
+                Err(err) => {
+                    backend.update_state.in_progress = false;
+                    backend.update_state.error =
+                        Some(format!("Failed to download/apply update: {}", err));
+                    backend.update_state.progress_message = None;
+                    backend.update_state.pending_restart = false;
+                    backend.update_state.restart_seconds_remaining = None;
+                    backend.send_update_status(&replier).await;
+                }
+            }
+        });
+    }
+
+    /// Points the pending download at app release `version`. Returns whether
+    /// there is anything to install.
+    async fn target_update_version(
+        &mut self,
+        functionality: &BackendReplier<Self>,
+        version: &str,
+    ) -> bool {
+        let version = version.trim().trim_start_matches('v');
+        if semver::Version::parse(version).is_err() {
+            self.send_error(functionality, &format!("Invalid app version '{version}'"));
+            return false;
+        }
+
+        match self
+            .updater
+            .check_for_updates(self.config.release_channel, Some(version))
             .await
         {
+            Ok(result) if result.update_available => {
+                self.update_state.pending_update_url = result.download_url;
+                self.update_state.pending_signature_url = result.signature_url;
+                true
+            }
+            Ok(_) => {
+                self.send_error(
+                    functionality,
+                    &format!("App version {version} is already installed"),
+                );
+                false
+            }
+            Err(err) => {
+                self.update_state.rate_limit = err.rate_limit();
+                self.update_state.error =
+                    Some(format!("Failed to find app version {version}: {err}"));
+                self.send_update_status(functionality).await;
+                false
+            }
+        }
+    }
+
+    /// Applies a signed app archive from the sideload directory without network access.
+    pub async fn handle_local_update(&mut self, functionality: &BackendReplier<Self>) {
+        if self.update_state.in_progress || self.update_state.pending_restart {
+            self.send_error(functionality, "Update already in progress");
+            return;
+        }
+
+        self.update_state.in_progress = true;
+        self.update_state.error = None;
+        self.update_state.cancelled = false;
+        self.update_state.progress_message =
+            Some("Installing update from local archive...".to_string());
+        self.send_update_status(functionality).await;
+
+        let sideload_dir = Path::new(&self.config.sideload_dir);
+        match self.updater.apply_local_update(sideload_dir).await {
             Ok(()) => {
-                self.begin_restart_countdown(functionality).await;
+                self.begin_restart_countdown(
+                    functionality,
+                    "Update installed. Restarting shortly...",
+                )
+                .await;
             }
             Err(err) => {
                 self.update_state.in_progress = false;
-                self.update_state.error = Some(format!("Failed to download/apply update: {}", err));
+                self.update_state.error = Some(format!("Failed to apply local update: {}", err));
                 self.update_state.progress_message = None;
-                self.update_state.pending_restart = false;
-                self.update_state.restart_seconds_remaining = None;
                 self.send_update_status(functionality).await;
             }
         }
     }
 
+    pub async fn handle_update_cancel(&mut self, functionality: &BackendReplier<Self>) {
+        match &self.update_state.cancel {
+            Some(cancel) if self.update_state.in_progress => {
+                cancel.cancel();
+                self.update_state.progress_message =
+                    Some("Cancelling update download...".to_string());
+                self.send_update_status(functionality).await;
+            }
+            _ => self.send_error(functionality, "No update download to cancel"),
+        }
+    }
+
+    pub async fn handle_update_rollback(&mut self, functionality: &BackendReplier<Self>) {
+        if self.update_state.in_progress || self.update_state.pending_restart {
+            self.send_error(functionality, "Update already in progress");
+            return;
+        }
+
+        self.update_state.in_progress = true;
+        self.update_state.error = None;
+        self.update_state.progress_message = Some("Restoring previous version...".to_string());
+        self.send_update_status(functionality).await;
+
+        match self.updater.roll_back_to_previous().await {
+            Ok(notice) => {
+                self.update_state.rolled_back = true;
+                let message = format!("{} Restarting shortly...", notice.describe());
+                self.begin_restart_countdown(functionality, &message).await;
+            }
+            Err(err) => {
+                self.update_state.in_progress = false;
+                self.update_state.error = Some(format!("Failed to roll back update: {}", err));
+                self.update_state.progress_message = None;
+                self.send_update_status(functionality).await;
+            }
+        }
+    }
+
+    /// Marks a freshly updated version as healthy now that the frontend is connected.
+    pub async fn confirm_update_healthy(&self) {
+        if let Err(err) = Updater::confirm_healthy().await {
+            warn!(error = ?err, "Failed to clear boot-pending marker");
+        }
+    }
+
     pub async fn send_update_status(&self, functionality: &BackendReplier<Self>) {
         let status = UpdateStatus {
             in_progress: self.update_state.in_progress,
             progress_message: self.update_state.progress_message.clone(),
             error: self.update_state.error.clone(),
-            success: !self.update_state.in_progress && self.update_state.error.is_none(),
+            success: !self.update_state.in_progress
+                && self.update_state.error.is_none()
+                && !self.update_state.cancelled,
             pending_restart: self.update_state.pending_restart,
             restart_seconds_remaining: self.update_state.restart_seconds_remaining,
+            cancellable: self.update_state.cancel.is_some(),
+            cancelled: self.update_state.cancelled,
+            rollback_available: Updater::rollback_available().await,
+            rolled_back: self.update_state.rolled_back,
+            local_update_archive: self
+                .updater
+                .local_update(Path::new(&self.config.sideload_dir))
+                .await
+                .map(|archive| archive.file_name()),
+            rate_limited: self.update_state.rate_limit.is_some(),
+            rate_limited_until: self
+                .update_state
+                .rate_limit
+                .and_then(|limit| limit.until_label()),
         };
 
         if let Err(err) = self
@@ -118,14 +333,18 @@ impl Backend {
         }
     }
 
-    pub async fn begin_restart_countdown(&mut self, functionality: &BackendReplier<Self>) {
+    pub async fn begin_restart_countdown(
+        &mut self,
+        functionality: &BackendReplier<Self>,
+        message: &str,
+    ) {
         self.update_state.in_progress = false;
         self.update_state.error = None;
         self.update_state.pending_update_url = None;
+        self.update_state.pending_signature_url = None;
         self.update_state.pending_restart = true;
         self.update_state.restart_seconds_remaining = Some(UPDATE_RESTART_DELAY_SECS as u32);
-        self.update_state.progress_message =
-            Some("Update installed. Restarting shortly...".to_string());
+        self.update_state.progress_message = Some(message.to_string());
         self.send_update_status(functionality).await;
         self.schedule_delayed_restart();
     }
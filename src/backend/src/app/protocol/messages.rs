@@ -10,6 +10,15 @@ pub const MSG_UPDATE_DOWNLOAD_REQUEST: u32 = 5;
 pub const MSG_UPDATE_RESTART_REQUEST: u32 = 6;
 pub const MSG_SYNCTHING_UPDATE_CHECK_REQUEST: u32 = 7;
 pub const MSG_SYNCTHING_UPDATE_INSTALL_REQUEST: u32 = 8;
+pub const MSG_INSTALL_CANCEL_REQUEST: u32 = 9;
+pub const MSG_UPDATE_CANCEL_REQUEST: u32 = 10;
+pub const MSG_UPDATE_ROLLBACK_REQUEST: u32 = 11;
+pub const MSG_INSTALL_VERSIONS_REQUEST: u32 = 12;
+pub const MSG_UNINSTALL_REQUEST: u32 = 13;
+pub const MSG_REPAIR_SERVICE_REQUEST: u32 = 14;
+pub const MSG_JOURNAL_REQUEST: u32 = 15;
+pub const MSG_JOURNAL_FOLLOW_REQUEST: u32 = 16;
+pub const MSG_UPDATE_VERSIONS_REQUEST: u32 = 17;
 
 // Response messages (to frontend)
 pub const MSG_STATUS_UPDATE: u32 = 100;
@@ -20,6 +29,10 @@ pub const MSG_UPDATE_CHECK_RESULT: u32 = 104;
 pub const MSG_UPDATE_DOWNLOAD_STATUS: u32 = 105;
 pub const MSG_SYNCTHING_UPDATE_CHECK_RESULT: u32 = 106;
 pub const MSG_SYNCTHING_UPDATE_STATUS: u32 = 107;
+pub const MSG_INSTALL_VERSIONS_RESULT: u32 = 108;
+pub const MSG_JOURNAL_PAGE: u32 = 109;
+pub const MSG_JOURNAL_ENTRY: u32 = 110;
+pub const MSG_UPDATE_VERSIONS_RESULT: u32 = 111;
 pub const MSG_ERROR: u32 = 500;
 
 // Timing constants
@@ -57,6 +70,15 @@ mod tests {
                 "msgSyncthingUpdateInstallRequest",
                 MSG_SYNCTHING_UPDATE_INSTALL_REQUEST,
             ),
+            ("msgInstallCancelRequest", MSG_INSTALL_CANCEL_REQUEST),
+            ("msgUpdateCancelRequest", MSG_UPDATE_CANCEL_REQUEST),
+            ("msgUpdateRollbackRequest", MSG_UPDATE_ROLLBACK_REQUEST),
+            ("msgInstallVersionsRequest", MSG_INSTALL_VERSIONS_REQUEST),
+            ("msgUninstallRequest", MSG_UNINSTALL_REQUEST),
+            ("msgRepairServiceRequest", MSG_REPAIR_SERVICE_REQUEST),
+            ("msgJournalRequest", MSG_JOURNAL_REQUEST),
+            ("msgJournalFollowRequest", MSG_JOURNAL_FOLLOW_REQUEST),
+            ("msgUpdateVersionsRequest", MSG_UPDATE_VERSIONS_REQUEST),
             ("msgStatusUpdate", MSG_STATUS_UPDATE),
             ("msgControlResult", MSG_CONTROL_RESULT),
             ("msgInstallStatus", MSG_INSTALL_STATUS),
@@ -68,6 +90,10 @@ mod tests {
                 MSG_SYNCTHING_UPDATE_CHECK_RESULT,
             ),
             ("msgSyncthingUpdateStatus", MSG_SYNCTHING_UPDATE_STATUS),
+            ("msgInstallVersionsResult", MSG_INSTALL_VERSIONS_RESULT),
+            ("msgJournalPage", MSG_JOURNAL_PAGE),
+            ("msgJournalEntry", MSG_JOURNAL_ENTRY),
+            ("msgUpdateVersionsResult", MSG_UPDATE_VERSIONS_RESULT),
             ("msgError", MSG_ERROR),
         ];
 
@@ -1,6 +1,6 @@
 use serde::Deserialize;
 
-use crate::systemd::ServiceAction;
+use crate::systemd::{JournalFilter, ServiceAction};
 
 #[derive(Debug, Deserialize)]
 pub struct ControlRequest {
@@ -11,3 +11,58 @@ pub struct ControlRequest {
 pub struct GuiAddressToggleRequest {
     pub address: String,
 }
+
+#[derive(Debug, Default, Deserialize)]
+pub struct InstallTriggerRequest {
+    /// Syncthing version to install instead of the pinned or newest one.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Install from the sideload directory instead of downloading.
+    #[serde(default)]
+    pub local: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateDownloadRequest {
+    /// Apply the archive from the sideload directory instead of downloading.
+    #[serde(default)]
+    pub local: bool,
+    /// App version to install instead of the one found by the last check.
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SyncthingUpdateInstallRequest {
+    /// Acknowledges that a major upgrade migrates Syncthing's database.
+    #[serde(default)]
+    pub confirm_major: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UninstallRequest {
+    /// Also delete Syncthing's configuration, keys and database.
+    #[serde(default)]
+    pub wipe_config: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct JournalRequest {
+    /// Cursor of the oldest entry shown; reads the page before it.
+    #[serde(default)]
+    pub before: Option<String>,
+    #[serde(flatten)]
+    pub filter: JournalFilter,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct JournalFollowRequest {
+    /// Starts following when set, stops otherwise.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Cursor of the newest entry shown, so no line is missed in between.
+    #[serde(default)]
+    pub after: Option<String>,
+    #[serde(flatten)]
+    pub filter: JournalFilter,
+}
@@ -18,15 +18,21 @@ impl Backend {
 
         if !task_is_running(&self.systemd_monitor_task) {
             let config = self.config.clone();
+            let runner = self.runner.clone();
             let replier = functionality.clone();
             self.systemd_monitor_task = Some(tokio::spawn(async move {
-                crate::systemd::monitor_service(config, SYSTEMD_MONITOR_INTERVAL_SECS, move || {
-                    let replier = replier.clone();
-                    tokio::spawn(async move {
-                        let mut backend = replier.backend.lock().await;
-                        backend.send_status(&replier, "systemd-monitor").await;
-                    });
-                })
+                crate::systemd::monitor_service(
+                    config,
+                    runner,
+                    SYSTEMD_MONITOR_INTERVAL_SECS,
+                    move || {
+                        let replier = replier.clone();
+                        tokio::spawn(async move {
+                            let mut backend = replier.backend.lock().await;
+                            backend.send_status(&replier, "systemd-monitor").await;
+                        });
+                    },
+                )
                 .await;
             }));
         }
@@ -3,8 +3,9 @@ use tracing::warn;
 
 use crate::config::Config;
 use crate::syncthing_client::SyncthingClient;
-use crate::systemd::query_status;
+use crate::systemd::{diagnose, query_status};
 use crate::types::{MonitorError, StatusPayload, SyncthingOverview};
+use crate::utils::CommandRunner;
 
 /// Builds a complete status payload by aggregating data from multiple sources.
 ///
@@ -14,11 +15,13 @@ use crate::types::{MonitorError, StatusPayload, SyncthingOverview};
 /// - Error handling and fallback values
 pub async fn build_status_payload(
     config: &Config,
+    runner: &dyn CommandRunner,
     client_slot: &mut Option<SyncthingClient>,
     reason: &str,
 ) -> StatusPayload {
     let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
-    let systemd = query_status(config).await;
+    let mut systemd = query_status(config, runner).await;
+    systemd.diagnosis = diagnose(runner, &systemd).await;
 
     let (syncthing, folders, peers, gui_address) = match ensure_client(config, client_slot).await {
         Ok(client) => {
@@ -2,9 +2,10 @@ use serde_json::Value;
 use tokio::fs;
 use tracing::{info, warn};
 
+use crate::deployment::http::client::{default_request_timeout, release_client};
 use crate::types::MonitorError;
 
-use super::{paths, Config};
+use super::{paths, Config, ServiceProfile, ServiceTuning};
 
 impl Config {
     /// Load configuration from config.json in the app directory
@@ -50,6 +51,144 @@ impl Config {
             config.disable_syncthing_installer = true;
         }
 
+        config.drop_invalid_settings();
+
         Ok(config)
     }
+
+    /// Resets settings that cannot be used to their defaults, so one bad value
+    /// does not discard the rest of config.json.
+    fn drop_invalid_settings(&mut self) {
+        self.pinned_syncthing_version = self
+            .pinned_syncthing_version
+            .take()
+            .and_then(|pinned| valid_pin(pinned, "pinned_syncthing_version"));
+        self.pinned_app_version = self
+            .pinned_app_version
+            .take()
+            .and_then(|pinned| valid_pin(pinned, "pinned_app_version"));
+
+        if let Err(err) = self.service_tuning.validate() {
+            warn!(error = ?err, "Ignoring invalid service_tuning");
+            self.service_tuning = ServiceTuning::default();
+        }
+
+        // The user only exists under the hardened profile.
+        if self.service_profile == ServiceProfile::Hardened {
+            let user = &self.service_user;
+            let valid_user = user
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+                && user
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+            if !valid_user || user == "root" {
+                let fallback = Config::default().service_user;
+                warn!(user = %user, fallback = %fallback, "Ignoring invalid service_user");
+                self.service_user = fallback;
+            }
+        }
+
+        // Checked here rather than failing every release request later.
+        if self.http_proxy.is_some() {
+            let proxy_only = Config {
+                ca_bundle_path: None,
+                ..self.clone()
+            };
+            if let Err(err) = release_client(default_request_timeout(), &proxy_only) {
+                warn!(error = ?err, "Ignoring unusable http_proxy");
+                self.http_proxy = None;
+            }
+        }
+        if self.ca_bundle_path.is_some() {
+            if let Err(err) = release_client(default_request_timeout(), self) {
+                warn!(error = ?err, "Ignoring unusable ca_bundle_path");
+                self.ca_bundle_path = None;
+            }
+        }
+    }
+}
+
+/// Normalises a pinned version to plain semver, or drops it with a warning.
+fn valid_pin(pinned: String, setting: &str) -> Option<String> {
+    let version = pinned.trim().trim_start_matches('v');
+    match semver::Version::parse(version) {
+        Ok(_) => Some(version.to_string()),
+        Err(err) => {
+            warn!(pinned = %pinned, error = %err, "Ignoring invalid {}", setting);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_pin_is_dropped_without_losing_other_settings() {
+        let mut config = Config {
+            pinned_syncthing_version: Some("latest".to_string()),
+            systemd_service_name: "syncthing@root.service".to_string(),
+            ..Config::default()
+        };
+        config.drop_invalid_settings();
+        assert_eq!(config.pinned_syncthing_version, None);
+        assert_eq!(config.systemd_service_name, "syncthing@root.service");
+
+        config.pinned_syncthing_version = Some(" v1.27.12".to_string());
+        config.pinned_app_version = Some("next".to_string());
+        config.drop_invalid_settings();
+        assert_eq!(config.pinned_syncthing_version.as_deref(), Some("1.27.12"));
+        assert_eq!(config.pinned_app_version, None);
+    }
+
+    #[test]
+    fn unreadable_ca_bundle_is_dropped_but_the_proxy_is_kept() {
+        let mut config = Config {
+            http_proxy: Some("http://proxy.example:3128".to_string()),
+            ca_bundle_path: Some("/nonexistent/ca-bundle.pem".to_string()),
+            disable_syncthing_installer: true,
+            ..Config::default()
+        };
+        config.drop_invalid_settings();
+
+        assert_eq!(
+            config.http_proxy.as_deref(),
+            Some("http://proxy.example:3128")
+        );
+        assert_eq!(config.ca_bundle_path, None);
+        assert!(config.disable_syncthing_installer);
+    }
+
+    #[test]
+    fn invalid_tuning_is_reset_as_a_whole() {
+        let mut config = Config {
+            service_tuning: ServiceTuning {
+                nice: Some(10),
+                cpu_quota: Some("half".to_string()),
+                ..ServiceTuning::default()
+            },
+            ..Config::default()
+        };
+        config.drop_invalid_settings();
+
+        assert!(config.service_tuning.is_empty());
+    }
+
+    #[test]
+    fn service_user_is_only_checked_for_the_hardened_profile() {
+        let mut config = Config {
+            service_user: "root".to_string(),
+            ..Config::default()
+        };
+        config.drop_invalid_settings();
+        assert_eq!(config.service_user, "root");
+
+        config.service_profile = ServiceProfile::Hardened;
+        config.drop_invalid_settings();
+        assert_eq!(config.service_user, "syncthing");
+        assert_eq!(config.service_profile, ServiceProfile::Hardened);
+    }
 }
@@ -3,4 +3,7 @@ mod paths;
 mod types;
 
 // Re-export the main Config type
-pub use types::Config;
+pub use types::{
+    Config, ProvisioningConfig, ReleaseChannel, ReleaseSource, ServiceProfile, ServiceTuning,
+    SyncthingUpgradeMethod,
+};
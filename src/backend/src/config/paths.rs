@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::sync::OnceLock;
 use tracing::{debug, warn};
 
 use crate::types::MonitorError;
@@ -32,9 +33,18 @@ impl Config {
     }
 }
 
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
 /// Get the path to the config.json file
 /// Looks for config.json in the app directory (parent of backend folder)
+///
+/// Resolved once per process: after an update swaps the app directory, the running
+/// executable's path points into the retired tree.
 pub(super) fn get_config_path() -> Result<PathBuf, MonitorError> {
+    Ok(CONFIG_PATH.get_or_init(resolve_config_path).clone())
+}
+
+fn resolve_config_path() -> PathBuf {
     // Try to get the executable path
     // Executable is at: app_root/backend/entry
     // Config should be at: app_root/config.json
@@ -48,12 +58,12 @@ pub(super) fn get_config_path() -> Result<PathBuf, MonitorError> {
             if let Some(app_root) = backend_dir.parent() {
                 let config_path = app_root.join("config.json");
                 debug!(path = %config_path.display(), "Looking for config");
-                return Ok(config_path);
+                return config_path;
             }
         }
     }
 
     // Fallback: look in current directory
     warn!("Using fallback: looking for config.json in current directory");
-    Ok(PathBuf::from("config.json"))
+    PathBuf::from("config.json")
 }
@@ -1,5 +1,9 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::types::MonitorError;
+
 /// Configuration for the Syncthing monitor application
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -11,6 +15,277 @@ pub struct Config {
 
     #[serde(default)]
     pub disable_syncthing_installer: bool,
+
+    /// Reinstalls the unit on start when an OS update removed it.
+    #[serde(default = "default_true")]
+    pub restore_service_after_os_update: bool,
+
+    #[serde(default)]
+    pub release_channel: ReleaseChannel,
+
+    /// Syncthing version the installer fetches instead of the newest release.
+    #[serde(default)]
+    pub pinned_syncthing_version: Option<String>,
+
+    /// App version the updater offers instead of the newest release.
+    #[serde(default)]
+    pub pinned_app_version: Option<String>,
+
+    #[serde(default)]
+    pub syncthing_upgrade_method: SyncthingUpgradeMethod,
+
+    /// Directory searched for release archives when installing offline.
+    #[serde(default = "default_sideload_dir")]
+    pub sideload_dir: String,
+
+    #[serde(default = "default_syncthing_release_source")]
+    pub syncthing_release_source: ReleaseSource,
+
+    #[serde(default = "default_app_release_source")]
+    pub app_release_source: ReleaseSource,
+
+    /// Proxy for all release requests, e.g. `http://proxy.example:3128`.
+    #[serde(default)]
+    pub http_proxy: Option<String>,
+
+    /// PEM file with extra root certificates, for TLS-intercepting proxies and mirrors.
+    #[serde(default)]
+    pub ca_bundle_path: Option<String>,
+
+    /// Only sent to the GitHub API of a `github` release source.
+    #[serde(default)]
+    pub github_token: Option<SecretString>,
+
+    #[serde(default)]
+    pub provisioning: ProvisioningConfig,
+
+    #[serde(default)]
+    pub service_tuning: ServiceTuning,
+
+    #[serde(default)]
+    pub service_profile: ServiceProfile,
+
+    /// Account the service runs as under the hardened profile, created if missing.
+    #[serde(default = "default_service_user")]
+    pub service_user: String,
+}
+
+/// Settings applied to a freshly installed Syncthing instance.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvisioningConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Device name; `{hostname}` and `{serial}` are replaced with the tablet's values.
+    #[serde(default = "default_device_name")]
+    pub device_name: String,
+
+    /// Drops the "Default Folder" Syncthing creates on first start.
+    #[serde(default)]
+    pub remove_default_folder: bool,
+
+    #[serde(default = "default_gui_user")]
+    pub gui_user: String,
+
+    /// GUI credentials are only set when a password is configured.
+    #[serde(default)]
+    pub gui_password: Option<SecretString>,
+
+    #[serde(default = "default_gui_address")]
+    pub gui_address: String,
+}
+
+/// Resource limits and environment for the Syncthing service, written to a
+/// drop-in next to the unit so reinstalls keep them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceTuning {
+    /// `Nice=`, from -20 (highest priority) to 19.
+    #[serde(default)]
+    pub nice: Option<i32>,
+
+    #[serde(default)]
+    pub io_scheduling_class: Option<IoSchedulingClass>,
+
+    /// `CPUQuota=`, e.g. `"50%"`.
+    #[serde(default)]
+    pub cpu_quota: Option<String>,
+
+    /// `MemoryMax=`, e.g. `"256M"`.
+    #[serde(default)]
+    pub memory_max: Option<String>,
+
+    /// Extra `Environment=` entries, e.g. `GOMAXPROCS` or `GOMEMLIMIT`.
+    #[serde(default)]
+    pub environment: BTreeMap<String, String>,
+
+    /// Arguments appended to `syncthing serve`.
+    #[serde(default)]
+    pub extra_serve_args: Vec<String>,
+}
+
+impl ServiceTuning {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Rejects values that systemd would refuse or that would break the drop-in.
+    pub fn validate(&self) -> Result<(), MonitorError> {
+        if let Some(nice) = self.nice {
+            if !(-20..=19).contains(&nice) {
+                return Err(MonitorError::Config(format!(
+                    "service_tuning.nice must be between -20 and 19, got {nice}"
+                )));
+            }
+        }
+        if let Some(quota) = &self.cpu_quota {
+            let percent = quota
+                .strip_suffix('%')
+                .and_then(|value| value.parse::<u32>().ok());
+            if percent.is_none_or(|value| value == 0) {
+                return Err(MonitorError::Config(format!(
+                    "service_tuning.cpu_quota must be a percentage like \"50%\", got '{quota}'"
+                )));
+            }
+        }
+        if let Some(memory) = &self.memory_max {
+            let digits = memory.trim_end_matches(['K', 'M', 'G', 'T']);
+            if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                return Err(MonitorError::Config(format!(
+                    "service_tuning.memory_max must be a size like \"256M\", got '{memory}'"
+                )));
+            }
+        }
+        for (name, value) in &self.environment {
+            let valid_name = name
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid_name || value.contains(['\n', '"', '\\']) {
+                return Err(MonitorError::Config(format!(
+                    "Invalid service_tuning.environment entry '{name}'"
+                )));
+            }
+        }
+        if let Some(arg) = self
+            .extra_serve_args
+            .iter()
+            .find(|arg| arg.is_empty() || arg.contains(['\n', '"', '\\']))
+        {
+            return Err(MonitorError::Config(format!(
+                "Invalid service_tuning.extra_serve_args entry '{arg}'"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// User and sandboxing of the generated Syncthing service.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceProfile {
+    /// Runs as root with full filesystem access.
+    #[default]
+    Root,
+    /// Runs as `service_user`, with writes limited to the Syncthing home and the
+    /// synced folders.
+    Hardened,
+}
+
+/// `IOSchedulingClass=` of the Syncthing service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoSchedulingClass {
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+impl std::fmt::Display for IoSchedulingClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IoSchedulingClass::Realtime => f.write_str("realtime"),
+            IoSchedulingClass::BestEffort => f.write_str("best-effort"),
+            IoSchedulingClass::Idle => f.write_str("idle"),
+        }
+    }
+}
+
+/// How Syncthing updates from the settings screen are applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncthingUpgradeMethod {
+    /// Syncthing's own `/rest/system/upgrade`, which replaces the binary itself.
+    #[default]
+    Builtin,
+    /// The app downloads the release, swaps the binary and restarts the service,
+    /// restoring the previous binary if the new one does not come up.
+    App,
+}
+
+/// Where release metadata is fetched from.
+///
+/// Every source yields releases in GitHub's format: `tag_name`, `prerelease`
+/// and `assets` with `name` and `browser_download_url`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReleaseSource {
+    /// The releases of a GitHub repository, read through the REST API.
+    Github {
+        repo: String,
+        #[serde(default = "default_github_api_url")]
+        api_url: String,
+    },
+    /// A JSON array of releases, e.g. on an internal mirror. Relative asset URLs
+    /// are resolved against the index URL.
+    Index { url: String },
+    /// Syncthing's upgrade metadata, as used by its built-in upgrader.
+    SyncthingMeta {
+        #[serde(default = "default_syncthing_meta_url")]
+        url: String,
+    },
+}
+
+/// A string kept out of `Debug` output.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("\"<redacted>\"")
+    }
+}
+
+/// Which GitHub releases are offered for installs and updates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseChannel {
+    #[default]
+    Stable,
+    /// Also offers releases marked as pre-releases, such as Syncthing's `-rc` builds.
+    Candidate,
+}
+
+impl ReleaseChannel {
+    pub fn allows_prerelease(self) -> bool {
+        matches!(self, ReleaseChannel::Candidate)
+    }
+}
+
+impl std::fmt::Display for ReleaseChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReleaseChannel::Stable => f.write_str("stable"),
+            ReleaseChannel::Candidate => f.write_str("candidate"),
+        }
+    }
 }
 
 impl Default for Config {
@@ -19,6 +294,34 @@ impl Default for Config {
             systemd_service_name: default_service_name(),
             syncthing_config_dir: default_config_dir(),
             disable_syncthing_installer: false,
+            restore_service_after_os_update: true,
+            release_channel: ReleaseChannel::default(),
+            pinned_syncthing_version: None,
+            pinned_app_version: None,
+            syncthing_upgrade_method: SyncthingUpgradeMethod::default(),
+            sideload_dir: default_sideload_dir(),
+            syncthing_release_source: default_syncthing_release_source(),
+            app_release_source: default_app_release_source(),
+            http_proxy: None,
+            ca_bundle_path: None,
+            github_token: None,
+            provisioning: ProvisioningConfig::default(),
+            service_tuning: ServiceTuning::default(),
+            service_profile: ServiceProfile::default(),
+            service_user: default_service_user(),
+        }
+    }
+}
+
+impl Default for ProvisioningConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            device_name: default_device_name(),
+            remove_default_folder: false,
+            gui_user: default_gui_user(),
+            gui_password: None,
+            gui_address: default_gui_address(),
         }
     }
 }
@@ -27,6 +330,52 @@ fn default_service_name() -> String {
     "syncthing.service".to_string()
 }
 
+fn default_service_user() -> String {
+    "syncthing".to_string()
+}
+
 fn default_config_dir() -> String {
     "/home/root/.config/syncthing".to_string()
 }
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_device_name() -> String {
+    "{hostname} {serial}".to_string()
+}
+
+fn default_gui_user() -> String {
+    "syncthing".to_string()
+}
+
+fn default_gui_address() -> String {
+    "127.0.0.1:8384".to_string()
+}
+
+fn default_sideload_dir() -> String {
+    "/home/root/sideload".to_string()
+}
+
+fn default_syncthing_release_source() -> ReleaseSource {
+    ReleaseSource::Github {
+        repo: "syncthing/syncthing".to_string(),
+        api_url: default_github_api_url(),
+    }
+}
+
+fn default_app_release_source() -> ReleaseSource {
+    ReleaseSource::Github {
+        repo: "paviro/Syncthing-for-reMarkable".to_string(),
+        api_url: default_github_api_url(),
+    }
+}
+
+fn default_github_api_url() -> String {
+    "https://api.github.com".to_string()
+}
+
+fn default_syncthing_meta_url() -> String {
+    "https://upgrades.syncthing.net/meta.json".to_string()
+}
@@ -1,26 +1,85 @@
 //! Helpers for selecting release assets for installer and updater flows.
 
-use reqwest::Client;
 use serde::Deserialize;
 
-use crate::types::MonitorError;
+use crate::config::ReleaseChannel;
+use crate::deployment::{AvailableVersion, AvailableVersions};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Release {
     pub tag_name: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
     pub assets: Vec<ReleaseAsset>,
 }
 
+impl Release {
+    /// The release tag as a semantic version, without the leading `v`.
+    pub fn version(&self) -> Option<semver::Version> {
+        semver::Version::parse(self.tag_name.trim_start_matches('v')).ok()
+    }
+
+    fn offered_on(&self, channel: ReleaseChannel) -> bool {
+        !self.draft && (!self.prerelease || channel.allows_prerelease())
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct ReleaseAsset {
     pub name: String,
     pub browser_download_url: String,
 }
 
-pub async fn fetch_release(client: &Client, url: &str) -> Result<Release, MonitorError> {
-    let response = client.get(url).send().await?.error_for_status()?;
-    let release: Release = response.json().await?;
-    Ok(release)
+/// Returns the releases offered on `channel`, newest version first.
+pub fn releases_for_channel(releases: &[Release], channel: ReleaseChannel) -> Vec<&Release> {
+    let mut offered: Vec<(semver::Version, &Release)> = releases
+        .iter()
+        .filter(|release| release.offered_on(channel))
+        .filter_map(|release| release.version().map(|version| (version, release)))
+        .collect();
+    offered.sort_by(|(a, _), (b, _)| b.cmp(a));
+    offered.into_iter().map(|(_, release)| release).collect()
+}
+
+/// Returns the newest release offered on `channel`.
+pub fn select_latest_release(releases: &[Release], channel: ReleaseChannel) -> Option<&Release> {
+    releases_for_channel(releases, channel).into_iter().next()
+}
+
+/// Lists the versions offered on `channel`, plus `pinned` when the channel
+/// does not offer it. The pin is the default, otherwise the newest release.
+pub fn available_versions(
+    releases: &[Release],
+    channel: ReleaseChannel,
+    pinned: Option<&str>,
+) -> AvailableVersions {
+    let mut versions: Vec<AvailableVersion> = releases_for_channel(releases, channel)
+        .into_iter()
+        .map(|release| AvailableVersion {
+            version: release.tag_name.trim_start_matches('v').to_string(),
+            prerelease: release.prerelease,
+        })
+        .collect();
+    let latest = versions.first().map(|entry| entry.version.clone());
+
+    let pinned_version = pinned.map(str::to_string);
+    if let Some(pinned) = &pinned_version {
+        if !versions.iter().any(|entry| &entry.version == pinned) {
+            versions.push(AvailableVersion {
+                version: pinned.clone(),
+                prerelease: pinned.contains('-'),
+            });
+        }
+    }
+
+    AvailableVersions {
+        channel,
+        default_version: pinned_version.clone().or(latest),
+        pinned_version,
+        versions,
+    }
 }
 
 pub fn select_asset_by_prefix<'a>(
@@ -36,3 +95,67 @@ pub fn select_asset_by_prefix<'a>(
 pub fn select_asset_exact<'a>(assets: &'a [ReleaseAsset], name: &str) -> Option<&'a ReleaseAsset> {
     assets.iter().find(|asset| asset.name == name)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(tag: &str, prerelease: bool, draft: bool) -> Release {
+        Release {
+            tag_name: tag.to_string(),
+            prerelease,
+            draft,
+            assets: Vec::new(),
+        }
+    }
+
+    fn sample_releases() -> Vec<Release> {
+        vec![
+            release("v2.0.1-rc.1", true, false),
+            release("v1.30.0", false, false),
+            release("v2.1.0", false, true),
+            release("v2.0.0", false, false),
+            release("nightly", false, false),
+        ]
+    }
+
+    #[test]
+    fn stable_channel_skips_prereleases_drafts_and_unversioned_tags() {
+        let releases = sample_releases();
+
+        let tags: Vec<&str> = releases_for_channel(&releases, ReleaseChannel::Stable)
+            .into_iter()
+            .map(|release| release.tag_name.as_str())
+            .collect();
+
+        assert_eq!(tags, ["v2.0.0", "v1.30.0"]);
+    }
+
+    #[test]
+    fn candidate_channel_offers_newest_prerelease() {
+        let releases = sample_releases();
+
+        let latest = select_latest_release(&releases, ReleaseChannel::Candidate)
+            .expect("select candidate release");
+
+        assert_eq!(latest.tag_name, "v2.0.1-rc.1");
+    }
+
+    #[test]
+    fn pinned_version_outside_channel_is_listed_and_default() {
+        let releases = sample_releases();
+
+        let available = available_versions(&releases, ReleaseChannel::Stable, Some("1.27.12"));
+
+        let versions: Vec<&str> = available
+            .versions
+            .iter()
+            .map(|entry| entry.version.as_str())
+            .collect();
+        assert_eq!(versions, ["2.0.0", "1.30.0", "1.27.12"]);
+        assert_eq!(available.default_version.as_deref(), Some("1.27.12"));
+
+        let available = available_versions(&releases, ReleaseChannel::Stable, None);
+        assert_eq!(available.default_version.as_deref(), Some("2.0.0"));
+    }
+}
@@ -0,0 +1,95 @@
+//! On-disk cache of release metadata responses, revalidated with `ETag`s.
+//!
+//! GitHub does not count `304 Not Modified` answers against the rate limit, so
+//! repeated checks only cost a request when something was published.
+
+use std::path::{Path, PathBuf};
+
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::warn;
+
+use crate::types::MonitorError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub url: String,
+    pub etag: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Returns the cached response for `url`, ignoring unreadable entries.
+    pub async fn load(&self, url: &str) -> Option<CachedResponse> {
+        let contents = fs::read(self.entry_path(url)).await.ok()?;
+        let cached: CachedResponse = serde_json::from_slice(&contents).ok()?;
+        (cached.url == url).then_some(cached)
+    }
+
+    pub async fn store(&self, url: &str, etag: &str, body: &str) {
+        let entry = CachedResponse {
+            url: url.to_string(),
+            etag: etag.to_string(),
+            body: body.to_string(),
+        };
+        if let Err(err) = self.write_entry(&entry).await {
+            warn!(url, error = ?err, "Failed to cache release metadata");
+        }
+    }
+
+    async fn write_entry(&self, entry: &CachedResponse) -> Result<(), MonitorError> {
+        fs::create_dir_all(&self.dir).await?;
+        let path = self.entry_path(&entry.url);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec(entry)?).await?;
+        fs::rename(&tmp_path, &path).await?;
+        Ok(())
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        entry_path(&self.dir, url)
+    }
+}
+
+fn entry_path(dir: &Path, url: &str) -> PathBuf {
+    let hash = digest(&SHA256, url.as_bytes());
+    let name: String = hash.as_ref()[..16]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    dir.join(format!("{}.json", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stored_response_is_returned_for_same_url_only() {
+        let dir = tempfile::tempdir().expect("create cache dir");
+        let cache = ResponseCache::new(dir.path().join("releases"));
+        let url = "https://api.github.com/repos/syncthing/syncthing/releases?per_page=100";
+
+        cache.store(url, "W/\"abc\"", "[]").await;
+
+        assert_eq!(
+            cache.load(url).await,
+            Some(CachedResponse {
+                url: url.to_string(),
+                etag: "W/\"abc\"".to_string(),
+                body: "[]".to_string(),
+            })
+        );
+        assert!(cache.load("https://example.com/other.json").await.is_none());
+    }
+}
@@ -1,9 +1,10 @@
 //! HTTP client utilities for deployment-related workflows.
 
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT};
-use reqwest::Client;
+use reqwest::{Certificate, Client, Proxy};
 use std::time::Duration;
 
+use crate::config::Config;
 use crate::types::MonitorError;
 
 const USER_AGENT: &str = "syncthing-for-remarkable-appload";
@@ -22,13 +23,38 @@ pub fn default_github_headers() -> HeaderMap {
     headers
 }
 
-pub fn github_client(timeout: Duration) -> Result<Client, MonitorError> {
-    Client::builder()
-        .user_agent(USER_AGENT)
-        .default_headers(default_github_headers())
-        .timeout(timeout)
-        .build()
-        .map_err(Into::into)
+/// Builds the client for release metadata and downloads, honouring the proxy
+/// and CA bundle settings from `config`.
+pub fn release_client(timeout: Duration, config: &Config) -> Result<Client, MonitorError> {
+    let mut builder = Client::builder().user_agent(USER_AGENT).timeout(timeout);
+
+    if let Some(proxy) = &config.http_proxy {
+        let proxy = Proxy::all(proxy).map_err(|err| {
+            MonitorError::Config(format!("Invalid http_proxy '{}': {}", proxy, err))
+        })?;
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(path) = &config.ca_bundle_path {
+        let pem = std::fs::read(path).map_err(|err| {
+            MonitorError::Config(format!("Failed to read ca_bundle_path '{}': {}", path, err))
+        })?;
+        let certificates = Certificate::from_pem_bundle(&pem).map_err(|err| {
+            MonitorError::Config(format!("Invalid CA bundle '{}': {}", path, err))
+        })?;
+        if certificates.is_empty() {
+            return Err(MonitorError::Config(format!(
+                "CA bundle '{}' contains no certificates",
+                path
+            )));
+        }
+        // Added on top of the built-in roots, which still cover public hosts.
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    builder.build().map_err(Into::into)
 }
 
 pub fn default_request_timeout() -> Duration {
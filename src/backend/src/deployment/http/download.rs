@@ -1,53 +1,424 @@
 //! Shared download and extraction helpers for deployment workflows.
 
-use reqwest::Client;
-use std::path::Path;
+use reqwest::header::{HeaderMap, CONTENT_RANGE, ETAG, IF_RANGE, RANGE};
+use reqwest::{Client, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use tokio::fs::File;
+use tokio::fs::{self, File, OpenOptions};
 use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
 
-use crate::deployment::{DownloadProgress, DownloadProgressSender};
+use crate::deployment::{CancelToken, DownloadProgress, DownloadProgressSender};
 use crate::types::MonitorError;
 
 const DOWNLOAD_TIMEOUT_SECS: u64 = 10 * 60;
+const PARTIAL_SUFFIX: &str = ".part";
+const PARTIAL_METADATA_SUFFIX: &str = ".part.json";
 
+/// Describes an interrupted download kept next to its destination.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct PartialDownload {
+    url: String,
+    etag: Option<String>,
+    total_bytes: Option<u64>,
+}
+
+/// Downloads `url` into `destination`.
+///
+/// Interrupted transfers leave a `.part` file plus metadata behind and are resumed
+/// with an HTTP Range request on the next call. When `cancel` fires, the transfer is
+/// aborted and all partial data is removed.
 pub async fn download_to_path(
     client: &Client,
     url: &str,
     destination: &Path,
     progress_tx: Option<DownloadProgressSender>,
+    cancel: Option<&CancelToken>,
 ) -> Result<(), MonitorError> {
-    let timeout = Duration::from_secs(DOWNLOAD_TIMEOUT_SECS);
-    let request = client.get(url).timeout(timeout);
+    let result = match cancel {
+        Some(cancel) => {
+            tokio::select! {
+                result = stream_to_file(client, url, destination, progress_tx) => {
+                    // A cancel that races with the last chunk still wins.
+                    if cancel.is_cancelled() {
+                        Err(MonitorError::Cancelled)
+                    } else {
+                        result
+                    }
+                }
+                _ = cancel.cancelled() => Err(MonitorError::Cancelled),
+            }
+        }
+        None => stream_to_file(client, url, destination, progress_tx).await,
+    };
 
-    let mut response = request.send().await?.error_for_status()?;
-    let mut file = File::create(destination).await?;
-    let mut downloaded_bytes: u64 = 0;
-    let total_bytes = response.content_length();
+    if matches!(result, Err(MonitorError::Cancelled)) {
+        discard_download(destination).await;
+    }
 
-    emit_progress(progress_tx.as_ref(), downloaded_bytes, total_bytes).await;
+    result
+}
+
+async fn stream_to_file(
+    client: &Client,
+    url: &str,
+    destination: &Path,
+    progress_tx: Option<DownloadProgressSender>,
+) -> Result<(), MonitorError> {
+    let partial_path = partial_path(destination);
+    let metadata_path = partial_metadata_path(destination);
+    let mut resume = load_resume_point(url, &partial_path, &metadata_path).await;
+
+    let mut response = send_request(client, url, resume.as_ref()).await?;
+    if resume.is_some() && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        info!(url, "Server rejected resume range, restarting download");
+        resume = None;
+        response = send_request(client, url, None).await?;
+    }
+    let mut response = response.error_for_status()?;
+
+    let (mut file, resumed_from, total_bytes, etag) = match resume {
+        Some((offset, previous)) if response.status() == StatusCode::PARTIAL_CONTENT => {
+            let total_bytes = match parse_content_range(response.headers()) {
+                Some((start, total)) if start == offset => total.or(previous.total_bytes),
+                _ => {
+                    discard_partial(&partial_path, &metadata_path).await;
+                    return Err(MonitorError::Config(format!(
+                        "Server answered resume of {} with an unexpected range",
+                        url
+                    )));
+                }
+            };
+            let file = OpenOptions::new().append(true).open(&partial_path).await?;
+            let etag = response_etag(&response).or(previous.etag);
+            info!(url, offset, "Resuming interrupted download");
+            (file, offset, total_bytes, etag)
+        }
+        _ => {
+            let file = File::create(&partial_path).await?;
+            (file, 0, response.content_length(), response_etag(&response))
+        }
+    };
+
+    let metadata = PartialDownload {
+        url: url.to_string(),
+        etag,
+        total_bytes,
+    };
+    fs::write(&metadata_path, serde_json::to_vec(&metadata)?).await?;
+
+    let mut downloaded_bytes = resumed_from;
+    emit_progress(
+        progress_tx.as_ref(),
+        downloaded_bytes,
+        total_bytes,
+        resumed_from,
+    )
+    .await;
 
     while let Some(chunk) = response.chunk().await? {
         file.write_all(&chunk).await?;
         downloaded_bytes = downloaded_bytes.saturating_add(chunk.len() as u64);
-        emit_progress(progress_tx.as_ref(), downloaded_bytes, total_bytes).await;
+        emit_progress(
+            progress_tx.as_ref(),
+            downloaded_bytes,
+            total_bytes,
+            resumed_from,
+        )
+        .await;
     }
 
     file.flush().await?;
+    drop(file);
+
+    if let Some(total) = total_bytes {
+        if downloaded_bytes != total {
+            return Err(MonitorError::Config(format!(
+                "Download of {} ended after {} of {} bytes",
+                url, downloaded_bytes, total
+            )));
+        }
+    }
+
+    fs::rename(&partial_path, destination).await?;
+    let _ = fs::remove_file(&metadata_path).await;
     Ok(())
 }
 
+async fn send_request(
+    client: &Client,
+    url: &str,
+    resume: Option<&(u64, PartialDownload)>,
+) -> Result<Response, MonitorError> {
+    let timeout = Duration::from_secs(DOWNLOAD_TIMEOUT_SECS);
+    let mut request = client.get(url).timeout(timeout);
+
+    if let Some((offset, previous)) = resume {
+        request = request.header(RANGE, format!("bytes={}-", offset));
+        if let Some(etag) = &previous.etag {
+            request = request.header(IF_RANGE, etag);
+        }
+    }
+
+    Ok(request.send().await?)
+}
+
+/// Returns the offset to resume from when a matching partial download exists.
+async fn load_resume_point(
+    url: &str,
+    partial_path: &Path,
+    metadata_path: &Path,
+) -> Option<(u64, PartialDownload)> {
+    let partial_len = fs::metadata(partial_path).await.ok()?.len();
+    let contents = fs::read(metadata_path).await.ok()?;
+    let previous: PartialDownload = match serde_json::from_slice(&contents) {
+        Ok(previous) => previous,
+        Err(err) => {
+            warn!(path = %metadata_path.display(), error = ?err, "Ignoring unreadable partial download metadata");
+            return None;
+        }
+    };
+
+    if previous.url != url || partial_len == 0 {
+        return None;
+    }
+    if previous
+        .total_bytes
+        .map(|total| partial_len >= total)
+        .unwrap_or(false)
+    {
+        return None;
+    }
+
+    Some((partial_len, previous))
+}
+
+/// Parses `Content-Range: bytes <start>-<end>/<total>` into (start, total).
+fn parse_content_range(headers: &HeaderMap) -> Option<(u64, Option<u64>)> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    let range = value.trim().strip_prefix("bytes ")?;
+    let (span, total) = range.split_once('/')?;
+    let (start, _end) = span.split_once('-')?;
+    let start = start.trim().parse::<u64>().ok()?;
+    let total = total.trim().parse::<u64>().ok();
+    Some((start, total))
+}
+
+fn response_etag(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get(ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+fn partial_path(destination: &Path) -> PathBuf {
+    path_with_suffix(destination, PARTIAL_SUFFIX)
+}
+
+fn partial_metadata_path(destination: &Path) -> PathBuf {
+    path_with_suffix(destination, PARTIAL_METADATA_SUFFIX)
+}
+
+fn path_with_suffix(destination: &Path, suffix: &str) -> PathBuf {
+    let mut name = destination
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    name.push(suffix);
+    destination.with_file_name(name)
+}
+
+async fn discard_partial(partial_path: &Path, metadata_path: &Path) {
+    for path in [partial_path, metadata_path] {
+        match fs::remove_file(path).await {
+            Ok(()) => info!(path = %path.display(), "Removed partial download"),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                warn!(path = %path.display(), error = ?err, "Failed to remove partial download")
+            }
+        }
+    }
+}
+
+async fn discard_download(destination: &Path) {
+    discard_partial(
+        &partial_path(destination),
+        &partial_metadata_path(destination),
+    )
+    .await;
+    let _ = fs::remove_file(destination).await;
+}
+
 async fn emit_progress(
     progress_tx: Option<&DownloadProgressSender>,
     downloaded_bytes: u64,
     total_bytes: Option<u64>,
+    resumed_from: u64,
 ) {
     if let Some(progress_tx) = progress_tx {
         let _ = progress_tx
             .send(DownloadProgress {
                 downloaded_bytes,
                 total_bytes,
+                resumed_from,
             })
             .await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use super::*;
+
+    const BODY: &[u8] = b"0123456789abcdef";
+
+    fn read_request(stream: &mut TcpStream) -> String {
+        let mut buffer = [0_u8; 2048];
+        let read = stream.read(&mut buffer).unwrap_or(0);
+        String::from_utf8_lossy(&buffer[..read]).to_lowercase()
+    }
+
+    #[tokio::test]
+    async fn cancelled_download_removes_partial_file() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind test listener");
+        let address = listener.local_addr().expect("read local addr");
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept request");
+            read_request(&mut stream);
+            // Announce more bytes than we send so the transfer stalls mid-body.
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1048576\r\n\r\npartial-bytes");
+            thread::sleep(Duration::from_secs(5));
+        });
+
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let destination = temp_dir.path().join("syncthing.tar.gz");
+        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::channel::<DownloadProgress>(16);
+        let cancel = CancelToken::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            while let Some(progress) = progress_rx.recv().await {
+                if progress.downloaded_bytes > 0 {
+                    canceller.cancel();
+                }
+            }
+        });
+
+        let result = download_to_path(
+            &Client::new(),
+            &format!("http://{}/syncthing.tar.gz", address),
+            &destination,
+            Some(progress_tx),
+            Some(&cancel),
+        )
+        .await;
+
+        assert!(matches!(result, Err(MonitorError::Cancelled)));
+        assert!(!destination.exists());
+        assert!(!partial_path(&destination).exists());
+        assert!(!partial_metadata_path(&destination).exists());
+    }
+
+    #[tokio::test]
+    async fn interrupted_download_resumes_with_range_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind test listener");
+        let address = listener.local_addr().expect("read local addr");
+        let server = thread::spawn(move || {
+            let (mut first, _) = listener.accept().expect("accept first request");
+            read_request(&mut first);
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\r\n",
+                BODY.len()
+            );
+            first.write_all(head.as_bytes()).expect("write head");
+            first.write_all(&BODY[..6]).expect("write first half");
+            drop(first);
+
+            let (mut second, _) = listener.accept().expect("accept resume request");
+            let request = read_request(&mut second);
+            let head = format!(
+                "HTTP/1.1 206 Partial Content\r\nETag: \"v1\"\r\nContent-Range: bytes 6-{}/{}\r\nContent-Length: {}\r\n\r\n",
+                BODY.len() - 1,
+                BODY.len(),
+                BODY.len() - 6
+            );
+            second.write_all(head.as_bytes()).expect("write head");
+            second.write_all(&BODY[6..]).expect("write remainder");
+            request
+        });
+
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let destination = temp_dir.path().join("update.zip");
+        let url = format!("http://{}/update.zip", address);
+        let client = Client::new();
+
+        let first = download_to_path(&client, &url, &destination, None, None).await;
+        assert!(first.is_err());
+        assert!(partial_path(&destination).exists());
+
+        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::channel::<DownloadProgress>(16);
+        download_to_path(&client, &url, &destination, Some(progress_tx), None)
+            .await
+            .expect("resume download");
+
+        let resume_request = server.join().expect("join server");
+        assert!(resume_request.contains("range: bytes=6-"));
+        assert!(resume_request.contains("if-range: \"v1\""));
+        let first_progress = progress_rx.recv().await.expect("progress update");
+        assert_eq!(first_progress.resumed_from, 6);
+        assert_eq!(first_progress.downloaded_bytes, 6);
+        assert_eq!(first_progress.total_bytes, Some(BODY.len() as u64));
+        assert_eq!(std::fs::read(&destination).expect("read download"), BODY);
+        assert!(!partial_metadata_path(&destination).exists());
+    }
+
+    #[tokio::test]
+    async fn resume_falls_back_to_full_download_when_range_is_ignored() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind test listener");
+        let address = listener.local_addr().expect("read local addr");
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept request");
+            read_request(&mut stream);
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", BODY.len());
+            stream.write_all(head.as_bytes()).expect("write head");
+            stream.write_all(BODY).expect("write body");
+        });
+
+        let temp_dir = tempfile::tempdir().expect("create temp dir");
+        let destination = temp_dir.path().join("update.zip");
+        let url = format!("http://{}/update.zip", address);
+        std::fs::write(partial_path(&destination), b"stale").expect("write partial");
+        let metadata = PartialDownload {
+            url: url.clone(),
+            etag: None,
+            total_bytes: Some(BODY.len() as u64),
+        };
+        std::fs::write(
+            partial_metadata_path(&destination),
+            serde_json::to_vec(&metadata).expect("encode metadata"),
+        )
+        .expect("write metadata");
+
+        download_to_path(&Client::new(), &url, &destination, None, None)
+            .await
+            .expect("full download");
+
+        assert_eq!(std::fs::read(&destination).expect("read download"), BODY);
+    }
+
+    #[test]
+    fn parses_content_range_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_RANGE, "bytes 100-199/1000".parse().unwrap());
+        assert_eq!(parse_content_range(&headers), Some((100, Some(1000))));
+
+        headers.insert(CONTENT_RANGE, "bytes 100-199/*".parse().unwrap());
+        assert_eq!(parse_content_range(&headers), Some((100, None)));
+    }
+}
@@ -1,5 +1,7 @@
 //! HTTP client and download utilities for deployment workflows.
 
 pub mod assets;
+pub mod cache;
 pub mod client;
 pub mod download;
+pub mod sources;
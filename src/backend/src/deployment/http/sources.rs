@@ -0,0 +1,312 @@
+//! Release metadata from the configured release source.
+
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Local, TimeZone};
+use reqwest::header::{HeaderMap, AUTHORIZATION, ETAG, IF_NONE_MATCH, RETRY_AFTER};
+use reqwest::{Client, RequestBuilder, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use tracing::{debug, warn};
+
+use crate::config::{Config, ReleaseSource};
+use crate::types::{MonitorError, RateLimit};
+
+use super::assets::Release;
+use super::cache::ResponseCache;
+use super::client::default_github_headers;
+
+/// Number of releases requested from the GitHub API in one page.
+const GITHUB_PAGE_SIZE: u32 = 100;
+const RATE_LIMIT_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+const RATE_LIMIT_RESET_HEADER: &str = "x-ratelimit-reset";
+/// Under the app root; app updates carry it into the new tree.
+pub(crate) const CACHE_DIR_NAME: &str = ".cache";
+
+#[derive(Clone)]
+pub struct ReleaseFeed {
+    client: Client,
+    source: ReleaseSource,
+    github_token: Option<String>,
+    cache: Option<ResponseCache>,
+    /// Set once the server reports an exhausted quota, so no request is wasted
+    /// before it resets.
+    rate_limit: Arc<Mutex<Option<RateLimit>>>,
+}
+
+impl ReleaseFeed {
+    pub fn new(client: Client, source: ReleaseSource, config: &Config) -> Self {
+        let cache = Config::app_root_dir()
+            .ok()
+            .map(|root| ResponseCache::new(root.join(CACHE_DIR_NAME).join("releases")));
+        Self {
+            client,
+            source,
+            github_token: config
+                .github_token
+                .as_ref()
+                .map(|token| token.expose().to_string()),
+            cache,
+            rate_limit: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Fetches the most recent releases published by the source.
+    pub async fn releases(&self) -> Result<Vec<Release>, MonitorError> {
+        match &self.source {
+            ReleaseSource::Github { repo, api_url } => {
+                let url = format!(
+                    "{}/repos/{}/releases?per_page={}",
+                    api_url.trim_end_matches('/'),
+                    repo,
+                    GITHUB_PAGE_SIZE
+                );
+                self.fetch_json(self.github_request(&url), &url).await
+            }
+            ReleaseSource::Index { url } | ReleaseSource::SyncthingMeta { url } => {
+                self.fetch_index(url).await
+            }
+        }
+    }
+
+    /// Fetches the release for an exact version, including pre-releases.
+    pub async fn release(&self, version: &str) -> Result<Release, MonitorError> {
+        let version = version.trim().trim_start_matches('v');
+        match &self.source {
+            ReleaseSource::Github { repo, api_url } => {
+                let url = format!(
+                    "{}/repos/{}/releases/tags/v{}",
+                    api_url.trim_end_matches('/'),
+                    repo,
+                    version
+                );
+                self.fetch_json(self.github_request(&url), &url).await
+            }
+            ReleaseSource::Index { url } | ReleaseSource::SyncthingMeta { url } => {
+                let wanted = semver::Version::parse(version).map_err(|err| {
+                    MonitorError::Config(format!("Invalid version '{}': {}", version, err))
+                })?;
+                self.fetch_index(url)
+                    .await?
+                    .into_iter()
+                    .find(|release| !release.draft && release.version().as_ref() == Some(&wanted))
+                    .ok_or_else(|| {
+                        MonitorError::Config(format!(
+                            "Release v{} is not listed in {}",
+                            version, url
+                        ))
+                    })
+            }
+        }
+    }
+
+    fn github_request(&self, url: &str) -> RequestBuilder {
+        let request = self.client.get(url).headers(default_github_headers());
+        match &self.github_token {
+            Some(token) => request.header(AUTHORIZATION, format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+
+    async fn fetch_index(&self, url: &str) -> Result<Vec<Release>, MonitorError> {
+        let base = Url::parse(url).map_err(|err| {
+            MonitorError::Config(format!("Invalid release index URL '{}': {}", url, err))
+        })?;
+        let mut releases: Vec<Release> =
+            self.fetch_json(self.client.get(base.clone()), url).await?;
+        for release in &mut releases {
+            resolve_asset_urls(release, &base)?;
+        }
+        Ok(releases)
+    }
+}
+
+impl ReleaseFeed {
+    /// Sends a metadata request, revalidating a cached copy and honouring rate limits.
+    async fn fetch_json<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+        url: &str,
+    ) -> Result<T, MonitorError> {
+        if let Some(limit) = self.active_rate_limit() {
+            return Err(MonitorError::RateLimited(limit));
+        }
+
+        let cached = match &self.cache {
+            Some(cache) => cache.load(url).await,
+            None => None,
+        };
+        let request = match &cached {
+            Some(cached) => request.header(IF_NONE_MATCH, &cached.etag),
+            None => request,
+        };
+
+        let response = request.send().await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+
+        if let Some(limit) = rate_limit_from_response(status, &headers) {
+            warn!(url, %limit, "Release server rate limit reached");
+            *self.rate_limit.lock().expect("rate limit lock poisoned") = Some(limit);
+            return Err(MonitorError::RateLimited(limit));
+        }
+
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some(cached) = cached {
+                debug!(url, "Release metadata unchanged, using cached copy");
+                return Ok(serde_json::from_str(&cached.body)?);
+            }
+        }
+
+        let body = response.error_for_status()?.text().await?;
+        let parsed = serde_json::from_str(&body)?;
+        if let (Some(cache), Some(etag)) = (
+            &self.cache,
+            headers.get(ETAG).and_then(|etag| etag.to_str().ok()),
+        ) {
+            cache.store(url, etag, &body).await;
+        }
+        Ok(parsed)
+    }
+
+    fn active_rate_limit(&self) -> Option<RateLimit> {
+        let mut guard = self.rate_limit.lock().expect("rate limit lock poisoned");
+        match *guard {
+            Some(limit) if limit.reset_at.is_some_and(|reset| reset > Local::now()) => Some(limit),
+            Some(_) => {
+                *guard = None;
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+/// Recognises GitHub's primary and secondary rate-limit responses.
+fn rate_limit_from_response(status: StatusCode, headers: &HeaderMap) -> Option<RateLimit> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+
+    let header_number = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<i64>().ok())
+    };
+    let exhausted = header_number(RATE_LIMIT_REMAINING_HEADER) == Some(0);
+    let retry_after = header_number(RETRY_AFTER.as_str());
+    if !exhausted && retry_after.is_none() && status != StatusCode::TOO_MANY_REQUESTS {
+        // A plain 403 is an access problem, not a rate limit.
+        return None;
+    }
+
+    let reset_at: Option<DateTime<Local>> = match retry_after {
+        Some(seconds) => Some(Local::now() + chrono::Duration::seconds(seconds)),
+        None => header_number(RATE_LIMIT_RESET_HEADER)
+            .and_then(|epoch| Local.timestamp_opt(epoch, 0).single()),
+    };
+    Some(RateLimit { reset_at })
+}
+
+/// Makes relative asset URLs absolute, so a mirror can list files next to its index.
+fn resolve_asset_urls(release: &mut Release, base: &Url) -> Result<(), MonitorError> {
+    for asset in &mut release.assets {
+        let resolved = base.join(&asset.browser_download_url).map_err(|err| {
+            MonitorError::Config(format!(
+                "Invalid download URL '{}' for {}: {}",
+                asset.browser_download_url, asset.name, err
+            ))
+        })?;
+        asset.browser_download_url = resolved.to_string();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn exhausted_quota_is_reported_with_reset_time() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RATE_LIMIT_REMAINING_HEADER, HeaderValue::from_static("0"));
+        headers.insert(
+            RATE_LIMIT_RESET_HEADER,
+            HeaderValue::from_static("1700000000"),
+        );
+
+        let limit =
+            rate_limit_from_response(StatusCode::FORBIDDEN, &headers).expect("detect rate limit");
+
+        assert_eq!(
+            limit.reset_at.map(|reset| reset.timestamp()),
+            Some(1_700_000_000)
+        );
+        assert!(MonitorError::RateLimited(limit)
+            .to_string()
+            .starts_with("rate limited until "));
+    }
+
+    #[test]
+    fn plain_forbidden_is_not_a_rate_limit() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RATE_LIMIT_REMAINING_HEADER, HeaderValue::from_static("42"));
+
+        assert!(rate_limit_from_response(StatusCode::FORBIDDEN, &headers).is_none());
+        assert!(rate_limit_from_response(StatusCode::OK, &HeaderMap::new()).is_none());
+    }
+
+    #[test]
+    fn relative_index_urls_resolve_against_index_location() {
+        let base = Url::parse("https://mirror.example/syncthing/index.json").expect("parse base");
+        let mut release: Release = serde_json::from_str(
+            r#"{
+                "tag_name": "v1.27.12",
+                "assets": [
+                    {"name": "a.tar.gz", "browser_download_url": "v1.27.12/a.tar.gz"},
+                    {"name": "b.tar.gz", "browser_download_url": "https://cdn.example/b.tar.gz"}
+                ]
+            }"#,
+        )
+        .expect("parse release");
+
+        resolve_asset_urls(&mut release, &base).expect("resolve URLs");
+
+        assert_eq!(
+            release.assets[0].browser_download_url,
+            "https://mirror.example/syncthing/v1.27.12/a.tar.gz"
+        );
+        assert_eq!(
+            release.assets[1].browser_download_url,
+            "https://cdn.example/b.tar.gz"
+        );
+    }
+
+    #[test]
+    fn release_sources_parse_from_config() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "syncthing_release_source": {"type": "syncthing_meta"},
+                "app_release_source": {"type": "index", "url": "https://mirror.example/app.json"},
+                "github_token": "ghp_secret"
+            }"#,
+        )
+        .expect("parse config");
+
+        assert_eq!(
+            config.syncthing_release_source,
+            ReleaseSource::SyncthingMeta {
+                url: "https://upgrades.syncthing.net/meta.json".to_string()
+            }
+        );
+        assert_eq!(
+            config.app_release_source,
+            ReleaseSource::Index {
+                url: "https://mirror.example/app.json".to_string()
+            }
+        );
+        assert!(!format!("{:?}", config).contains("ghp_secret"));
+    }
+}
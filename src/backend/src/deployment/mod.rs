@@ -4,10 +4,13 @@ pub mod http;
 pub mod system;
 pub mod types;
 pub mod util;
+pub mod verify;
 pub mod workflows;
 
 // Re-export commonly used items for convenience
 pub use types::*;
+pub use util::cancel::CancelToken;
 pub use util::progress::*;
 pub use workflows::installer::Installer;
+pub use workflows::rollback;
 pub use workflows::updater::Updater;
@@ -1,6 +1,5 @@
-use tokio::process::Command;
-
 use crate::types::MonitorError;
+use crate::utils::CommandRunner;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Architecture {
@@ -42,8 +41,8 @@ impl Architecture {
     }
 }
 
-pub async fn detect_architecture() -> Result<Architecture, MonitorError> {
-    let output = Command::new("uname").arg("-m").output().await?;
+pub async fn detect_architecture(runner: &dyn CommandRunner) -> Result<Architecture, MonitorError> {
+    let output = runner.output("uname", &["-m"]).await?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -62,3 +61,25 @@ pub async fn detect_architecture() -> Result<Architecture, MonitorError> {
         ))
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::command::fake::{success, ScriptedRunner};
+
+    #[tokio::test]
+    async fn unsupported_architecture_is_rejected() {
+        let runner = ScriptedRunner::new()
+            .expect("uname -m", success("aarch64\n"))
+            .expect("uname -m", success("x86_64\n"));
+
+        assert_eq!(
+            detect_architecture(&runner).await.expect("detect arm64"),
+            Architecture::Arm64
+        );
+        let err = detect_architecture(&runner)
+            .await
+            .expect_err("x86_64 is unsupported");
+        assert!(err.to_string().contains("x86_64"), "{}", err);
+    }
+}
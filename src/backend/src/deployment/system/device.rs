@@ -0,0 +1,69 @@
+//! Identity of the tablet, used to name its Syncthing device.
+
+use tokio::fs;
+
+const HOSTNAME_PATH: &str = "/proc/sys/kernel/hostname";
+/// SoC serial exposed by the i.MX kernels of all reMarkable models.
+const SERIAL_PATH: &str = "/sys/devices/soc0/serial_number";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceIdentity {
+    pub hostname: Option<String>,
+    pub serial: Option<String>,
+}
+
+impl DeviceIdentity {
+    pub async fn detect() -> Self {
+        Self {
+            hostname: read_trimmed(HOSTNAME_PATH).await,
+            serial: read_trimmed(SERIAL_PATH).await,
+        }
+    }
+
+    /// Fills `{hostname}` and `{serial}` in `template`. Unknown values are left
+    /// out, and the result falls back to `reMarkable` if nothing is left.
+    pub fn render_name(&self, template: &str) -> String {
+        let rendered = template
+            .replace("{hostname}", self.hostname.as_deref().unwrap_or(""))
+            .replace("{serial}", self.serial.as_deref().unwrap_or(""));
+        let name = rendered.split_whitespace().collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            "reMarkable".to_string()
+        } else {
+            name
+        }
+    }
+}
+
+async fn read_trimmed(path: &str) -> Option<String> {
+    let contents = fs::read_to_string(path).await.ok()?;
+    let value = contents.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_values_are_dropped_from_device_name() {
+        let identity = DeviceIdentity {
+            hostname: Some("reMarkable".to_string()),
+            serial: None,
+        };
+
+        assert_eq!(identity.render_name("{hostname} {serial}"), "reMarkable");
+        assert_eq!(
+            DeviceIdentity {
+                serial: Some("0x1234abcd".to_string()),
+                ..identity.clone()
+            }
+            .render_name("Team tablet {serial}"),
+            "Team tablet 0x1234abcd"
+        );
+        assert_eq!(
+            DeviceIdentity::default().render_name("{serial}"),
+            "reMarkable"
+        );
+    }
+}
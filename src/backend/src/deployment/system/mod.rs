@@ -1,4 +1,7 @@
-//! System-level utilities for deployment (architecture detection, archive handling).
+//! System-level utilities for deployment (architecture detection, archive handling,
+//! device identity).
 
 pub mod architecture;
 pub mod archive;
+pub mod device;
+pub mod swap;
@@ -0,0 +1,138 @@
+//! Atomic directory replacement used to switch app versions.
+
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::types::MonitorError;
+
+/// Swaps `current` and `staged` so `current` holds the staged tree and `staged`
+/// holds the retired one.
+///
+/// Uses `renameat2(RENAME_EXCHANGE)` so there is no instant at which `current`
+/// is missing or half-populated. Filesystems without exchange support fall back
+/// to two renames.
+pub async fn replace_dir(current: &Path, staged: &Path) -> Result<(), MonitorError> {
+    let current = current.to_path_buf();
+    let staged = staged.to_path_buf();
+    tokio::task::spawn_blocking(move || replace_dir_blocking(&current, &staged))
+        .await
+        .map_err(|err| MonitorError::Io(io::Error::other(err)))?
+        .map_err(MonitorError::Io)
+}
+
+fn replace_dir_blocking(current: &Path, staged: &Path) -> io::Result<()> {
+    match exchange_paths(current, staged) {
+        Ok(()) => {}
+        Err(err) if exchange_unsupported(&err) => {
+            warn!(error = ?err, "Atomic directory exchange unsupported, falling back to renames");
+            let retired = with_suffix(current, ".retired");
+            std::fs::rename(current, &retired)?;
+            std::fs::rename(staged, current)?;
+            std::fs::rename(&retired, staged)?;
+        }
+        Err(err) => return Err(err),
+    }
+
+    if let Some(parent) = current.parent() {
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn exchange_paths(a: &Path, b: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let a = CString::new(a.as_os_str().as_bytes())?;
+    let b = CString::new(b.as_os_str().as_bytes())?;
+    // Called through syscall() so older glibc without a renameat2 wrapper still works.
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            libc::AT_FDCWD,
+            a.as_ptr(),
+            libc::AT_FDCWD,
+            b.as_ptr(),
+            libc::RENAME_EXCHANGE,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn exchange_paths(_a: &Path, _b: &Path) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+fn exchange_unsupported(err: &io::Error) -> bool {
+    #[cfg(target_os = "linux")]
+    if matches!(err.raw_os_error(), Some(libc::EINVAL | libc::ENOSYS)) {
+        return true;
+    }
+    err.kind() == io::ErrorKind::Unsupported
+}
+
+/// Flushes every file and directory below `root` to disk.
+pub async fn sync_tree(root: &Path) -> Result<(), MonitorError> {
+    let root = root.to_path_buf();
+    tokio::task::spawn_blocking(move || sync_tree_blocking(&root))
+        .await
+        .map_err(|err| MonitorError::Io(io::Error::other(err)))?
+        .map_err(MonitorError::Io)
+}
+
+fn sync_tree_blocking(path: &Path) -> io::Result<()> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            sync_tree_blocking(&entry?.path())?;
+        }
+    } else if !metadata.is_file() {
+        return Ok(());
+    }
+    File::open(path)?.sync_all()
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replace_dir_swaps_directory_contents() {
+        let parent = tempfile::tempdir().expect("create parent dir");
+        let current = parent.path().join("app");
+        let staged = parent.path().join(".app.staging");
+        std::fs::create_dir(&current).expect("create current dir");
+        std::fs::create_dir(&staged).expect("create staged dir");
+        std::fs::write(current.join("version"), "old").expect("write old version");
+        std::fs::write(staged.join("version"), "new").expect("write new version");
+
+        replace_dir(&current, &staged).await.expect("replace dir");
+
+        assert_eq!(
+            std::fs::read_to_string(current.join("version")).expect("read current"),
+            "new"
+        );
+        assert_eq!(
+            std::fs::read_to_string(staged.join("version")).expect("read retired"),
+            "old"
+        );
+    }
+}
@@ -3,6 +3,8 @@
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::Sender;
 
+use crate::config::ReleaseChannel;
+
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct InstallerStatus {
     pub binary_present: bool,
@@ -11,14 +13,118 @@ pub struct InstallerStatus {
     pub progress_message: Option<String>,
     pub error: Option<String>,
     pub installer_disabled: bool,
+    pub cancellable: bool,
+    pub cancelled: bool,
+    /// Syncthing archive in the sideload directory that can be installed offline.
+    pub local_archive: Option<String>,
+    pub rate_limited: bool,
+    /// Local time (`HH:MM`) at which the release server accepts requests again.
+    pub rate_limited_until: Option<String>,
+    /// Post-install checks of the last run, in order.
+    pub checkpoints: Vec<InstallCheckpoint>,
+    /// Where the installed unit file differs from the one the installer writes.
+    pub unit_drift: Vec<UnitDifference>,
+    /// Paths Syncthing cannot write under the hardened service profile.
+    pub profile_issues: Vec<String>,
+    /// The binary and configuration are present but the unit is gone, as after
+    /// a reMarkable OS update replaced the root partition.
+    pub service_lost: bool,
+}
+
+/// A post-install check confirming the installed Syncthing works.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmokeTestStep {
+    BinaryRuns,
+    ServiceRunning,
+    ConfigGenerated,
+    ApiReachable,
+}
+
+impl SmokeTestStep {
+    pub const ALL: [SmokeTestStep; 4] = [
+        SmokeTestStep::BinaryRuns,
+        SmokeTestStep::ServiceRunning,
+        SmokeTestStep::ConfigGenerated,
+        SmokeTestStep::ApiReachable,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SmokeTestStep::BinaryRuns => "Binary runs on this device",
+            SmokeTestStep::ServiceRunning => "Service is running",
+            SmokeTestStep::ConfigGenerated => "Configuration generated",
+            SmokeTestStep::ApiReachable => "API reachable",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckpointState {
+    Pending,
+    Running,
+    Passed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InstallCheckpoint {
+    pub step: SmokeTestStep,
+    pub label: &'static str,
+    pub state: CheckpointState,
+    /// Reported version on success, the reason on failure.
+    pub detail: Option<String>,
+}
+
+impl InstallCheckpoint {
+    pub fn pending(step: SmokeTestStep) -> Self {
+        Self {
+            step,
+            label: step.label(),
+            state: CheckpointState::Pending,
+            detail: None,
+        }
+    }
+}
+
+/// A unit file setting whose installed value differs from the expected one.
+/// A missing side is `None`; repeated keys are joined with newlines.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnitDifference {
+    /// Unit file or drop-in, relative to the unit directory.
+    pub file: String,
+    pub section: String,
+    pub key: String,
+    pub expected: Option<String>,
+    pub installed: Option<String>,
+}
+
+/// Syncthing or app versions that can be installed, newest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct AvailableVersions {
+    pub channel: ReleaseChannel,
+    pub pinned_version: Option<String>,
+    /// Version installed when none is chosen explicitly.
+    pub default_version: Option<String>,
+    pub versions: Vec<AvailableVersion>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AvailableVersion {
+    pub version: String,
+    pub prerelease: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateCheckResult {
     pub current_version: String,
+    /// The pinned or chosen version, otherwise the newest on the channel.
     pub latest_version: String,
     pub update_available: bool,
     pub download_url: Option<String>,
+    /// Detached OpenPGP signature published next to the update archive.
+    pub signature_url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -29,6 +135,17 @@ pub struct UpdateStatus {
     pub success: bool,
     pub pending_restart: bool,
     pub restart_seconds_remaining: Option<u32>,
+    pub cancellable: bool,
+    pub cancelled: bool,
+    /// A backup of the previous version exists and can be restored.
+    pub rollback_available: bool,
+    /// The pending restart loads a restored previous version.
+    pub rolled_back: bool,
+    /// App archive in the sideload directory that can be applied offline.
+    pub local_update_archive: Option<String>,
+    pub rate_limited: bool,
+    /// Local time (`HH:MM`) at which the release server accepts requests again.
+    pub rate_limited_until: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -44,6 +161,8 @@ pub struct SyncthingUpdateStatus {
 pub struct DownloadProgress {
     pub downloaded_bytes: u64,
     pub total_bytes: Option<u64>,
+    /// Bytes already on disk from an earlier, interrupted attempt.
+    pub resumed_from: u64,
 }
 
 impl DownloadProgress {
@@ -1,3 +1,4 @@
 //! Utility functions for deployment workflows.
 
+pub mod cancel;
 pub mod progress;
@@ -6,6 +6,15 @@ use crate::utils::format_bytes;
 const DOWNLOAD_PROGRESS_BYTE_STEP: u64 = 512 * 1024;
 
 pub fn render_download_progress_message(prefix: &str, progress: &DownloadProgress) -> String {
+    let prefix = if progress.resumed_from > 0 {
+        format!(
+            "{} (resumed at {})",
+            prefix,
+            format_bytes(progress.resumed_from)
+        )
+    } else {
+        prefix.to_string()
+    };
     match progress.total_bytes {
         Some(total) => {
             let percent = progress.percent().unwrap_or(0);
@@ -0,0 +1,93 @@
+//! SHA-256 helpers for checking downloaded release artifacts.
+
+use std::path::Path;
+
+use ring::digest::{Context, SHA256};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+use crate::types::MonitorError;
+
+/// Returns the lowercase hex SHA-256 digest of the file at `path`.
+pub async fn sha256_file(path: &Path) -> Result<String, MonitorError> {
+    let mut file = File::open(path).await?;
+    let mut context = Context::new(&SHA256);
+    let mut buffer = vec![0_u8; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        context.update(&buffer[..read]);
+    }
+
+    Ok(to_hex(context.finish().as_ref()))
+}
+
+/// Looks up `file_name` in `sha256sum`-style output (`<hex>  <name>` per line).
+pub fn find_checksum<'a>(checksums: &'a str, file_name: &str) -> Option<&'a str> {
+    checksums.lines().find_map(|line| {
+        let (hash, name) = line.trim().split_once(char::is_whitespace)?;
+        // Binary-mode entries prefix the name with `*`.
+        let name = name.trim_start();
+        let name = name.strip_prefix('*').unwrap_or(name);
+        (name == file_name && hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+            .then_some(hash)
+    })
+}
+
+/// Fails unless the file at `path` hashes to `expected`.
+pub async fn verify_sha256(path: &Path, expected: &str) -> Result<(), MonitorError> {
+    let actual = sha256_file(path).await?;
+    if actual.eq_ignore_ascii_case(expected) {
+        Ok(())
+    } else {
+        Err(MonitorError::Verification(format!(
+            "SHA-256 mismatch for {}: expected {}, got {}",
+            path.display(),
+            expected,
+            actual
+        )))
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_checksum_for_exact_file_name() {
+        let checksums = "aa  other.tar.gz\n\
+            1b45d6ea32e50bd52ecdadb9ada5141f2ad8d238a97ed840f1845c3696aa0b95  syncthing.tar.gz\n\
+            0000000000000000000000000000000000000000000000000000000000000000 *syncthing.tar.gz.sig";
+
+        assert_eq!(
+            find_checksum(checksums, "syncthing.tar.gz"),
+            Some("1b45d6ea32e50bd52ecdadb9ada5141f2ad8d238a97ed840f1845c3696aa0b95")
+        );
+        assert!(find_checksum(checksums, "syncthing.tar.gz.sig").is_some());
+        assert_eq!(find_checksum(checksums, "other.tar.gz"), None);
+    }
+
+    #[tokio::test]
+    async fn verify_sha256_detects_mismatch() {
+        let file = tempfile::NamedTempFile::new().expect("create tempfile");
+        std::fs::write(file.path(), b"fake tarball").expect("write tempfile");
+
+        verify_sha256(
+            file.path(),
+            "1b45d6ea32e50bd52ecdadb9ada5141f2ad8d238a97ed840f1845c3696aa0b95",
+        )
+        .await
+        .expect("matching digest");
+        assert!(matches!(
+            verify_sha256(file.path(), &"0".repeat(64)).await,
+            Err(MonitorError::Verification(_))
+        ));
+    }
+}
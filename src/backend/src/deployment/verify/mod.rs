@@ -0,0 +1,48 @@
+//! Integrity checks for downloaded release artifacts.
+
+pub mod checksum;
+pub mod openpgp;
+
+use crate::types::MonitorError;
+use openpgp::Keyring;
+
+/// Syncthing's release signing key, used for `sha256sum.txt.asc`.
+const SYNCTHING_RELEASE_KEY: &str = include_str!("../../../keys/syncthing-release.asc");
+
+/// This app's release signing key, used for detached signatures on update archives.
+const APP_RELEASE_KEY: &str = include_str!("../../../keys/app-release.asc");
+const PUBLIC_KEY_ARMOR: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----";
+
+pub fn syncthing_release_keyring() -> Result<Keyring, MonitorError> {
+    embedded_keyring(SYNCTHING_RELEASE_KEY, "Syncthing")
+}
+
+/// The app's release key, or `None` while this build embeds none. Updates
+/// have to be signed from the first release that ships the key, so copies
+/// installed before signed assets were published can still update to it.
+pub fn app_release_keyring() -> Result<Option<Keyring>, MonitorError> {
+    if !APP_RELEASE_KEY.contains(PUBLIC_KEY_ARMOR) {
+        return Ok(None);
+    }
+    embedded_keyring(APP_RELEASE_KEY, "app").map(Some)
+}
+
+fn embedded_keyring(armored: &str, owner: &str) -> Result<Keyring, MonitorError> {
+    Keyring::from_armored(armored).map_err(|err| match err {
+        MonitorError::Verification(reason) => MonitorError::Verification(format!(
+            "embedded {} release key is unusable ({})",
+            owner, reason
+        )),
+        other => other,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_app_release_key_is_absent_or_usable() {
+        app_release_keyring().expect("an armored app key must parse");
+    }
+}
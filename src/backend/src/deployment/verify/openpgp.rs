@@ -0,0 +1,482 @@
+//! Minimal OpenPGP signature verification for release artifacts.
+//!
+//! Only what release signing needs is supported: v4 RSA and Ed25519 keys, v4
+//! signatures with SHA-256/384/512, clear-signed messages and detached signatures.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::digest;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+
+use crate::types::MonitorError;
+
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+
+const ALGO_RSA: u8 = 1;
+const ALGO_RSA_SIGN_ONLY: u8 = 3;
+const ALGO_EDDSA_LEGACY: u8 = 22;
+
+const HASH_SHA256: u8 = 8;
+const HASH_SHA384: u8 = 9;
+const HASH_SHA512: u8 = 10;
+
+const SIG_TYPE_BINARY: u8 = 0x00;
+const SIG_TYPE_TEXT: u8 = 0x01;
+
+const ED25519_OID: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x0f, 0x01];
+
+const CLEARSIGN_HEADER: &str = "-----BEGIN PGP SIGNED MESSAGE-----";
+const SIGNATURE_HEADER: &str = "-----BEGIN PGP SIGNATURE-----";
+
+#[derive(Debug, Clone)]
+enum PublicKey {
+    Rsa { n: Vec<u8>, e: Vec<u8> },
+    Ed25519 { point: Vec<u8> },
+}
+
+/// Set of trusted signing keys, including subkeys.
+#[derive(Debug, Clone)]
+pub struct Keyring {
+    keys: Vec<PublicKey>,
+}
+
+impl Keyring {
+    /// Parses an ASCII-armored public key block.
+    pub fn from_armored(armored: &str) -> Result<Self, MonitorError> {
+        let data = decode_armor(armored, "PGP PUBLIC KEY BLOCK")?;
+        let mut keys = Vec::new();
+
+        for packet in parse_packets(&data)? {
+            if packet.tag == TAG_PUBLIC_KEY || packet.tag == TAG_PUBLIC_SUBKEY {
+                // Keys with algorithms we do not understand are skipped, not fatal.
+                if let Some(key) = parse_public_key(packet.body)? {
+                    keys.push(key);
+                }
+            }
+        }
+
+        if keys.is_empty() {
+            return Err(verification_error(
+                "public key block contains no supported signing keys",
+            ));
+        }
+
+        Ok(Self { keys })
+    }
+
+    /// Verifies a clear-signed message and returns the signed text.
+    ///
+    /// Only the returned text is covered by the signature; anything else in the
+    /// input must not be trusted.
+    pub fn verify_cleartext(&self, armored: &str) -> Result<String, MonitorError> {
+        let (text, signature_block) = split_cleartext(armored)?;
+        let signatures = decode_armor(signature_block, "PGP SIGNATURE")?;
+        self.verify_any(text.as_bytes(), &signatures, SIG_TYPE_TEXT)?;
+        Ok(text)
+    }
+
+    /// Verifies an armored detached signature over `data`.
+    pub fn verify_detached(
+        &self,
+        data: &[u8],
+        armored_signature: &str,
+    ) -> Result<(), MonitorError> {
+        let signatures = decode_armor(armored_signature, "PGP SIGNATURE")?;
+        self.verify_any(data, &signatures, SIG_TYPE_BINARY)
+    }
+
+    fn verify_any(
+        &self,
+        data: &[u8],
+        signature_packets: &[u8],
+        expected_type: u8,
+    ) -> Result<(), MonitorError> {
+        let mut last_error = verification_error("no signature found");
+
+        for packet in parse_packets(signature_packets)? {
+            if packet.tag != TAG_SIGNATURE {
+                continue;
+            }
+            let signature = match parse_signature(packet.body) {
+                Ok(signature) => signature,
+                Err(err) => {
+                    last_error = err;
+                    continue;
+                }
+            };
+            if signature.sig_type != expected_type {
+                last_error = verification_error("unexpected signature type");
+                continue;
+            }
+            if self.keys.iter().any(|key| signature.verify(key, data)) {
+                return Ok(());
+            }
+            last_error = verification_error("signature does not match any trusted key");
+        }
+
+        Err(last_error)
+    }
+}
+
+struct Packet<'a> {
+    tag: u8,
+    body: &'a [u8],
+}
+
+struct Signature {
+    sig_type: u8,
+    pubkey_algo: u8,
+    hash_algo: u8,
+    /// Signature fields covered by the hash (version through hashed subpackets).
+    hashed_fields: Vec<u8>,
+    mpis: Vec<Vec<u8>>,
+}
+
+impl Signature {
+    fn hash_input(&self, data: &[u8]) -> Vec<u8> {
+        let mut input = Vec::with_capacity(data.len() + self.hashed_fields.len() + 6);
+        input.extend_from_slice(data);
+        input.extend_from_slice(&self.hashed_fields);
+        input.extend_from_slice(&[0x04, 0xff]);
+        input.extend_from_slice(&(self.hashed_fields.len() as u32).to_be_bytes());
+        input
+    }
+
+    fn verify(&self, key: &PublicKey, data: &[u8]) -> bool {
+        let input = self.hash_input(data);
+        match (key, self.pubkey_algo) {
+            (PublicKey::Rsa { n, e }, ALGO_RSA | ALGO_RSA_SIGN_ONLY) => {
+                let params = match self.hash_algo {
+                    HASH_SHA256 => &signature::RSA_PKCS1_2048_8192_SHA256,
+                    HASH_SHA384 => &signature::RSA_PKCS1_2048_8192_SHA384,
+                    HASH_SHA512 => &signature::RSA_PKCS1_2048_8192_SHA512,
+                    _ => return false,
+                };
+                let Some(sig) = self.mpis.first() else {
+                    return false;
+                };
+                let components = RsaPublicKeyComponents { n, e };
+                components
+                    .verify(params, &input, &left_pad(sig, n.len()))
+                    .is_ok()
+            }
+            (PublicKey::Ed25519 { point }, ALGO_EDDSA_LEGACY) => {
+                let algorithm = match self.hash_algo {
+                    HASH_SHA256 => &digest::SHA256,
+                    HASH_SHA384 => &digest::SHA384,
+                    HASH_SHA512 => &digest::SHA512,
+                    _ => return false,
+                };
+                let (Some(r), Some(s)) = (self.mpis.first(), self.mpis.get(1)) else {
+                    return false;
+                };
+                let mut sig = left_pad(r, 32);
+                sig.extend_from_slice(&left_pad(s, 32));
+                let hashed = digest::digest(algorithm, &input);
+                UnparsedPublicKey::new(&signature::ED25519, point)
+                    .verify(hashed.as_ref(), &sig)
+                    .is_ok()
+            }
+            _ => false,
+        }
+    }
+}
+
+fn verification_error(message: &str) -> MonitorError {
+    MonitorError::Verification(message.to_string())
+}
+
+fn left_pad(value: &[u8], len: usize) -> Vec<u8> {
+    let mut padded = vec![0_u8; len.saturating_sub(value.len())];
+    padded.extend_from_slice(value);
+    padded
+}
+
+/// Extracts the base64 payload of the first armor block with `label`.
+fn decode_armor(armored: &str, label: &str) -> Result<Vec<u8>, MonitorError> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+    let mut lines = armored
+        .lines()
+        .map(str::trim_end)
+        .skip_while(|line| *line != begin)
+        .skip(1);
+
+    // Armor headers end at the first blank line.
+    for line in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+    }
+
+    let mut payload = String::new();
+    let mut terminated = false;
+    for line in lines {
+        if line == end {
+            terminated = true;
+            break;
+        }
+        if line.starts_with('=') {
+            // CRC24 checksum; the signature check is what actually authenticates.
+            continue;
+        }
+        payload.push_str(line.trim());
+    }
+
+    if !terminated {
+        return Err(verification_error(&format!("missing {} armor", label)));
+    }
+
+    STANDARD
+        .decode(payload.as_bytes())
+        .map_err(|err| verification_error(&format!("invalid {} armor: {}", label, err)))
+}
+
+/// Splits a clear-signed message into canonical signed text and the signature armor.
+fn split_cleartext(armored: &str) -> Result<(String, &str), MonitorError> {
+    let start = armored
+        .find(CLEARSIGN_HEADER)
+        .ok_or_else(|| verification_error("missing clear-signed message header"))?;
+    let message = &armored[start + CLEARSIGN_HEADER.len()..];
+    let signature_start = message
+        .find(SIGNATURE_HEADER)
+        .ok_or_else(|| verification_error("missing signature block"))?;
+    let (body, signature_block) = message.split_at(signature_start);
+
+    let mut lines = body.split('\n').skip(1);
+    for line in lines.by_ref() {
+        if line.trim_end().is_empty() {
+            break;
+        }
+        if !line.starts_with("Hash:") {
+            return Err(verification_error("unexpected clear-signed message header"));
+        }
+    }
+
+    let mut text_lines: Vec<&str> = lines.collect();
+    // The line break before the signature armor belongs to the armor, not the text.
+    if text_lines.last() == Some(&"") {
+        text_lines.pop();
+    }
+
+    let canonical = text_lines
+        .into_iter()
+        .map(|line| {
+            let line = line.strip_prefix("- ").unwrap_or(line);
+            line.trim_end_matches([' ', '\t', '\r'])
+        })
+        .collect::<Vec<_>>()
+        .join("\r\n");
+
+    Ok((canonical, signature_block))
+}
+
+fn parse_packets(mut data: &[u8]) -> Result<Vec<Packet<'_>>, MonitorError> {
+    let mut packets = Vec::new();
+
+    while !data.is_empty() {
+        let header = data[0];
+        if header & 0x80 == 0 {
+            return Err(verification_error("malformed packet header"));
+        }
+
+        let (tag, body_len, header_len): (u8, usize, usize) = if header & 0x40 != 0 {
+            let tag = header & 0x3f;
+            let first = *data.get(1).ok_or_else(truncated)? as usize;
+            match first {
+                0..=191 => (tag, first, 2),
+                192..=223 => {
+                    let second = *data.get(2).ok_or_else(truncated)? as usize;
+                    (tag, ((first - 192) << 8) + second + 192, 3)
+                }
+                255 => (tag, read_u32(data, 2)? as usize, 6),
+                _ => {
+                    return Err(verification_error(
+                        "partial packet lengths are not supported",
+                    ))
+                }
+            }
+        } else {
+            let tag = (header >> 2) & 0x0f;
+            match header & 0x03 {
+                0 => (tag, *data.get(1).ok_or_else(truncated)? as usize, 2),
+                1 => (tag, read_u16(data, 1)? as usize, 3),
+                2 => (tag, read_u32(data, 1)? as usize, 5),
+                _ => (tag, data.len() - 1, 1),
+            }
+        };
+
+        let end = header_len
+            .checked_add(body_len)
+            .filter(|end| *end <= data.len())
+            .ok_or_else(truncated)?;
+        packets.push(Packet {
+            tag,
+            body: &data[header_len..end],
+        });
+        data = &data[end..];
+    }
+
+    Ok(packets)
+}
+
+fn parse_public_key(body: &[u8]) -> Result<Option<PublicKey>, MonitorError> {
+    if body.first() != Some(&4) {
+        return Ok(None);
+    }
+    let algo = *body.get(5).ok_or_else(truncated)?;
+    let mut cursor = 6;
+
+    match algo {
+        ALGO_RSA | ALGO_RSA_SIGN_ONLY => {
+            let n = read_mpi(body, &mut cursor)?;
+            let e = read_mpi(body, &mut cursor)?;
+            Ok(Some(PublicKey::Rsa { n, e }))
+        }
+        ALGO_EDDSA_LEGACY => {
+            let oid_len = *body.get(cursor).ok_or_else(truncated)? as usize;
+            let oid = body
+                .get(cursor + 1..cursor + 1 + oid_len)
+                .ok_or_else(truncated)?;
+            cursor += 1 + oid_len;
+            if oid != ED25519_OID {
+                return Ok(None);
+            }
+            let point = read_mpi(body, &mut cursor)?;
+            // Native point encoding is prefixed with 0x40.
+            match point.split_first() {
+                Some((0x40, point)) if point.len() == 32 => Ok(Some(PublicKey::Ed25519 {
+                    point: point.to_vec(),
+                })),
+                _ => Err(verification_error("invalid Ed25519 public key")),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+fn parse_signature(body: &[u8]) -> Result<Signature, MonitorError> {
+    if body.first() != Some(&4) {
+        return Err(verification_error(
+            "only version 4 signatures are supported",
+        ));
+    }
+    let sig_type = *body.get(1).ok_or_else(truncated)?;
+    let pubkey_algo = *body.get(2).ok_or_else(truncated)?;
+    let hash_algo = *body.get(3).ok_or_else(truncated)?;
+
+    let hashed_len = read_u16(body, 4)? as usize;
+    let hashed_end = 6 + hashed_len;
+    let hashed_fields = body.get(..hashed_end).ok_or_else(truncated)?.to_vec();
+
+    let unhashed_len = read_u16(body, hashed_end)? as usize;
+    // Skip the unhashed subpackets and the two-byte hash prefix.
+    let mut cursor = hashed_end + 2 + unhashed_len + 2;
+    if cursor > body.len() {
+        return Err(truncated());
+    }
+
+    let mut mpis = Vec::new();
+    while cursor < body.len() {
+        mpis.push(read_mpi(body, &mut cursor)?);
+    }
+
+    Ok(Signature {
+        sig_type,
+        pubkey_algo,
+        hash_algo,
+        hashed_fields,
+        mpis,
+    })
+}
+
+fn read_mpi(data: &[u8], cursor: &mut usize) -> Result<Vec<u8>, MonitorError> {
+    let bits = read_u16(data, *cursor)? as usize;
+    let len = bits.div_ceil(8);
+    let start = *cursor + 2;
+    let value = data.get(start..start + len).ok_or_else(truncated)?;
+    *cursor = start + len;
+    Ok(value.to_vec())
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, MonitorError> {
+    let bytes = data.get(offset..offset + 2).ok_or_else(truncated)?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, MonitorError> {
+    let bytes = data.get(offset..offset + 4).ok_or_else(truncated)?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn truncated() -> MonitorError {
+    verification_error("truncated OpenPGP data")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSA_KEY: &str = include_str!("testdata/rsa-release-key.asc");
+    const ED25519_KEY: &str = include_str!("testdata/ed25519-release-key.asc");
+    const RSA_SIGNED: &str = include_str!("testdata/sha256sum-rsa.txt.asc");
+    const ED25519_SIGNED: &str = include_str!("testdata/sha256sum-ed25519.txt.asc");
+    const DETACHED_SIGNATURE: &str = include_str!("testdata/update.zip-ed25519.asc");
+
+    #[test]
+    fn verifies_rsa_clearsigned_checksums() {
+        let keyring = Keyring::from_armored(RSA_KEY).expect("parse RSA key");
+        let text = keyring
+            .verify_cleartext(RSA_SIGNED)
+            .expect("verify RSA signature");
+
+        assert!(text.contains("syncthing-linux-arm64-v9.9.9.tar.gz"));
+        // Dash-escaping is undone and trailing whitespace stripped.
+        assert!(text.ends_with("\r\n- not a checksum line"));
+    }
+
+    #[test]
+    fn verifies_ed25519_clearsigned_checksums() {
+        let keyring = Keyring::from_armored(ED25519_KEY).expect("parse Ed25519 key");
+        keyring
+            .verify_cleartext(ED25519_SIGNED)
+            .expect("verify Ed25519 signature");
+    }
+
+    #[test]
+    fn rejects_tampered_checksums() {
+        let keyring = Keyring::from_armored(RSA_KEY).expect("parse RSA key");
+        let tampered = RSA_SIGNED.replacen("1b45d6", "2b45d6", 1);
+
+        assert!(matches!(
+            keyring.verify_cleartext(&tampered),
+            Err(MonitorError::Verification(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_signature_from_untrusted_key() {
+        let keyring = Keyring::from_armored(ED25519_KEY).expect("parse Ed25519 key");
+
+        assert!(keyring.verify_cleartext(RSA_SIGNED).is_err());
+    }
+
+    #[test]
+    fn verifies_detached_signature() {
+        let keyring = Keyring::from_armored(ED25519_KEY).expect("parse Ed25519 key");
+
+        keyring
+            .verify_detached(b"fake tarball", DETACHED_SIGNATURE)
+            .expect("verify detached signature");
+        assert!(keyring
+            .verify_detached(b"fake tarball!", DETACHED_SIGNATURE)
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_keyring_without_keys() {
+        assert!(Keyring::from_armored("not a key").is_err());
+    }
+}
@@ -0,0 +1,196 @@
+//! App-managed Syncthing upgrades that keep the previous binary until the new
+//! one is confirmed running.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::deployment::{CancelToken, DownloadProgressSender};
+use crate::syncthing_client::{probe_version, wait_for_version, SyncthingUpgradeCheck};
+use crate::types::MonitorError;
+
+use super::installer::Installer;
+use super::major_upgrade::StateSnapshot;
+
+pub const PREVIOUS_BINARY_SUFFIX: &str = ".prev";
+
+/// How long the restarted service gets to report the new version.
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(90);
+
+impl Installer {
+    /// Compares the running Syncthing with the pinned version or the newest
+    /// release on the configured channel.
+    pub async fn check_binary_upgrade(&self) -> Result<SyncthingUpgradeCheck, MonitorError> {
+        let running = probe_version(&self.config).await?;
+        let latest = self.fetch_target_release(None).await?;
+        Ok(compare_versions(
+            &running,
+            latest.tag_name.trim_start_matches('v'),
+            self.config.pinned_syncthing_version.is_some(),
+        ))
+    }
+
+    /// Replaces the Syncthing binary with `version`, keeping the old one as
+    /// `syncthing.prev`, and restarts the service.
+    ///
+    /// If the API does not come back with the new version, the previous binary is
+    /// restored and started again, together with the state `snapshot` taken before
+    /// a major upgrade; once the new version runs, that snapshot is deleted.
+    /// Returns the installed version.
+    pub async fn upgrade_binary(
+        &self,
+        version: &str,
+        snapshot: Option<&StateSnapshot>,
+        progress_tx: Option<DownloadProgressSender>,
+        cancel: Option<CancelToken>,
+    ) -> Result<String, MonitorError> {
+        let binary = self.binary_path()?;
+        let previous = previous_binary_path(&binary);
+        keep_previous_binary(&binary, &previous).await?;
+
+        let installed = self
+            .download_binary(Some(version), progress_tx, cancel)
+            .await?;
+
+        let restarted = self.restart_service().await;
+        let confirmed = match restarted {
+            Ok(()) => wait_for_version(&self.config, &installed, HEALTH_TIMEOUT).await,
+            Err(err) => Err(err),
+        };
+        let Err(err) = confirmed else {
+            info!(version = %installed, "Syncthing is running the upgraded binary");
+            if let Some(snapshot) = snapshot {
+                self.discard_major_upgrade_snapshot(snapshot).await;
+            }
+            return Ok(installed);
+        };
+
+        warn!(version = %installed, error = ?err, "Upgraded Syncthing did not come up, restoring previous binary");
+        match snapshot {
+            Some(snapshot) => self.roll_back_major_upgrade(snapshot).await?,
+            None => {
+                self.restore_previous_binary().await?;
+                self.restart_service().await?;
+            }
+        }
+        let restored = if snapshot.is_some() {
+            "the previous binary and state were restored"
+        } else {
+            "the previous binary was restored"
+        };
+        Err(MonitorError::Syncthing(format!(
+            "Syncthing v{} did not start correctly ({}); {}",
+            installed, err, restored
+        )))
+    }
+
+    /// Moves `syncthing.prev` back in place of the current binary.
+    pub async fn restore_previous_binary(&self) -> Result<(), MonitorError> {
+        let binary = self.binary_path()?;
+        let previous = previous_binary_path(&binary);
+        if fs::metadata(&previous).await.is_err() {
+            return Err(MonitorError::Config(
+                "No previous Syncthing binary to restore".to_string(),
+            ));
+        }
+        fs::rename(&previous, &binary).await?;
+        info!(path = %binary.display(), "Restored previous Syncthing binary");
+        Ok(())
+    }
+}
+
+/// Keeps a copy of the live binary next to it; the live one is replaced by rename,
+/// so a hard link is enough.
+pub(super) async fn keep_previous_binary(
+    binary: &Path,
+    previous: &Path,
+) -> Result<(), MonitorError> {
+    if fs::metadata(binary).await.is_err() {
+        return Ok(());
+    }
+    if fs::symlink_metadata(previous).await.is_ok() {
+        fs::remove_file(previous).await?;
+    }
+    if let Err(err) = fs::hard_link(binary, previous).await {
+        warn!(error = ?err, "Hard-linking previous Syncthing binary failed, copying instead");
+        fs::copy(binary, previous).await?;
+    }
+    Ok(())
+}
+
+/// A pinned version counts as an update whenever it differs from the running one,
+/// so a pin can also move Syncthing back.
+fn compare_versions(running: &str, latest: &str, pinned: bool) -> SyncthingUpgradeCheck {
+    let parsed = (
+        semver::Version::parse(running).ok(),
+        semver::Version::parse(latest).ok(),
+    );
+    let (newer, major_newer) = match parsed {
+        (Some(running), Some(latest)) => (
+            if pinned {
+                latest != running
+            } else {
+                latest > running
+            },
+            latest.major > running.major,
+        ),
+        _ => (running != latest, false),
+    };
+    SyncthingUpgradeCheck {
+        running: running.to_string(),
+        latest: latest.to_string(),
+        newer,
+        major_newer,
+    }
+}
+
+pub fn previous_binary_path(binary: &Path) -> PathBuf {
+    let mut name = binary
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    name.push(PREVIOUS_BINARY_SUFFIX);
+    binary.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn previous_binary_survives_replacement_by_rename() {
+        let dir = tempfile::tempdir().expect("create app dir");
+        let binary = dir.path().join("syncthing");
+        let previous = previous_binary_path(&binary);
+        fs::write(&binary, b"old").await.expect("write old binary");
+        fs::write(&previous, b"older")
+            .await
+            .expect("write stale backup");
+
+        keep_previous_binary(&binary, &previous)
+            .await
+            .expect("keep previous binary");
+        let replacement = dir.path().join("syncthing.new");
+        fs::write(&replacement, b"new")
+            .await
+            .expect("write new binary");
+        fs::rename(&replacement, &binary)
+            .await
+            .expect("swap in new binary");
+
+        assert_eq!(previous.file_name().unwrap(), "syncthing.prev");
+        assert_eq!(fs::read(&previous).await.expect("read previous"), b"old");
+        assert_eq!(fs::read(&binary).await.expect("read binary"), b"new");
+    }
+
+    #[test]
+    fn pinned_version_is_offered_even_when_older() {
+        assert!(compare_versions("1.29.2", "1.30.0", false).newer);
+        assert!(!compare_versions("1.29.2", "1.27.12", false).newer);
+        assert!(compare_versions("1.29.2", "1.27.12", true).newer);
+        assert!(!compare_versions("1.29.2", "1.29.2", true).newer);
+        assert!(compare_versions("1.29.2", "2.0.0", false).major_newer);
+    }
+}
@@ -0,0 +1,325 @@
+//! Opt-in hardened service profile: Syncthing runs as `service_user` and can
+//! only write to its home and the synced folders.
+//!
+//! The backend keeps running as root, so it still reads the API key from
+//! `config.xml` and controls the unit as before.
+
+use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::config::ServiceProfile;
+use crate::syncthing_client::load_folder_paths;
+use crate::types::MonitorError;
+use crate::utils::CommandRunner;
+
+use super::installer::Installer;
+
+pub(super) const HARDENING_DROPIN: &str = "hardening.conf";
+const PASSWD_PATH: &str = "/etc/passwd";
+/// Directories never handed to the service user, even when synced as a whole.
+const PROTECTED_PATHS: [&str; 6] = ["/", "/etc", "/home", "/home/root", "/usr", "/var"];
+
+impl Installer {
+    fn hardened(&self) -> bool {
+        self.config.service_profile == ServiceProfile::Hardened
+    }
+
+    /// The Syncthing home followed by the synced folders from `config.xml`.
+    pub(super) async fn writable_paths(&self) -> Vec<PathBuf> {
+        let mut paths = vec![PathBuf::from(&self.config.syncthing_config_dir)];
+        match load_folder_paths(&self.config).await {
+            Ok(folders) => paths.extend(folders.into_iter().map(PathBuf::from)),
+            // A new instance has no config.xml before its first start.
+            Err(MonitorError::Io(err)) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => warn!(error = ?err, "Failed to read folder paths from config.xml"),
+        }
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    /// The drop-in for the hardened profile, or `None` under the root profile.
+    pub(super) fn render_hardening_dropin(&self, writable: &[PathBuf]) -> Option<String> {
+        self.hardened().then(|| {
+            render_dropin(
+                &self.config.service_user,
+                &self.config.syncthing_config_dir,
+                writable,
+            )
+        })
+    }
+
+    /// Writes or removes the hardening drop-in and, under the hardened profile,
+    /// creates the service user and hands it the writable paths.
+    pub(super) async fn apply_service_profile(&self) -> Result<(), MonitorError> {
+        let writable = self.writable_paths().await;
+        let user = &self.config.service_user;
+        if self.hardened() {
+            if let Some(path) = writable.iter().find(|path| {
+                PROTECTED_PATHS
+                    .iter()
+                    .any(|protected| *path == Path::new(protected))
+            }) {
+                return Err(MonitorError::Config(format!(
+                    "Refusing to hand {} to {}; sync a subdirectory instead",
+                    path.display(),
+                    user
+                )));
+            }
+        }
+        self.write_dropin(HARDENING_DROPIN, self.render_hardening_dropin(&writable))
+            .await?;
+        if !self.hardened() {
+            return Ok(());
+        }
+
+        let uid = self.ensure_service_user().await?;
+        for path in &writable {
+            fs::create_dir_all(path).await?;
+            chown_recursive(self.runner.as_ref(), user, path).await?;
+            open_traversal(path, uid).await?;
+        }
+        open_traversal(&self.binary_path()?, uid).await?;
+        info!(user = %user, paths = writable.len(), "Applied hardened service profile");
+        Ok(())
+    }
+
+    /// Problems that keep Syncthing from writing its home or a synced folder
+    /// under the hardened profile. Empty under the root profile.
+    pub async fn profile_issues(&self) -> Vec<String> {
+        if !self.hardened() {
+            return Vec::new();
+        }
+        let user = &self.config.service_user;
+        let Some(uid) = lookup_uid_in_passwd(user).await else {
+            return vec![format!("User {user} does not exist")];
+        };
+
+        let mut issues = Vec::new();
+        for path in self.writable_paths().await {
+            if let Some(issue) = check_writable(&path, uid).await {
+                issues.push(format!("{}: {}", path.display(), issue));
+            }
+        }
+        issues
+    }
+
+    async fn ensure_service_user(&self) -> Result<u32, MonitorError> {
+        let user = &self.config.service_user;
+        if let Some(uid) = lookup_uid_in_passwd(user).await {
+            return Ok(uid);
+        }
+
+        let home = &self.config.syncthing_config_dir;
+        let useradd = self
+            .runner
+            .output(
+                "useradd",
+                &[
+                    "--system",
+                    "--user-group",
+                    "--no-create-home",
+                    "--home-dir",
+                    home,
+                    "--shell",
+                    "/bin/false",
+                    user,
+                ],
+            )
+            .await;
+        let output = match useradd {
+            Ok(output) => output,
+            // BusyBox systems ship adduser instead.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.runner
+                    .output(
+                        "adduser",
+                        &["-S", "-D", "-H", "-h", home, "-s", "/bin/false", user],
+                    )
+                    .await?
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if !output.status.success() {
+            return Err(MonitorError::Config(format!(
+                "Failed to create user {}: {}",
+                user,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        info!(user = %user, "Created Syncthing service user");
+
+        lookup_uid_in_passwd(user).await.ok_or_else(|| {
+            MonitorError::Config(format!("User {user} is missing after creating it"))
+        })
+    }
+}
+
+fn render_dropin(user: &str, home: &str, writable: &[PathBuf]) -> String {
+    let writable: Vec<String> = writable
+        .iter()
+        .map(|path| {
+            // A leading `-` keeps a missing folder from failing the whole unit.
+            let path = path.display().to_string();
+            if path.contains(char::is_whitespace) {
+                format!("\"-{path}\"")
+            } else {
+                format!("-{path}")
+            }
+        })
+        .collect();
+    format!(
+        "# Generated for the hardened service_profile in config.json.
+[Service]
+User={user}
+WorkingDirectory={home}
+# The binary is read-only under ProtectSystem=strict; app-managed upgrades still work.
+Environment=STNOUPGRADE=1
+NoNewPrivileges=yes
+PrivateTmp=yes
+ProtectSystem=strict
+ReadWritePaths={}
+",
+        writable.join(" ")
+    )
+}
+
+async fn lookup_uid_in_passwd(user: &str) -> Option<u32> {
+    let passwd = fs::read_to_string(PASSWD_PATH).await.ok()?;
+    lookup_uid(&passwd, user)
+}
+
+fn lookup_uid(passwd: &str, user: &str) -> Option<u32> {
+    passwd.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? != user {
+            return None;
+        }
+        fields.nth(1)?.parse().ok()
+    })
+}
+
+async fn chown_recursive(
+    runner: &dyn CommandRunner,
+    user: &str,
+    path: &Path,
+) -> Result<(), MonitorError> {
+    let owner = format!("{user}:");
+    let output = runner
+        .output("chown", &["-R", &owner, &path.to_string_lossy()])
+        .await?;
+    if !output.status.success() {
+        return Err(MonitorError::Config(format!(
+            "Failed to hand {} to {}: {}",
+            path.display(),
+            user,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Adds search permission for others to the ancestors of `path` the service
+/// user cannot traverse, e.g. `/home/root`. Directory listings stay closed.
+async fn open_traversal(path: &Path, uid: u32) -> Result<(), MonitorError> {
+    for dir in path.ancestors().skip(1) {
+        let metadata = fs::metadata(dir).await?;
+        let mode = metadata.permissions().mode();
+        if metadata.uid() != uid && mode & 0o001 == 0 {
+            fs::set_permissions(dir, std::fs::Permissions::from_mode(mode | 0o001)).await?;
+            info!(path = %dir.display(), "Allowed the service user to traverse directory");
+        }
+    }
+    Ok(())
+}
+
+async fn check_writable(path: &Path, uid: u32) -> Option<&'static str> {
+    let metadata = match fs::metadata(path).await {
+        Ok(metadata) => metadata,
+        Err(_) => return Some("missing"),
+    };
+    if metadata.uid() != uid {
+        return Some("not owned by the service user");
+    }
+    if metadata.permissions().mode() & 0o300 != 0o300 {
+        return Some("not writable by its owner");
+    }
+    for dir in path.ancestors().skip(1) {
+        match fs::metadata(dir).await {
+            Ok(parent) if parent.uid() == uid || parent.permissions().mode() & 0o001 != 0 => {}
+            _ => return Some("a parent directory cannot be traversed"),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::utils::command::fake::{failure, not_found, ScriptedRunner};
+    use crate::utils::filesystem::RootMounts;
+
+    #[tokio::test]
+    async fn busybox_adduser_is_used_without_useradd() {
+        let runner = ScriptedRunner::new()
+            .expect(
+                "useradd --system --user-group --no-create-home --home-dir /home/root/.config/syncthing \
+                 --shell /bin/false st-hardening-test",
+                not_found(),
+            )
+            .expect(
+                "adduser -S -D -H -h /home/root/.config/syncthing -s /bin/false st-hardening-test",
+                failure(1, "adduser: permission denied"),
+            )
+            .shared();
+        let config = Config {
+            service_user: "st-hardening-test".to_string(),
+            syncthing_config_dir: "/home/root/.config/syncthing".to_string(),
+            ..Config::default()
+        };
+        let installer = Installer::new(config, runner.clone(), RootMounts::new(runner));
+
+        let err = installer
+            .ensure_service_user()
+            .await
+            .expect_err("adduser fails");
+        assert!(
+            err.to_string()
+                .contains("Failed to create user st-hardening-test"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn uid_is_looked_up_by_exact_name() {
+        let passwd = "root:x:0:0:root:/home/root:/bin/sh\n\
+                      syncthing-old:x:998:998::/nonexistent:/bin/false\n\
+                      syncthing:x:999:999::/home/root/.config/syncthing:/bin/false\n";
+
+        assert_eq!(lookup_uid(passwd, "syncthing"), Some(999));
+        assert_eq!(lookup_uid(passwd, "nobody"), None);
+    }
+
+    #[test]
+    fn dropin_limits_writes_to_home_and_folders() {
+        let dropin = render_dropin(
+            "syncthing",
+            "/home/root/.config/syncthing",
+            &[
+                PathBuf::from("/home/root/.config/syncthing"),
+                PathBuf::from("/home/root/My Notes"),
+            ],
+        );
+
+        assert!(dropin.contains("User=syncthing\n"));
+        assert!(dropin.contains("ProtectSystem=strict\n"));
+        assert!(dropin
+            .contains("ReadWritePaths=-/home/root/.config/syncthing \"-/home/root/My Notes\"\n"));
+    }
+}
@@ -3,37 +3,61 @@
 use std::ffi::OsStr;
 use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use reqwest::Client;
 use tokio::fs;
-use tokio::process::Command;
-use tracing::{error, warn};
+use tracing::{error, info, warn};
 
 use crate::config::Config;
-use crate::deployment::http::assets::{self, ReleaseAsset};
-use crate::deployment::http::client::{default_request_timeout, github_client};
+use crate::deployment::http::assets::{self, Release, ReleaseAsset};
+use crate::deployment::http::client::{default_request_timeout, release_client};
 use crate::deployment::http::download::download_to_path;
+use crate::deployment::http::sources::ReleaseFeed;
 use crate::deployment::system::architecture::detect_architecture;
 use crate::deployment::system::archive;
-use crate::deployment::DownloadProgressSender;
+use crate::deployment::verify::{checksum, syncthing_release_keyring};
+use crate::deployment::workflows::sideload::{self, SyncthingArchive};
+use crate::deployment::workflows::smoke_test::run_version_check;
+use crate::deployment::{AvailableVersions, CancelToken, DownloadProgressSender};
 use crate::types::MonitorError;
-use crate::utils::{filesystem, systemctl};
+use crate::utils::filesystem::RootMounts;
+use crate::utils::{systemctl, SharedRunner};
 
-const RELEASE_API_URL: &str = "https://api.github.com/repos/syncthing/syncthing/releases/latest";
-const TAR_EXTENSION: &str = ".tar.gz";
+pub(super) const TAR_EXTENSION: &str = ".tar.gz";
+pub(super) const CHECKSUMS_ASSET_NAME: &str = "sha256sum.txt.asc";
 const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const UNIT_DIR: &str = "/etc/systemd/system";
 
 #[derive(Clone)]
 pub struct Installer {
-    config: Config,
+    pub(super) config: Config,
+    pub(super) runner: SharedRunner,
+    pub(super) mounts: Arc<RootMounts>,
+    unit_dir: PathBuf,
     client: Client,
+    feed: ReleaseFeed,
 }
 
 impl Installer {
-    pub fn new(config: Config) -> Self {
-        let client = github_client(default_request_timeout())
+    /// An installer whose external commands go to `runner` and whose unit
+    /// writes hold a guard from `mounts`.
+    pub fn new(config: Config, runner: SharedRunner, mounts: Arc<RootMounts>) -> Self {
+        let client = release_client(default_request_timeout(), &config)
             .expect("Failed to construct HTTP client for installer");
-        Self { config, client }
+        let feed = ReleaseFeed::new(
+            client.clone(),
+            config.syncthing_release_source.clone(),
+            &config,
+        );
+        Self {
+            config,
+            runner,
+            mounts,
+            unit_dir: PathBuf::from(UNIT_DIR),
+            client,
+            feed,
+        }
     }
 
     pub async fn binary_present(&self) -> bool {
@@ -58,10 +82,9 @@ impl Installer {
 
     pub async fn service_installed(&self) -> bool {
         let service_name = &self.config.systemd_service_name;
-        match Command::new("systemctl")
-            .arg("cat")
-            .arg(service_name)
-            .output()
+        match self
+            .runner
+            .output("systemctl", &["cat", service_name])
             .await
         {
             Ok(output) => output.status.success(),
@@ -72,11 +95,43 @@ impl Installer {
         }
     }
 
-    pub async fn download_latest_binary(
+    /// Whether an installed Syncthing lost its unit, as happens when an OS update
+    /// replaces the root partition while `/home` is kept. The unit is generated
+    /// from `config.json`, so it can be written again without a download.
+    pub async fn service_lost(&self) -> bool {
+        self.binary_present().await
+            && fs::metadata(self.config.syncthing_config_xml_path())
+                .await
+                .is_ok()
+            && !self.service_installed().await
+    }
+
+    /// Lists the releases offered on the configured channel, plus the pinned version.
+    pub async fn list_versions(&self) -> Result<AvailableVersions, MonitorError> {
+        let releases = self.feed.releases().await?;
+        Ok(assets::available_versions(
+            &releases,
+            self.config.release_channel,
+            self.config.pinned_syncthing_version.as_deref(),
+        ))
+    }
+
+    /// Downloads and verifies a Syncthing release and installs its binary.
+    ///
+    /// Without an explicit `version`, the pinned version from the config is used,
+    /// falling back to the newest release on the configured channel. Returns the
+    /// installed version.
+    pub async fn download_binary(
         &self,
+        version: Option<&str>,
         progress_tx: Option<DownloadProgressSender>,
-    ) -> Result<(), MonitorError> {
-        let asset = self.fetch_latest_asset().await?;
+        cancel: Option<CancelToken>,
+    ) -> Result<String, MonitorError> {
+        let release = self.fetch_target_release(version).await?;
+        let (asset, checksums_asset) = self.select_release_assets(&release).await?;
+        let expected_sha256 = self
+            .fetch_verified_checksum(&checksums_asset, &asset.name)
+            .await?;
         let app_root = Config::app_root_dir()?;
         let tarball_path = app_root.join(&asset.name);
 
@@ -85,18 +140,69 @@ impl Installer {
             &asset.browser_download_url,
             &tarball_path,
             progress_tx,
+            cancel.as_ref(),
         )
         .await?;
 
-        self.extract_binary(&tarball_path).await?;
+        if let Err(err) = checksum::verify_sha256(&tarball_path, &expected_sha256).await {
+            let _ = fs::remove_file(&tarball_path).await;
+            return Err(err);
+        }
+
+        let extract_result = self.extract_binary(&tarball_path).await;
         let _ = fs::remove_file(&tarball_path).await;
-        Ok(())
+        extract_result?;
+        Ok(release.tag_name.trim_start_matches('v').to_string())
+    }
+
+    /// Returns the Syncthing archive waiting in the sideload directory, if any.
+    pub async fn local_archive(&self) -> Option<SyncthingArchive> {
+        let architecture = match detect_architecture(self.runner.as_ref()).await {
+            Ok(architecture) => architecture,
+            Err(err) => {
+                warn!(error = ?err, "Cannot look for sideloaded Syncthing archives");
+                return None;
+            }
+        };
+        match sideload::find_syncthing_archive(
+            Path::new(&self.config.sideload_dir),
+            architecture.syncthing_asset_prefix(),
+            self.config.pinned_syncthing_version.as_deref(),
+        )
+        .await
+        {
+            Ok(archive) => archive,
+            Err(err) => {
+                warn!(error = ?err, "Failed to scan sideload directory");
+                None
+            }
+        }
+    }
+
+    /// Installs the Syncthing binary from the sideload directory without network access.
+    ///
+    /// The tarball goes through the same checksum and signature checks as a download
+    /// and is removed once installed. Returns the installed version.
+    pub async fn install_local_binary(&self) -> Result<String, MonitorError> {
+        let archive = self.local_archive().await.ok_or_else(|| {
+            MonitorError::Config(format!(
+                "No Syncthing archive with {} found in {}",
+                CHECKSUMS_ASSET_NAME, self.config.sideload_dir
+            ))
+        })?;
+        let signed = fs::read_to_string(&archive.checksums).await?;
+        let expected_sha256 = Self::checksum_from_signed_list(&signed, &archive.file_name())?;
+        checksum::verify_sha256(&archive.tarball, &expected_sha256).await?;
+
+        self.extract_binary(&archive.tarball).await?;
+        sideload::remove_consumed(&archive.tarball).await;
+        Ok(archive.version.to_string())
     }
 
     pub async fn install_service(&self) -> Result<(), MonitorError> {
-        let was_readonly = filesystem::remount_root_rw().await?;
+        let guard = self.mounts.writable().await?;
         let service_result = self.install_service_inner().await;
-        let restore_result = filesystem::restore_mounts_if_needed(was_readonly).await;
+        let restore_result = guard.release().await;
 
         if let Err(err) = &restore_result {
             error!(error = ?err, "Failed to restore mounts after installer run");
@@ -107,24 +213,98 @@ impl Installer {
 
     pub async fn restart_service(&self) -> Result<(), MonitorError> {
         let service_name = &self.config.systemd_service_name;
-        systemctl::execute(&["restart", service_name]).await
+        systemctl::execute(self.runner.as_ref(), &["restart", service_name]).await
     }
 
-    fn binary_path(&self) -> Result<PathBuf, MonitorError> {
+    pub(super) fn binary_path(&self) -> Result<PathBuf, MonitorError> {
         self.config.syncthing_binary_path()
     }
 
-    async fn fetch_latest_asset(&self) -> Result<ReleaseAsset, MonitorError> {
-        let architecture = detect_architecture().await?;
+    pub(super) async fn fetch_target_release(
+        &self,
+        version: Option<&str>,
+    ) -> Result<Release, MonitorError> {
+        let requested = version.or(self.config.pinned_syncthing_version.as_deref());
+        if let Some(version) = requested {
+            return self.feed.release(version).await.map_err(|err| {
+                MonitorError::Config(format!(
+                    "Syncthing release {} is not available: {}",
+                    version, err
+                ))
+            });
+        }
+
+        let channel = self.config.release_channel;
+        let releases = self.feed.releases().await?;
+        assets::select_latest_release(&releases, channel)
+            .cloned()
+            .ok_or_else(|| {
+                MonitorError::Config(format!(
+                    "No Syncthing release found on the {} channel",
+                    channel
+                ))
+            })
+    }
+
+    /// Returns the tarball for this architecture and the signed checksum list.
+    async fn select_release_assets(
+        &self,
+        release: &Release,
+    ) -> Result<(ReleaseAsset, ReleaseAsset), MonitorError> {
+        let architecture = detect_architecture(self.runner.as_ref()).await?;
         let asset_prefix = architecture.syncthing_asset_prefix();
-        let release = assets::fetch_release(&self.client, RELEASE_API_URL).await?;
-        assets::select_asset_by_prefix(&release.assets, asset_prefix, TAR_EXTENSION)
+        let asset = assets::select_asset_by_prefix(&release.assets, asset_prefix, TAR_EXTENSION)
             .cloned()
             .ok_or_else(|| {
                 MonitorError::Config(format!(
-                    "Latest Syncthing release does not contain the expected {} asset",
+                    "Syncthing release {} does not contain the expected {} asset",
+                    release.tag_name,
                     architecture.description()
                 ))
+            })?;
+        let checksums = assets::select_asset_exact(&release.assets, CHECKSUMS_ASSET_NAME)
+            .cloned()
+            .ok_or_else(|| {
+                MonitorError::Verification(format!(
+                    "Syncthing release {} does not publish {}",
+                    release.tag_name, CHECKSUMS_ASSET_NAME
+                ))
+            })?;
+        Ok((asset, checksums))
+    }
+
+    /// Downloads the clear-signed checksum list, verifies it against the embedded
+    /// release key and returns the expected SHA-256 for `asset_name`.
+    async fn fetch_verified_checksum(
+        &self,
+        checksums_asset: &ReleaseAsset,
+        asset_name: &str,
+    ) -> Result<String, MonitorError> {
+        // Fail before downloading anything if the checksums cannot be authenticated.
+        syncthing_release_keyring()?;
+        let signed = self
+            .client
+            .get(&checksums_asset.browser_download_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Self::checksum_from_signed_list(&signed, asset_name)
+    }
+
+    /// Verifies a clear-signed `sha256sum.txt.asc` against the embedded release key
+    /// and returns the expected SHA-256 for `asset_name`.
+    fn checksum_from_signed_list(signed: &str, asset_name: &str) -> Result<String, MonitorError> {
+        let checksums = syncthing_release_keyring()?.verify_cleartext(signed)?;
+
+        checksum::find_checksum(&checksums, asset_name)
+            .map(str::to_string)
+            .ok_or_else(|| {
+                MonitorError::Verification(format!(
+                    "{} has no checksum for {}",
+                    CHECKSUMS_ASSET_NAME, asset_name
+                ))
             })
     }
 
@@ -132,19 +312,33 @@ impl Installer {
         let binary_path = self.binary_path()?;
         let archive_root = Self::expected_archive_root(tarball_path)?;
         let archive_binary_path = PathBuf::from(&archive_root).join("syncthing");
+        // Extracted next to the live binary so a running service keeps working
+        // until the rename, which also makes reinstalls and downgrades atomic.
+        let new_binary_path = binary_path.with_extension("new");
+
+        let result = async {
+            archive::extract_tarball_file(tarball_path, &archive_binary_path, &new_binary_path)
+                .await?;
+            Self::validate_syncthing_binary(&new_binary_path)?;
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let permissions = std::fs::Permissions::from_mode(0o755);
+                fs::set_permissions(&new_binary_path, permissions).await?;
+            }
+            // A binary that cannot run on this CPU must not replace a working one.
+            run_version_check(self.runner.as_ref(), &new_binary_path).await?;
 
-        archive::extract_tarball_file(tarball_path, &archive_binary_path, &binary_path).await?;
-
-        Self::validate_syncthing_binary(&binary_path)?;
-
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let permissions = std::fs::Permissions::from_mode(0o755);
-            fs::set_permissions(&binary_path, permissions).await?;
+            fs::rename(&new_binary_path, &binary_path).await?;
+            Ok(())
         }
+        .await;
 
-        Ok(())
+        if result.is_err() {
+            let _ = fs::remove_file(&new_binary_path).await;
+        }
+        result
     }
 
     fn expected_archive_root(tarball_path: &Path) -> Result<String, MonitorError> {
@@ -199,29 +393,74 @@ impl Installer {
     }
 
     async fn install_service_inner(&self) -> Result<(), MonitorError> {
-        if let Err(err) = filesystem::unmount_etc_if_needed().await {
-            warn!(error = ?err, "Warning during installer unmount");
-        }
         self.write_service_file().await?;
-        systemctl::execute(&["daemon-reload"]).await?;
+        self.write_tuning_dropin().await?;
+        self.apply_service_profile().await?;
+        systemctl::execute(self.runner.as_ref(), &["daemon-reload"]).await?;
         let service_name = &self.config.systemd_service_name;
-        systemctl::execute(&["enable", service_name]).await?;
-        systemctl::execute(&["start", service_name]).await
+        systemctl::execute(self.runner.as_ref(), &["enable", service_name]).await?;
+        systemctl::execute(self.runner.as_ref(), &["start", service_name]).await
     }
 
-    async fn write_service_file(&self) -> Result<(), MonitorError> {
-        let unit_dir = Path::new("/etc/systemd/system");
-        if !unit_dir.exists() {
-            fs::create_dir_all(unit_dir).await?;
+    pub(super) async fn write_service_file(&self) -> Result<(), MonitorError> {
+        let unit_path = self.unit_path();
+        if let Some(unit_dir) = unit_path.parent() {
+            if !unit_dir.exists() {
+                fs::create_dir_all(unit_dir).await?;
+            }
         }
-        let unit_path = unit_dir.join(&self.config.systemd_service_name);
         let binary = self.binary_path()?;
         let contents = self.render_service_unit(&binary);
         fs::write(&unit_path, contents).await?;
         Ok(())
     }
 
-    fn render_service_unit(&self, binary_path: &Path) -> String {
+    pub(super) fn unit_path(&self) -> PathBuf {
+        self.unit_dir.join(&self.config.systemd_service_name)
+    }
+
+    /// Path of the drop-in `name` in the unit's `.d` directory.
+    pub(super) fn dropin_path(&self, name: &str) -> PathBuf {
+        self.unit_dir
+            .join(format!("{}.d", self.config.systemd_service_name))
+            .join(name)
+    }
+
+    /// Writes the drop-in `name`, or removes it for `None` together with its
+    /// directory once that is empty.
+    pub(super) async fn write_dropin(
+        &self,
+        name: &str,
+        contents: Option<String>,
+    ) -> Result<(), MonitorError> {
+        let path = self.dropin_path(name);
+        let Some(contents) = contents else {
+            match fs::remove_file(&path).await {
+                Ok(()) => info!(path = %path.display(), "Removed systemd drop-in"),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+                Err(err) => return Err(err.into()),
+            }
+            if let Some(dir) = path.parent() {
+                // Drop-ins added by hand may live there; only an empty directory goes.
+                let _ = fs::remove_dir(dir).await;
+            }
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        fs::write(&path, contents).await?;
+        info!(path = %path.display(), "Wrote systemd drop-in");
+        Ok(())
+    }
+
+    pub(super) fn render_service_unit(&self, binary_path: &Path) -> String {
+        // A pinned version must not be replaced by Syncthing's own auto-upgrade.
+        let upgrade_environment = if self.config.pinned_syncthing_version.is_some() {
+            "Environment=STNOUPGRADE=1\n"
+        } else {
+            ""
+        };
         format!(
             "[Unit]
 Description=Syncthing
@@ -234,7 +473,7 @@ StartLimitBurst=4
 User=root
 WorkingDirectory=/home/root
 Environment=HOME=/home/root
-ExecStart={} serve --no-browser --no-restart --home={}
+{}ExecStart={}
 Restart=on-failure
 RestartSec=5
 SuccessExitStatus=3 4
@@ -243,6 +482,14 @@ RestartForceExitStatus=3 4
 [Install]
 WantedBy=multi-user.target
 ",
+            upgrade_environment,
+            self.exec_start(binary_path)
+        )
+    }
+
+    pub(super) fn exec_start(&self, binary_path: &Path) -> String {
+        format!(
+            "{} serve --no-browser --no-restart --home={}",
             binary_path.display(),
             self.config.syncthing_config_dir
         )
@@ -256,6 +503,48 @@ mod tests {
     use tempfile::NamedTempFile;
 
     use super::*;
+    use crate::utils::command::fake::{failure, not_found, success, ScriptedRunner};
+    use crate::utils::filesystem::fake::{FakeMounts, ETC_OVERLAY_MOUNT, READ_ONLY_ROOT};
+    use crate::utils::system_runner;
+
+    fn installer(runner: ScriptedRunner) -> (Installer, FakeMounts) {
+        let runner = runner.shared();
+        let fake = FakeMounts::new(runner.clone(), READ_ONLY_ROOT);
+        let installer = Installer::new(Config::default(), runner, fake.mounts.clone());
+        (installer, fake)
+    }
+
+    /// An installer writing units to a temporary directory, with the writes
+    /// wrapped in the remount of a read-only root.
+    fn unit_installer(
+        systemctl: Vec<(&str, std::io::Result<std::process::Output>)>,
+    ) -> (Installer, FakeMounts, tempfile::TempDir) {
+        let runner = ScriptedRunner::new()
+            .expect("mount -o remount,rw /", success(""))
+            .expect("umount -R /etc", success(""));
+        let runner = systemctl
+            .into_iter()
+            .fold(runner, |runner, (command, result)| {
+                runner.expect(command, result)
+            })
+            .expect("mount -o remount,ro /", success(""))
+            .expect(ETC_OVERLAY_MOUNT, success(""))
+            .shared();
+        let dir = tempfile::tempdir().expect("create unit dir");
+        let fake = FakeMounts::new(runner.clone(), READ_ONLY_ROOT);
+        let config = Config {
+            syncthing_config_dir: dir.path().join("home").to_string_lossy().into_owned(),
+            ..Config::default()
+        };
+        let mut installer = Installer::new(config, runner, fake.mounts.clone());
+        installer.unit_dir = dir.path().join("units");
+        (installer, fake, dir)
+    }
+
+    fn unmounted_installer(config: Config) -> Installer {
+        let runner = system_runner();
+        Installer::new(config, runner.clone(), RootMounts::new(runner))
+    }
 
     #[test]
     fn expected_archive_root_strips_tar_gz_suffix() {
@@ -274,6 +563,20 @@ mod tests {
         .is_err());
     }
 
+    #[test]
+    fn service_unit_disables_self_upgrade_only_when_pinned() {
+        let binary = Path::new("/home/root/syncthing");
+        let unpinned = unmounted_installer(Config::default()).render_service_unit(binary);
+        let pinned = unmounted_installer(Config {
+            pinned_syncthing_version: Some("1.27.12".to_string()),
+            ..Config::default()
+        })
+        .render_service_unit(binary);
+
+        assert!(!unpinned.contains("STNOUPGRADE"));
+        assert!(pinned.contains("Environment=STNOUPGRADE=1\nExecStart=/home/root/syncthing serve"));
+    }
+
     #[test]
     fn validate_syncthing_binary_accepts_elf_header() {
         let binary = NamedTempFile::new().expect("create binary tempfile");
@@ -293,4 +596,86 @@ mod tests {
 
         assert!(Installer::validate_syncthing_binary(profile.path()).is_err());
     }
+
+    #[tokio::test]
+    async fn service_is_installed_only_when_systemctl_finds_the_unit() {
+        let (installer, _mounts) = installer(
+            ScriptedRunner::new()
+                .expect("systemctl cat syncthing.service", success("[Unit]\n"))
+                .expect(
+                    "systemctl cat syncthing.service",
+                    failure(1, "No files found for syncthing.service."),
+                )
+                .expect("systemctl cat syncthing.service", not_found()),
+        );
+
+        assert!(installer.service_installed().await);
+        assert!(!installer.service_installed().await);
+        assert!(!installer.service_installed().await);
+    }
+
+    #[tokio::test]
+    async fn service_is_reloaded_enabled_and_started_on_a_writable_root() {
+        let (installer, mounts, dir) = unit_installer(vec![
+            ("systemctl daemon-reload", success("")),
+            ("systemctl enable syncthing.service", success("")),
+            ("systemctl start syncthing.service", success("")),
+        ]);
+
+        installer.install_service().await.expect("install service");
+
+        let unit =
+            std::fs::read_to_string(dir.path().join("units/syncthing.service")).expect("read unit");
+        assert!(unit.contains("ExecStart="), "{}", unit);
+        assert!(!mounts.marker_present());
+    }
+
+    #[tokio::test]
+    async fn failed_enable_skips_start_and_restores_mounts() {
+        let (installer, mounts, _dir) = unit_installer(vec![
+            ("systemctl daemon-reload", success("")),
+            (
+                "systemctl enable syncthing.service",
+                failure(
+                    1,
+                    "Failed to enable unit: Unit file syncthing.service is masked.",
+                ),
+            ),
+        ]);
+
+        let err = installer.install_service().await.expect_err("enable fails");
+        assert!(err.to_string().contains("masked"), "{}", err);
+        assert!(!mounts.marker_present());
+    }
+
+    #[tokio::test]
+    async fn failed_start_is_reported_after_mounts_are_restored() {
+        let (installer, mounts, _dir) = unit_installer(vec![
+            ("systemctl daemon-reload", success("")),
+            ("systemctl enable syncthing.service", success("")),
+            (
+                "systemctl start syncthing.service",
+                failure(1, "Job for syncthing.service failed."),
+            ),
+        ]);
+
+        let err = installer.install_service().await.expect_err("start fails");
+        assert!(err.to_string().contains("systemctl start"), "{}", err);
+        assert!(!mounts.marker_present());
+    }
+
+    #[tokio::test]
+    async fn service_is_not_installed_when_root_stays_read_only() {
+        let (installer, mounts) = installer(ScriptedRunner::new().expect(
+            "mount -o remount,rw /",
+            failure(32, "mount: /: permission denied"),
+        ));
+
+        let err = installer
+            .install_service()
+            .await
+            .expect_err("remount fails");
+        assert!(err.to_string().contains("permission denied"), "{}", err);
+        assert!(!mounts.marker_present());
+    }
 }
@@ -0,0 +1,332 @@
+//! Safeguards for Syncthing major-version upgrades.
+//!
+//! A major release may migrate the index database on first start, after which the
+//! old binary can no longer read it. Before such an upgrade the Syncthing home is
+//! snapshotted next to itself and the current binary kept as `syncthing.prev`, so
+//! both can be put back together. Once the new version is confirmed running the
+//! snapshot is deleted, as it doubles the space Syncthing's state takes.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+use tracing::{error, info, warn};
+
+use crate::types::MonitorError;
+use crate::utils::systemctl;
+
+use super::binary_upgrade::{keep_previous_binary, previous_binary_path};
+use super::installer::Installer;
+
+/// Files identifying the device and its configuration.
+const STATE_FILES: [&str; 5] = [
+    "config.xml",
+    "cert.pem",
+    "key.pem",
+    "https-cert.pem",
+    "https-key.pem",
+];
+/// Prefix shared by every index database layout Syncthing has used.
+const INDEX_PREFIX: &str = "index-";
+const SNAPSHOT_SUFFIX: &str = ".pre-upgrade";
+/// Headroom left free on top of the snapshot and the migrated database.
+const SPACE_MARGIN_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Copy of the Syncthing home taken before a major upgrade.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateSnapshot {
+    pub dir: PathBuf,
+    /// Version whose state the snapshot holds.
+    pub version: String,
+}
+
+impl Installer {
+    /// Checks that a major upgrade away from `running` can be rolled back and
+    /// prepares the rollback: keeps the current binary and snapshots the state
+    /// with the service stopped.
+    pub async fn prepare_major_upgrade(
+        &self,
+        running: &str,
+    ) -> Result<StateSnapshot, MonitorError> {
+        let binary = self.binary_path()?;
+        if fs::metadata(&binary).await.is_err() {
+            return Err(MonitorError::Config(format!(
+                "Syncthing is not installed at {}, so a major upgrade could not be rolled back",
+                binary.display()
+            )));
+        }
+
+        let home = PathBuf::from(&self.config.syncthing_config_dir);
+        let usage = state_usage(&home).await?;
+        let required = usage.snapshot_bytes + usage.index_bytes + SPACE_MARGIN_BYTES;
+        let available = available_space(&home).await?;
+        if available < required {
+            return Err(MonitorError::Config(format!(
+                "Not enough free space for a major Syncthing upgrade: {} MiB needed, {} MiB available",
+                required.div_ceil(1024 * 1024),
+                available / (1024 * 1024)
+            )));
+        }
+
+        keep_previous_binary(&binary, &previous_binary_path(&binary)).await?;
+
+        let service_name = &self.config.systemd_service_name;
+        systemctl::execute(self.runner.as_ref(), &["stop", service_name]).await?;
+        let snapshot = snapshot_state(&home, running).await;
+        let started = systemctl::execute(self.runner.as_ref(), &["start", service_name]).await;
+        let snapshot = snapshot?;
+        started?;
+        info!(path = %snapshot.dir.display(), version = %snapshot.version, "Snapshotted Syncthing state before major upgrade");
+        Ok(snapshot)
+    }
+
+    /// Puts back the binary and state from before a major upgrade.
+    pub async fn roll_back_major_upgrade(
+        &self,
+        snapshot: &StateSnapshot,
+    ) -> Result<(), MonitorError> {
+        let home = PathBuf::from(&self.config.syncthing_config_dir);
+        let service_name = &self.config.systemd_service_name;
+        if let Err(err) = systemctl::execute(self.runner.as_ref(), &["stop", service_name]).await {
+            warn!(error = ?err, "Failed to stop Syncthing before rollback");
+        }
+
+        let restored = async {
+            self.restore_previous_binary().await?;
+            restore_state(snapshot, &home).await
+        }
+        .await;
+        if let Err(err) = &restored {
+            error!(error = ?err, "Failed to roll back major Syncthing upgrade");
+        }
+
+        let started = systemctl::execute(self.runner.as_ref(), &["start", service_name]).await;
+        restored.and(started)?;
+        info!(version = %snapshot.version, "Rolled back to Syncthing v{}", snapshot.version);
+        Ok(())
+    }
+
+    /// Deletes the snapshot of a major upgrade that is confirmed running. A
+    /// failure is only logged; the snapshot is then removed on uninstall.
+    pub async fn discard_major_upgrade_snapshot(&self, snapshot: &StateSnapshot) {
+        match discard_snapshot(snapshot).await {
+            Ok(()) => info!(path = %snapshot.dir.display(), "Removed Syncthing state snapshot"),
+            Err(err) => {
+                warn!(path = %snapshot.dir.display(), error = ?err, "Failed to remove Syncthing state snapshot")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct StateUsage {
+    /// Everything the snapshot copies.
+    snapshot_bytes: u64,
+    /// The index database alone, which a migration rewrites.
+    index_bytes: u64,
+}
+
+fn is_state_entry(name: &str) -> bool {
+    STATE_FILES.contains(&name) || name.starts_with(INDEX_PREFIX)
+}
+
+pub(super) fn snapshot_path(home: &Path) -> PathBuf {
+    let mut name = home
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    name.push(SNAPSHOT_SUFFIX);
+    home.with_file_name(name)
+}
+
+async fn state_usage(home: &Path) -> Result<StateUsage, MonitorError> {
+    let home = home.to_path_buf();
+    run_blocking(move || {
+        let mut usage = StateUsage::default();
+        for entry in std::fs::read_dir(&home)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !is_state_entry(&name) {
+                continue;
+            }
+            let size = tree_size(&entry.path())?;
+            usage.snapshot_bytes += size;
+            if name.starts_with(INDEX_PREFIX) {
+                usage.index_bytes += size;
+            }
+        }
+        Ok(usage)
+    })
+    .await
+}
+
+/// Copies the state entries of `home` into a fresh snapshot directory.
+async fn snapshot_state(home: &Path, version: &str) -> Result<StateSnapshot, MonitorError> {
+    let home = home.to_path_buf();
+    let dir = snapshot_path(&home);
+    let target = dir.clone();
+    run_blocking(move || {
+        if target.exists() {
+            std::fs::remove_dir_all(&target)?;
+        }
+        std::fs::create_dir_all(&target)?;
+        for entry in std::fs::read_dir(&home)? {
+            let entry = entry?;
+            if is_state_entry(&entry.file_name().to_string_lossy()) {
+                copy_tree(&entry.path(), &target.join(entry.file_name()))?;
+            }
+        }
+        Ok(())
+    })
+    .await?;
+    Ok(StateSnapshot {
+        dir,
+        version: version.to_string(),
+    })
+}
+
+/// Replaces the state entries of `home` with those in the snapshot, dropping any
+/// database layout the newer version created.
+async fn restore_state(snapshot: &StateSnapshot, home: &Path) -> Result<(), MonitorError> {
+    let home = home.to_path_buf();
+    let dir = snapshot.dir.clone();
+    run_blocking(move || {
+        for entry in std::fs::read_dir(&home)? {
+            let entry = entry?;
+            if is_state_entry(&entry.file_name().to_string_lossy()) {
+                remove_tree(&entry.path())?;
+            }
+        }
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &home.join(entry.file_name()))?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+async fn discard_snapshot(snapshot: &StateSnapshot) -> Result<(), MonitorError> {
+    match fs::remove_dir_all(&snapshot.dir).await {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+async fn available_space(path: &Path) -> Result<u64, MonitorError> {
+    let path = path.to_path_buf();
+    run_blocking(move || {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(c_path.as_ptr(), &mut stats) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(stats.f_bavail as u64 * stats.f_frsize as u64)
+    })
+    .await
+}
+
+fn tree_size(path: &Path) -> io::Result<u64> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        total += tree_size(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+fn copy_tree(source: &Path, dest: &Path) -> io::Result<()> {
+    if !std::fs::symlink_metadata(source)?.is_dir() {
+        std::fs::copy(source, dest)?;
+        return Ok(());
+    }
+    std::fs::create_dir_all(dest)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        copy_tree(&entry.path(), &dest.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+fn remove_tree(path: &Path) -> io::Result<()> {
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+async fn run_blocking<T, F>(operation: F) -> Result<T, MonitorError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(operation)
+        .await
+        .map_err(|err| MonitorError::Io(io::Error::other(err)))?
+        .map_err(MonitorError::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("create parent dir");
+        }
+        std::fs::write(path, contents).expect("write state file");
+    }
+
+    #[tokio::test]
+    async fn snapshot_restores_state_and_drops_migrated_database() {
+        let root = tempfile::tempdir().expect("create root");
+        let home = root.path().join("syncthing");
+        write(&home.join("config.xml"), "<configuration/>");
+        write(&home.join("key.pem"), "key");
+        write(&home.join("index-v0.14.0.db/000001.ldb"), "leveldb");
+        write(&home.join("syncthing.log"), "log");
+
+        let usage = state_usage(&home).await.expect("measure state");
+        assert_eq!(
+            usage,
+            StateUsage {
+                snapshot_bytes: 26,
+                index_bytes: 7,
+            }
+        );
+
+        let snapshot = snapshot_state(&home, "1.29.2").await.expect("snapshot");
+        assert_eq!(snapshot.dir, root.path().join("syncthing.pre-upgrade"));
+        assert!(!snapshot.dir.join("syncthing.log").exists());
+
+        std::fs::remove_dir_all(home.join("index-v0.14.0.db")).expect("migrate away");
+        write(&home.join("index-v2/index.db"), "sqlite");
+        write(&home.join("config.xml"), "<configuration version=\"51\"/>");
+
+        restore_state(&snapshot, &home).await.expect("restore");
+
+        assert!(!home.join("index-v2").exists());
+        assert_eq!(
+            std::fs::read_to_string(home.join("index-v0.14.0.db/000001.ldb")).expect("read db"),
+            "leveldb"
+        );
+        assert_eq!(
+            std::fs::read_to_string(home.join("config.xml")).expect("read config"),
+            "<configuration/>"
+        );
+        assert!(home.join("syncthing.log").exists());
+
+        discard_snapshot(&snapshot).await.expect("discard");
+        assert!(!snapshot.dir.exists());
+        discard_snapshot(&snapshot)
+            .await
+            .expect("discarding twice is a no-op");
+    }
+}
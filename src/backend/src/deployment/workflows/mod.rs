@@ -1,4 +1,15 @@
 //! High-level deployment workflows (installer and updater).
 
+pub mod binary_upgrade;
+pub mod hardening;
 pub mod installer;
+pub mod major_upgrade;
+pub mod provisioning;
+pub mod rollback;
+pub mod service_tuning;
+pub mod sideload;
+pub mod smoke_test;
+mod staging;
+pub mod uninstall;
+pub mod unit_drift;
 pub mod updater;
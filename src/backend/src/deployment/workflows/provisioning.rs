@@ -0,0 +1,193 @@
+//! First-run setup of a freshly installed Syncthing instance from `config.json`,
+//! so every tablet comes up with the same settings.
+
+use serde_json::Value;
+use tokio::fs;
+use tracing::info;
+
+use crate::config::ProvisioningConfig;
+use crate::deployment::system::device::DeviceIdentity;
+use crate::syncthing_client::SyncthingClient;
+use crate::types::MonitorError;
+
+use super::installer::Installer;
+
+const DEFAULT_FOLDER_ID: &str = "default";
+/// Port the app reaches the Syncthing API on.
+const API_PORT_SUFFIX: &str = ":8384";
+
+impl Installer {
+    /// Whether this install creates a new instance that should be provisioned.
+    pub async fn needs_provisioning(&self) -> bool {
+        self.config.provisioning.enabled
+            && fs::metadata(self.config.syncthing_config_xml_path())
+                .await
+                .is_err()
+    }
+
+    /// Creates the configuration and keys with `syncthing generate`, including the
+    /// GUI credentials when a password is configured. The password goes over
+    /// stdin, as arguments are readable by any process in `/proc/<pid>/cmdline`.
+    pub async fn generate_config(&self) -> Result<(), MonitorError> {
+        let settings = &self.config.provisioning;
+        let binary = self.binary_path()?;
+        let binary = binary.to_string_lossy();
+        let home = format!("--home={}", self.config.syncthing_config_dir);
+        let output = match &settings.gui_password {
+            Some(password) => {
+                let user = format!("--gui-user={}", settings.gui_user);
+                let args = ["generate", &home, &user, "--gui-password=-"];
+                self.runner
+                    .output_with_input(&binary, &args, password.expose().as_bytes())
+                    .await?
+            }
+            None => self.runner.output(&binary, &["generate", &home]).await?,
+        };
+        if !output.status.success() {
+            return Err(MonitorError::Config(format!(
+                "syncthing generate failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        info!(home = %self.config.syncthing_config_dir, "Generated Syncthing configuration");
+        Ok(())
+    }
+
+    /// Applies the device name, GUI address and default-folder setting through
+    /// the API of the running instance.
+    pub async fn provision_instance(&self) -> Result<(), MonitorError> {
+        let mut client = SyncthingClient::discover(&self.config).await?;
+        let my_id = client.my_id().await?;
+        let device_name = DeviceIdentity::detect()
+            .await
+            .render_name(&self.config.provisioning.device_name);
+
+        let mut config = client.get_config().await?;
+        apply_provisioning(&mut config, &my_id, &device_name, &self.config.provisioning)?;
+        client.put_config(&config).await?;
+        info!(device_name = %device_name, "Provisioned Syncthing instance");
+        Ok(())
+    }
+}
+
+fn apply_provisioning(
+    config: &mut Value,
+    my_id: &str,
+    device_name: &str,
+    settings: &ProvisioningConfig,
+) -> Result<(), MonitorError> {
+    if !settings.gui_address.ends_with(API_PORT_SUFFIX) {
+        return Err(MonitorError::Config(format!(
+            "provisioning.gui_address must use port 8384, got {}",
+            settings.gui_address
+        )));
+    }
+
+    let device = config
+        .get_mut("devices")
+        .and_then(Value::as_array_mut)
+        .and_then(|devices| {
+            devices
+                .iter_mut()
+                .find(|device| device.get("deviceID").and_then(Value::as_str) == Some(my_id))
+        })
+        .ok_or_else(|| {
+            MonitorError::Syncthing(format!("Own device {} missing from /rest/config", my_id))
+        })?;
+    device["name"] = Value::String(device_name.to_string());
+
+    let gui = config
+        .get_mut("gui")
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| MonitorError::Syncthing("/rest/config has no GUI object".to_string()))?;
+    gui.insert(
+        "address".to_string(),
+        Value::String(settings.gui_address.clone()),
+    );
+
+    if settings.remove_default_folder {
+        if let Some(folders) = config.get_mut("folders").and_then(Value::as_array_mut) {
+            folders.retain(|folder| {
+                folder.get("id").and_then(Value::as_str) != Some(DEFAULT_FOLDER_ID)
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::utils::command::fake::{failure, ScriptedRunner};
+    use crate::utils::filesystem::RootMounts;
+
+    #[test]
+    fn provisioning_names_own_device_and_drops_default_folder() {
+        let mut config = json!({
+            "devices": [
+                {"deviceID": "PEER", "name": "laptop"},
+                {"deviceID": "SELF", "name": "reMarkable"}
+            ],
+            "folders": [
+                {"id": "default", "path": "/home/root/Sync"},
+                {"id": "notes", "path": "/home/root/notes"}
+            ],
+            "gui": {"address": "127.0.0.1:8384", "user": "syncthing"}
+        });
+        let settings = ProvisioningConfig {
+            remove_default_folder: true,
+            gui_address: "0.0.0.0:8384".to_string(),
+            ..ProvisioningConfig::default()
+        };
+
+        apply_provisioning(&mut config, "SELF", "reMarkable 0x1234", &settings)
+            .expect("apply provisioning");
+
+        assert_eq!(config["devices"][0]["name"], "laptop");
+        assert_eq!(config["devices"][1]["name"], "reMarkable 0x1234");
+        assert_eq!(config["gui"]["address"], "0.0.0.0:8384");
+        assert_eq!(config["gui"]["user"], "syncthing");
+        assert_eq!(config["folders"].as_array().map(Vec::len), Some(1));
+        assert_eq!(config["folders"][0]["id"], "notes");
+    }
+
+    #[tokio::test]
+    async fn gui_password_is_passed_over_stdin() {
+        let config: Config = serde_json::from_value(json!({
+            "syncthing_config_dir": "/home/root/.config/syncthing",
+            "provisioning": {"gui_password": "hunter2"}
+        }))
+        .expect("parse config");
+        let binary = config.syncthing_binary_path().expect("binary path");
+        let runner = std::sync::Arc::new(ScriptedRunner::new().expect(
+            &format!(
+                "{} generate --home=/home/root/.config/syncthing --gui-user=syncthing --gui-password=-",
+                binary.display()
+            ),
+            failure(1, "config.xml already exists"),
+        ));
+        let installer = Installer::new(config, runner.clone(), RootMounts::new(runner.clone()));
+
+        let err = installer
+            .generate_config()
+            .await
+            .expect_err("generate fails");
+        assert!(err.to_string().contains("already exists"), "{}", err);
+        assert_eq!(runner.inputs(), [b"hunter2".to_vec()]);
+    }
+
+    #[test]
+    fn gui_address_on_another_port_is_rejected() {
+        let mut config = json!({"devices": [{"deviceID": "SELF"}], "gui": {}});
+        let settings = ProvisioningConfig {
+            gui_address: "0.0.0.0:9999".to_string(),
+            ..ProvisioningConfig::default()
+        };
+
+        assert!(apply_provisioning(&mut config, "SELF", "tablet", &settings).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+//! Backup and rollback of the app bundle around self-updates.
+//!
+//! The tree replaced by an update is kept as `.rollback/previous`, without user
+//! data. The new version is then on probation: every launch is
+//! counted in a boot-pending marker until the frontend connects. If that does not
+//! happen within [`MAX_UNHEALTHY_LAUNCHES`] launches, the backup is restored.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::types::MonitorError;
+
+use super::updater::Updater;
+
+pub const ROLLBACK_DIR_NAME: &str = ".rollback";
+const BACKUP_DIR_NAME: &str = "previous";
+const BOOT_PENDING_FILE: &str = "boot-pending.json";
+const ROLLBACK_NOTICE_FILE: &str = "rolled-back.json";
+
+/// Launches without a connected frontend before the previous version is restored.
+pub const MAX_UNHEALTHY_LAUNCHES: u32 = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BootPending {
+    version: Option<String>,
+    previous_version: Option<String>,
+    launches: u32,
+}
+
+/// Left behind after a rollback so the restored version can report it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollbackNotice {
+    pub from_version: Option<String>,
+    pub to_version: Option<String>,
+    pub automatic: bool,
+}
+
+impl RollbackNotice {
+    pub fn describe(&self) -> String {
+        let from = self.from_version.as_deref().unwrap_or("unknown");
+        let to = self.to_version.as_deref().unwrap_or("the previous version");
+        if self.automatic {
+            format!(
+                "Version {} did not start correctly and was rolled back to {}.",
+                from, to
+            )
+        } else {
+            format!("Rolled back from version {} to {}.", from, to)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LaunchOutcome {
+    Normal,
+    /// The running binary was replaced by the backup and should exit.
+    RolledBack(RollbackNotice),
+}
+
+impl Updater {
+    /// Counts a launch of a freshly updated version and rolls back once it has
+    /// failed to become healthy too often.
+    pub async fn record_launch(&self) -> Result<LaunchOutcome, MonitorError> {
+        record_launch_in(self, &Config::app_root_dir()?).await
+    }
+
+    /// Clears the boot-pending marker once the frontend has connected.
+    pub async fn confirm_healthy() -> Result<(), MonitorError> {
+        let marker = rollback_dir(&Config::app_root_dir()?).join(BOOT_PENDING_FILE);
+        match fs::remove_file(&marker).await {
+            Ok(()) => {
+                info!("Updated app reached a healthy state");
+                Ok(())
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Returns and clears the notice written by a previous rollback.
+    pub async fn take_rollback_notice() -> Option<RollbackNotice> {
+        let path = rollback_dir(&Config::app_root_dir().ok()?).join(ROLLBACK_NOTICE_FILE);
+        let contents = fs::read(&path).await.ok()?;
+        let _ = fs::remove_file(&path).await;
+        serde_json::from_slice(&contents).ok()
+    }
+
+    pub async fn rollback_available() -> bool {
+        match Config::app_root_dir() {
+            Ok(app_root) => backup_present(&app_root).await,
+            Err(_) => false,
+        }
+    }
+
+    /// Restores the backed-up version on user request.
+    pub async fn roll_back_to_previous(&self) -> Result<RollbackNotice, MonitorError> {
+        let app_root = Config::app_root_dir()?;
+        restore_backup(self, &app_root, false).await
+    }
+
+    /// Puts the freshly applied version on probation until it reports healthy.
+    pub(super) async fn mark_boot_pending(
+        &self,
+        app_root: &Path,
+        previous_version: Option<String>,
+    ) -> Result<(), MonitorError> {
+        let marker = BootPending {
+            version: read_manifest_version(app_root).await,
+            previous_version,
+            launches: 0,
+        };
+        write_json(&rollback_dir(app_root).join(BOOT_PENDING_FILE), &marker).await
+    }
+}
+
+async fn record_launch_in(
+    updater: &Updater,
+    app_root: &Path,
+) -> Result<LaunchOutcome, MonitorError> {
+    let marker_path = rollback_dir(app_root).join(BOOT_PENDING_FILE);
+    let mut marker: BootPending = match fs::read(&marker_path).await {
+        Ok(contents) => match serde_json::from_slice(&contents) {
+            Ok(marker) => marker,
+            Err(err) => {
+                warn!(error = ?err, "Discarding unreadable boot-pending marker");
+                let _ = fs::remove_file(&marker_path).await;
+                return Ok(LaunchOutcome::Normal);
+            }
+        },
+        Err(_) => return Ok(LaunchOutcome::Normal),
+    };
+
+    if marker.launches >= MAX_UNHEALTHY_LAUNCHES {
+        warn!(
+            launches = marker.launches,
+            version = ?marker.version,
+            "Updated app never became healthy, rolling back"
+        );
+        return restore_backup(updater, app_root, true)
+            .await
+            .map(LaunchOutcome::RolledBack);
+    }
+
+    marker.launches += 1;
+    info!(
+        launch = marker.launches,
+        "Launching updated app on probation"
+    );
+    write_json(&marker_path, &marker).await?;
+    Ok(LaunchOutcome::Normal)
+}
+
+async fn restore_backup(
+    updater: &Updater,
+    app_root: &Path,
+    automatic: bool,
+) -> Result<RollbackNotice, MonitorError> {
+    let backup = backup_dir(app_root);
+    if !backup_present(app_root).await {
+        return Err(MonitorError::Config(
+            "No previous app version available to roll back to".to_string(),
+        ));
+    }
+
+    let notice = RollbackNotice {
+        from_version: read_manifest_version(app_root).await,
+        to_version: read_manifest_version(&backup).await,
+        automatic,
+    };
+
+    // Rollback state is not carried over, so the boot-pending marker of the
+    // abandoned version goes away with it.
+    let retired = updater.install_tree(app_root, &backup).await?;
+    if let Err(err) = fs::remove_dir_all(&retired).await {
+        warn!(path = %retired.display(), error = ?err, "Failed to remove abandoned app version");
+    }
+    write_json(&rollback_dir(app_root).join(ROLLBACK_NOTICE_FILE), &notice).await?;
+
+    info!(from = ?notice.from_version, to = ?notice.to_version, automatic, "Rolled back app update");
+    Ok(notice)
+}
+
+async fn backup_present(app_root: &Path) -> bool {
+    fs::metadata(backup_dir(app_root).join("manifest.json"))
+        .await
+        .is_ok()
+}
+
+pub(super) async fn read_manifest_version(root: &Path) -> Option<String> {
+    let contents = fs::read_to_string(root.join("manifest.json")).await.ok()?;
+    let manifest: serde_json::Value = serde_json::from_str(&contents).ok()?;
+    manifest
+        .get("version")
+        .and_then(|version| version.as_str())
+        .map(str::to_string)
+}
+
+async fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), MonitorError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(path, serde_json::to_vec_pretty(value)?).await?;
+    Ok(())
+}
+
+pub(super) fn rollback_dir(app_root: &Path) -> PathBuf {
+    app_root.join(ROLLBACK_DIR_NAME)
+}
+
+pub(super) fn backup_dir(app_root: &Path) -> PathBuf {
+    rollback_dir(app_root).join(BACKUP_DIR_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn write_version(root: &Path, version: &str) {
+        fs::create_dir_all(root.join("backend"))
+            .await
+            .expect("create backend dir");
+        fs::write(
+            root.join("manifest.json"),
+            format!("{{\"version\": \"{}\"}}", version),
+        )
+        .await
+        .expect("write manifest");
+        fs::write(root.join("resources.rcc"), version)
+            .await
+            .expect("write resources");
+        fs::write(root.join("icon.png"), b"icon")
+            .await
+            .expect("write icon");
+        let entry = root.join("backend").join("entry");
+        fs::write(&entry, version).await.expect("write entry");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&entry, std::fs::Permissions::from_mode(0o755))
+                .await
+                .expect("set executable permission");
+        }
+    }
+
+    #[tokio::test]
+    async fn unhealthy_update_is_rolled_back_after_max_launches() {
+        let parent = tempfile::tempdir().expect("create parent dir");
+        let root = parent.path().join("syncthing");
+        let payload = parent.path().join("payload");
+        let updater = Updater::new(&Config::default());
+        write_version(&root, "1.0.0").await;
+        write_version(&payload, "2.0.0").await;
+        fs::write(payload.join("added-by-update.qml"), "")
+            .await
+            .expect("write new file");
+        fs::write(root.join("config.json"), "{}")
+            .await
+            .expect("write config");
+
+        let retired = updater
+            .install_tree(&root, &payload)
+            .await
+            .expect("install update");
+        updater
+            .retire_to_backup(&root, &retired)
+            .await
+            .expect("keep backup");
+        updater
+            .mark_boot_pending(&root, Some("1.0.0".to_string()))
+            .await
+            .expect("mark boot pending");
+
+        for _ in 0..MAX_UNHEALTHY_LAUNCHES {
+            assert_eq!(
+                record_launch_in(&updater, &root)
+                    .await
+                    .expect("record launch"),
+                LaunchOutcome::Normal
+            );
+        }
+
+        let outcome = record_launch_in(&updater, &root)
+            .await
+            .expect("record launch");
+        assert_eq!(
+            outcome,
+            LaunchOutcome::RolledBack(RollbackNotice {
+                from_version: Some("2.0.0".to_string()),
+                to_version: Some("1.0.0".to_string()),
+                automatic: true,
+            })
+        );
+        assert_eq!(read_manifest_version(&root).await.as_deref(), Some("1.0.0"));
+        assert!(!root.join("added-by-update.qml").exists());
+        assert!(root.join("config.json").exists());
+        assert!(!backup_present(&root).await);
+        assert!(!rollback_dir(&root).join(BOOT_PENDING_FILE).exists());
+    }
+
+    #[tokio::test]
+    async fn launch_without_pending_update_is_normal() {
+        let app_root = tempfile::tempdir().expect("create app root");
+
+        assert_eq!(
+            record_launch_in(&Updater::new(&Config::default()), app_root.path())
+                .await
+                .expect("record launch"),
+            LaunchOutcome::Normal
+        );
+    }
+}
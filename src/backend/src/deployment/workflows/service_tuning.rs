@@ -0,0 +1,130 @@
+//! Drop-in carrying the `service_tuning` settings, kept apart from the unit file
+//! so the installer can rewrite the unit without losing them.
+
+use std::path::Path;
+
+use crate::config::ServiceTuning;
+use crate::types::MonitorError;
+
+use super::installer::Installer;
+
+pub(super) const TUNING_DROPIN: &str = "tuning.conf";
+
+impl Installer {
+    /// The drop-in for the configured tuning, or `None` when nothing is tuned.
+    pub(super) fn render_tuning_dropin(
+        &self,
+        binary_path: &Path,
+    ) -> Result<Option<String>, MonitorError> {
+        let tuning = &self.config.service_tuning;
+        if tuning.is_empty() {
+            return Ok(None);
+        }
+        tuning.validate()?;
+        Ok(Some(render_dropin(tuning, &self.exec_start(binary_path))))
+    }
+
+    /// Writes the drop-in, or removes it when no tuning is configured.
+    pub(super) async fn write_tuning_dropin(&self) -> Result<(), MonitorError> {
+        let contents = self.render_tuning_dropin(&self.binary_path()?)?;
+        self.write_dropin(TUNING_DROPIN, contents).await
+    }
+}
+
+fn render_dropin(tuning: &ServiceTuning, exec_start: &str) -> String {
+    let mut lines = vec![
+        "# Generated from service_tuning in config.json.".to_string(),
+        "[Service]".to_string(),
+    ];
+    if let Some(nice) = tuning.nice {
+        lines.push(format!("Nice={nice}"));
+    }
+    if let Some(class) = tuning.io_scheduling_class {
+        lines.push(format!("IOSchedulingClass={class}"));
+    }
+    if let Some(quota) = &tuning.cpu_quota {
+        lines.push(format!("CPUQuota={quota}"));
+    }
+    if let Some(memory) = &tuning.memory_max {
+        lines.push(format!("MemoryMax={memory}"));
+    }
+    for (name, value) in &tuning.environment {
+        lines.push(format!("Environment=\"{name}={value}\""));
+    }
+    if !tuning.extra_serve_args.is_empty() {
+        let args: Vec<String> = tuning
+            .extra_serve_args
+            .iter()
+            .map(|arg| {
+                if arg.contains(char::is_whitespace) {
+                    format!("\"{arg}\"")
+                } else {
+                    arg.clone()
+                }
+            })
+            .collect();
+        // An empty ExecStart= clears the unit's command before replacing it.
+        lines.push("ExecStart=".to_string());
+        lines.push(format!("ExecStart={} {}", exec_start, args.join(" ")));
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropin_sets_limits_environment_and_serve_arguments() {
+        let tuning: ServiceTuning = serde_json::from_value(serde_json::json!({
+            "nice": 10,
+            "io_scheduling_class": "idle",
+            "memory_max": "256M",
+            "environment": {"STNODEFAULTFOLDER": "1", "GOMAXPROCS": "1"},
+            "extra_serve_args": ["--log-max-old-files=1"]
+        }))
+        .expect("parse tuning");
+        tuning.validate().expect("valid tuning");
+
+        assert_eq!(
+            render_dropin(&tuning, "/home/root/syncthing serve --no-browser"),
+            "# Generated from service_tuning in config.json.
+[Service]
+Nice=10
+IOSchedulingClass=idle
+MemoryMax=256M
+Environment=\"GOMAXPROCS=1\"
+Environment=\"STNODEFAULTFOLDER=1\"
+ExecStart=
+ExecStart=/home/root/syncthing serve --no-browser --log-max-old-files=1
+"
+        );
+    }
+
+    #[test]
+    fn invalid_tuning_is_rejected() {
+        let invalid = [
+            ServiceTuning {
+                nice: Some(25),
+                ..ServiceTuning::default()
+            },
+            ServiceTuning {
+                cpu_quota: Some("half".to_string()),
+                ..ServiceTuning::default()
+            },
+            ServiceTuning {
+                environment: [("GO MEMLIMIT".to_string(), "100MiB".to_string())].into(),
+                ..ServiceTuning::default()
+            },
+            ServiceTuning {
+                extra_serve_args: vec!["--gui-address=\"x\"".to_string()],
+                ..ServiceTuning::default()
+            },
+        ];
+
+        for tuning in invalid {
+            assert!(tuning.validate().is_err(), "{:?}", tuning);
+        }
+    }
+}
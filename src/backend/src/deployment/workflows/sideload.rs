@@ -0,0 +1,238 @@
+//! Release archives placed in a local drop directory for offline installs and updates.
+//!
+//! Archives keep their upstream file names and are verified exactly like
+//! downloaded ones: a Syncthing tarball needs the clear-signed `sha256sum.txt.asc`
+//! of its release next to it, an app archive its detached `.asc` signature once
+//! the build embeds the app's release key.
+
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+use tracing::warn;
+
+use crate::deployment::verify::checksum;
+use crate::types::MonitorError;
+
+use super::installer::{CHECKSUMS_ASSET_NAME, TAR_EXTENSION};
+use super::updater::SIGNATURE_EXTENSION;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncthingArchive {
+    pub tarball: PathBuf,
+    pub checksums: PathBuf,
+    pub version: semver::Version,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppArchive {
+    pub archive: PathBuf,
+    pub signature: Option<PathBuf>,
+}
+
+impl SyncthingArchive {
+    pub fn file_name(&self) -> String {
+        display_name(&self.tarball)
+    }
+}
+
+impl AppArchive {
+    pub fn file_name(&self) -> String {
+        display_name(&self.archive)
+    }
+}
+
+/// Finds the Syncthing tarball for this architecture in `dir`.
+///
+/// Only tarballs named in the `sha256sum.txt.asc` next to them are considered,
+/// so a tarball from another release is never paired with the wrong list. With
+/// a `pinned` version only that version is considered; otherwise the newest one
+/// wins. The list's signature is checked when the archive is installed.
+pub async fn find_syncthing_archive(
+    dir: &Path,
+    asset_prefix: &str,
+    pinned: Option<&str>,
+) -> Result<Option<SyncthingArchive>, MonitorError> {
+    let checksums = dir.join(CHECKSUMS_ASSET_NAME);
+    if !is_file(&checksums).await {
+        return Ok(None);
+    }
+    let listed = fs::read_to_string(&checksums).await?;
+    let pinned = match pinned {
+        Some(version) => Some(
+            semver::Version::parse(version.trim_start_matches('v')).map_err(|err| {
+                MonitorError::Config(format!("Invalid pinned version '{version}': {err}"))
+            })?,
+        ),
+        None => None,
+    };
+
+    let mut best: Option<SyncthingArchive> = None;
+    for (name, path) in list_files(dir).await? {
+        let Some(version) = name
+            .strip_prefix(asset_prefix)
+            .and_then(|rest| rest.strip_suffix(TAR_EXTENSION))
+            .and_then(|version| semver::Version::parse(version.trim_start_matches('v')).ok())
+        else {
+            continue;
+        };
+        if pinned.as_ref().is_some_and(|pinned| pinned != &version) {
+            continue;
+        }
+        if checksum::find_checksum(&listed, &name).is_none() {
+            warn!(archive = %name, "Ignoring sideloaded tarball missing from {CHECKSUMS_ASSET_NAME}");
+            continue;
+        }
+        if best.as_ref().is_none_or(|best| version > best.version) {
+            best = Some(SyncthingArchive {
+                tarball: path,
+                checksums: checksums.clone(),
+                version,
+            });
+        }
+    }
+    Ok(best)
+}
+
+/// Finds the app archive named `asset_name` in `dir`, with its signature if
+/// one lies next to it.
+pub async fn find_app_archive(dir: &Path, asset_name: &str) -> Option<AppArchive> {
+    let archive = dir.join(asset_name);
+    if !is_file(&archive).await {
+        return None;
+    }
+    let signature = dir.join(format!("{asset_name}{SIGNATURE_EXTENSION}"));
+    let signature = is_file(&signature).await.then_some(signature);
+    Some(AppArchive { archive, signature })
+}
+
+/// Removes a consumed archive so it is not offered again.
+pub async fn remove_consumed(path: &Path) {
+    if let Err(err) = fs::remove_file(path).await {
+        warn!(path = %path.display(), error = ?err, "Failed to remove sideloaded archive");
+    }
+}
+
+async fn list_files(dir: &Path) -> Result<Vec<(String, PathBuf)>, MonitorError> {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut files = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_file() {
+            files.push((
+                entry.file_name().to_string_lossy().into_owned(),
+                entry.path(),
+            ));
+        }
+    }
+    Ok(files)
+}
+
+async fn is_file(path: &Path) -> bool {
+    fs::metadata(path)
+        .await
+        .map(|metadata| metadata.is_file())
+        .unwrap_or(false)
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARM64_PREFIX: &str = "syncthing-linux-arm64-";
+
+    fn touch(dir: &Path, name: &str) {
+        std::fs::write(dir.join(name), b"").expect("create drop file");
+    }
+
+    fn write_checksums(dir: &Path, names: &[&str]) {
+        let list: String = names
+            .iter()
+            .map(|name| format!("{}  {name}\n", "0".repeat(64)))
+            .collect();
+        std::fs::write(dir.join(CHECKSUMS_ASSET_NAME), list).expect("write checksum list");
+    }
+
+    #[tokio::test]
+    async fn newest_matching_tarball_is_selected() {
+        let dir = tempfile::tempdir().expect("create drop dir");
+        write_checksums(
+            dir.path(),
+            &[
+                "syncthing-linux-arm64-v1.27.12.tar.gz",
+                "syncthing-linux-arm64-v1.29.2.tar.gz",
+                "syncthing-linux-arm-v1.30.0.tar.gz",
+            ],
+        );
+        touch(dir.path(), "syncthing-linux-arm64-v1.27.12.tar.gz");
+        touch(dir.path(), "syncthing-linux-arm64-v1.29.2.tar.gz");
+        touch(dir.path(), "syncthing-linux-arm-v1.30.0.tar.gz");
+
+        let archive = find_syncthing_archive(dir.path(), ARM64_PREFIX, None)
+            .await
+            .expect("scan drop dir")
+            .expect("find archive");
+
+        assert_eq!(archive.file_name(), "syncthing-linux-arm64-v1.29.2.tar.gz");
+
+        let pinned = find_syncthing_archive(dir.path(), ARM64_PREFIX, Some("1.27.12"))
+            .await
+            .expect("scan drop dir")
+            .expect("find pinned archive");
+        assert_eq!(pinned.version, semver::Version::new(1, 27, 12));
+    }
+
+    #[tokio::test]
+    async fn tarball_is_chosen_by_the_checksum_list() {
+        let dir = tempfile::tempdir().expect("create drop dir");
+        write_checksums(dir.path(), &["syncthing-linux-arm64-v1.27.12.tar.gz"]);
+        touch(dir.path(), "syncthing-linux-arm64-v1.27.12.tar.gz");
+        touch(dir.path(), "syncthing-linux-arm64-v1.29.2.tar.gz");
+
+        let archive = find_syncthing_archive(dir.path(), ARM64_PREFIX, None)
+            .await
+            .expect("scan drop dir")
+            .expect("find listed archive");
+        assert_eq!(archive.file_name(), "syncthing-linux-arm64-v1.27.12.tar.gz");
+
+        let pinned = find_syncthing_archive(dir.path(), ARM64_PREFIX, Some("1.29.2"))
+            .await
+            .expect("scan drop dir");
+        assert!(pinned.is_none());
+    }
+
+    #[tokio::test]
+    async fn archives_without_verification_material_are_ignored() {
+        let dir = tempfile::tempdir().expect("create drop dir");
+        touch(dir.path(), "syncthing-linux-arm64-v1.29.2.tar.gz");
+        touch(dir.path(), "syncthing-rm-appload-aarch64.zip");
+
+        assert!(find_syncthing_archive(dir.path(), ARM64_PREFIX, None)
+            .await
+            .expect("scan drop dir")
+            .is_none());
+        let app = find_app_archive(dir.path(), "syncthing-rm-appload-aarch64.zip")
+            .await
+            .expect("find app archive");
+        assert_eq!(app.signature, None);
+    }
+
+    #[tokio::test]
+    async fn missing_drop_dir_has_no_archives() {
+        let dir = tempfile::tempdir().expect("create parent dir");
+
+        let result = find_syncthing_archive(&dir.path().join("missing"), ARM64_PREFIX, None)
+            .await
+            .expect("scan missing dir");
+
+        assert!(result.is_none());
+    }
+}
@@ -0,0 +1,222 @@
+//! Post-install checks that the installed Syncthing actually runs on this device.
+
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::time::Duration;
+
+use tokio::fs;
+use tokio::time::{sleep, timeout, Instant};
+
+use crate::syncthing_client::probe_version;
+use crate::systemd;
+use crate::types::MonitorError;
+use crate::utils::CommandRunner;
+
+use super::installer::Installer;
+
+const VERSION_TIMEOUT: Duration = Duration::from_secs(15);
+const SERVICE_TIMEOUT: Duration = Duration::from_secs(30);
+const CONFIG_TIMEOUT: Duration = Duration::from_secs(30);
+const API_TIMEOUT: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Consecutive `active (running)` observations required, so a binary that
+/// crashes right after start and is restarted by systemd does not pass.
+const STABLE_OBSERVATIONS: u32 = 2;
+
+impl Installer {
+    /// Runs the installed binary with `--version`. Returns the version it reports.
+    pub async fn check_binary_runs(&self) -> Result<String, MonitorError> {
+        run_version_check(self.runner.as_ref(), &self.binary_path()?).await
+    }
+
+    /// Waits until the unit is `active (running)` and stays there.
+    pub async fn wait_for_service_running(&self) -> Result<(), MonitorError> {
+        let deadline = Instant::now() + SERVICE_TIMEOUT;
+        let mut stable = 0;
+        let mut last_state = String::from("unknown");
+        while Instant::now() < deadline {
+            let status = systemd::query_status(&self.config, self.runner.as_ref()).await;
+            let active = status.active_state.as_deref().unwrap_or("unknown");
+            let sub = status.sub_state.as_deref().unwrap_or("unknown");
+            if active == "failed" {
+                return Err(MonitorError::Systemd(format!(
+                    "{} failed to start (result: {})",
+                    self.config.systemd_service_name,
+                    status.result.as_deref().unwrap_or("unknown")
+                )));
+            }
+            if active == "active" && sub == "running" {
+                stable += 1;
+                if stable >= STABLE_OBSERVATIONS {
+                    return Ok(());
+                }
+            } else {
+                stable = 0;
+            }
+            last_state = format!("{} ({})", active, sub);
+            sleep(POLL_INTERVAL).await;
+        }
+        Err(MonitorError::Systemd(format!(
+            "{} did not reach active (running) within {}s, last state {}",
+            self.config.systemd_service_name,
+            SERVICE_TIMEOUT.as_secs(),
+            last_state
+        )))
+    }
+
+    /// Waits for Syncthing to generate its `config.xml` on first start.
+    pub async fn wait_for_config_xml(&self) -> Result<(), MonitorError> {
+        let path = self.config.syncthing_config_xml_path();
+        let deadline = Instant::now() + CONFIG_TIMEOUT;
+        while Instant::now() < deadline {
+            if fs::metadata(&path).await.is_ok_and(|meta| meta.len() > 0) {
+                return Ok(());
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+        Err(MonitorError::Config(format!(
+            "{} was not generated within {}s",
+            path,
+            CONFIG_TIMEOUT.as_secs()
+        )))
+    }
+
+    /// Waits until the API answers with the key from `config.xml`. Returns the
+    /// version it reports.
+    pub async fn wait_for_api(&self) -> Result<String, MonitorError> {
+        let deadline = Instant::now() + API_TIMEOUT;
+        loop {
+            match probe_version(&self.config).await {
+                Ok(version) => return Ok(version),
+                Err(err) if Instant::now() >= deadline => {
+                    return Err(MonitorError::Syncthing(format!(
+                        "API not reachable within {}s: {}",
+                        API_TIMEOUT.as_secs(),
+                        err
+                    )))
+                }
+                Err(_) => sleep(POLL_INTERVAL).await,
+            }
+        }
+    }
+}
+
+/// Executes `binary --version`, catching binaries built for another CPU or
+/// crashing on start.
+pub(super) async fn run_version_check(
+    runner: &dyn CommandRunner,
+    binary: &Path,
+) -> Result<String, MonitorError> {
+    let output = timeout(
+        VERSION_TIMEOUT,
+        runner.output(&binary.to_string_lossy(), &["--version"]),
+    )
+    .await
+    .map_err(|_| {
+        MonitorError::Config(format!(
+            "{} --version did not finish within {}s",
+            binary.display(),
+            VERSION_TIMEOUT.as_secs()
+        ))
+    })??;
+
+    if let Some(signal) = output.status.signal() {
+        return Err(MonitorError::Config(format!(
+            "{} crashed with signal {} ({})",
+            binary.display(),
+            signal,
+            signal_name(signal)
+        )));
+    }
+    if !output.status.success() {
+        return Err(MonitorError::Config(format!(
+            "{} --version failed with {}: {}",
+            binary.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_version_output(&stdout).ok_or_else(|| {
+        MonitorError::Config(format!(
+            "Unexpected --version output from {}: {}",
+            binary.display(),
+            stdout.trim()
+        ))
+    })
+}
+
+/// Extracts `1.27.12` from `syncthing v1.27.12 "Gold Grasshopper" (go1.22.5 linux-arm64) ...`.
+fn parse_version_output(output: &str) -> Option<String> {
+    let mut words = output.split_whitespace();
+    if words.next()? != "syncthing" {
+        return None;
+    }
+    let version = words.next()?.strip_prefix('v')?;
+    semver::Version::parse(version).ok()?;
+    Some(version.to_string())
+}
+
+fn signal_name(signal: i32) -> &'static str {
+    match signal {
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGILL => "SIGILL",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGKILL => "SIGKILL",
+        _ => "unknown signal",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+    use crate::utils::command::SystemRunner;
+
+    fn script(dir: &Path, body: &str) -> std::path::PathBuf {
+        let path = dir.join("syncthing");
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).expect("write script");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+            .expect("make script executable");
+        path
+    }
+
+    #[test]
+    fn version_is_parsed_from_syncthing_banner() {
+        assert_eq!(
+            parse_version_output(
+                "syncthing v1.27.12 \"Gold Grasshopper\" (go1.22.5 linux-arm64) builder@github.com 2024-08-06\n"
+            ),
+            Some("1.27.12".to_string())
+        );
+        assert_eq!(parse_version_output("ufw v0.36\n"), None);
+    }
+
+    #[tokio::test]
+    async fn crashing_binary_is_reported_with_its_signal() {
+        let dir = tempfile::tempdir().expect("create dir");
+        let binary = script(dir.path(), "kill -SEGV $$");
+
+        let err = run_version_check(&SystemRunner, &binary)
+            .await
+            .expect_err("crash must fail the check");
+
+        assert!(err.to_string().contains("SIGSEGV"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn working_binary_reports_its_version() {
+        let dir = tempfile::tempdir().expect("create dir");
+        let binary = script(dir.path(), "echo 'syncthing v2.0.3 \"Hafnium Hornet\"'");
+
+        assert_eq!(
+            run_version_check(&SystemRunner, &binary)
+                .await
+                .expect("run version check"),
+            "2.0.3"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! Staged installation of a complete app tree next to the live one.
+
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::deployment::http::sources::CACHE_DIR_NAME;
+use crate::deployment::system::swap::{replace_dir, sync_tree};
+use crate::types::MonitorError;
+
+use super::rollback::{backup_dir, rollback_dir};
+use super::updater::Updater;
+
+const CONFIG_FILE_NAME: &str = "config.json";
+const SYNCTHING_BINARY_NAME: &str = "syncthing";
+/// Kept by app-managed Syncthing upgrades until the new binary is confirmed.
+const PREVIOUS_SYNCTHING_BINARY_NAME: &str = "syncthing.prev";
+
+impl Updater {
+    /// Builds a complete app tree from `source` in a sibling staging directory and
+    /// swaps it in as `app_root` in one step.
+    ///
+    /// `config.json`, the Syncthing binaries and the release cache are carried
+    /// over. Returns the path
+    /// now holding the retired tree; the caller decides whether to keep it.
+    pub(super) async fn install_tree(
+        &self,
+        app_root: &Path,
+        source: &Path,
+    ) -> Result<PathBuf, MonitorError> {
+        let staging = staging_path(app_root)?;
+        if fs::symlink_metadata(&staging).await.is_ok() {
+            warn!(path = %staging.display(), "Removing leftover staging directory");
+            fs::remove_dir_all(&staging).await?;
+        }
+        fs::create_dir_all(&staging).await?;
+
+        let staged = self.populate_staging(app_root, source, &staging).await;
+        if let Err(err) = staged {
+            let _ = fs::remove_dir_all(&staging).await;
+            return Err(err);
+        }
+
+        replace_dir(app_root, &staging).await?;
+        info!(path = %app_root.display(), "Switched to staged app version");
+        Ok(staging)
+    }
+
+    async fn populate_staging(
+        &self,
+        app_root: &Path,
+        source: &Path,
+        staging: &Path,
+    ) -> Result<(), MonitorError> {
+        self.copy_update_files(source, staging).await?;
+        carry_over(app_root, staging).await?;
+        let cache = app_root.join(CACHE_DIR_NAME);
+        if fs::metadata(&cache).await.is_ok() {
+            // Only saves requests; the update does not depend on it.
+            if let Err(err) = self
+                .copy_dir_recursive(&cache, &staging.join(CACHE_DIR_NAME))
+                .await
+            {
+                warn!(error = ?err, "Failed to carry over the release cache");
+            }
+        }
+        self.validate_update_payload(staging).await?;
+        sync_tree(staging).await
+    }
+
+    /// Keeps a retired tree as the rollback backup of `app_root`.
+    pub(super) async fn retire_to_backup(
+        &self,
+        app_root: &Path,
+        retired: &Path,
+    ) -> Result<(), MonitorError> {
+        // User data lives on in the new tree; the backup only holds app files.
+        for name in [
+            CONFIG_FILE_NAME,
+            SYNCTHING_BINARY_NAME,
+            PREVIOUS_SYNCTHING_BINARY_NAME,
+        ] {
+            let _ = fs::remove_file(retired.join(name)).await;
+        }
+        let _ = fs::remove_dir_all(rollback_dir(retired)).await;
+        let _ = fs::remove_dir_all(retired.join(CACHE_DIR_NAME)).await;
+        let mut entries = fs::read_dir(retired).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("update.zip")
+            {
+                let _ = fs::remove_file(entry.path()).await;
+            }
+        }
+
+        let backup = backup_dir(app_root);
+        if fs::symlink_metadata(&backup).await.is_ok() {
+            fs::remove_dir_all(&backup).await?;
+        }
+        fs::create_dir_all(rollback_dir(app_root)).await?;
+        fs::rename(retired, &backup).await?;
+        info!(path = %backup.display(), "Kept previous app version for rollback");
+        Ok(())
+    }
+}
+
+/// Copies user data from the live tree into the staged one.
+async fn carry_over(app_root: &Path, staging: &Path) -> Result<(), MonitorError> {
+    let config = app_root.join(CONFIG_FILE_NAME);
+    if fs::metadata(&config).await.is_ok() {
+        fs::copy(&config, staging.join(CONFIG_FILE_NAME)).await?;
+    }
+
+    for name in [SYNCTHING_BINARY_NAME, PREVIOUS_SYNCTHING_BINARY_NAME] {
+        let binary = app_root.join(name);
+        if fs::metadata(&binary).await.is_err() {
+            continue;
+        }
+        let staged_binary = staging.join(name);
+        // A hard link keeps the running service's binary without doubling its size.
+        if let Err(err) = fs::hard_link(&binary, &staged_binary).await {
+            warn!(error = ?err, "Hard-linking Syncthing binary failed, copying instead");
+            fs::copy(&binary, &staged_binary).await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn staging_path(app_root: &Path) -> Result<PathBuf, MonitorError> {
+    let name = app_root.file_name().ok_or_else(|| {
+        MonitorError::Config(format!(
+            "App root has no directory name: {}",
+            app_root.display()
+        ))
+    })?;
+    // Hidden so AppLoad does not list the staging tree as a second app.
+    let mut staging_name = std::ffi::OsString::from(".");
+    staging_name.push(name);
+    staging_name.push(".staging");
+    Ok(app_root.with_file_name(staging_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    async fn write_app(root: &Path, version: &str) {
+        fs::create_dir_all(root.join("backend"))
+            .await
+            .expect("create backend dir");
+        fs::write(
+            root.join("manifest.json"),
+            format!("{{\"version\": \"{}\"}}", version),
+        )
+        .await
+        .expect("write manifest");
+        fs::write(root.join("resources.rcc"), version)
+            .await
+            .expect("write resources");
+        fs::write(root.join("icon.png"), b"icon")
+            .await
+            .expect("write icon");
+        let entry = root.join("backend").join("entry");
+        fs::write(&entry, version).await.expect("write entry");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&entry, std::fs::Permissions::from_mode(0o755))
+                .await
+                .expect("set executable permission");
+        }
+    }
+
+    #[tokio::test]
+    async fn install_tree_swaps_in_new_version_and_keeps_user_data() {
+        let parent = tempfile::tempdir().expect("create parent dir");
+        let app_root = parent.path().join("syncthing");
+        let payload = parent.path().join("payload");
+        write_app(&app_root, "1.0.0").await;
+        write_app(&payload, "2.0.0").await;
+        fs::write(app_root.join("config.json"), "{\"keep\": true}")
+            .await
+            .expect("write config");
+        fs::write(app_root.join("syncthing"), b"\x7fELF")
+            .await
+            .expect("write binary");
+        fs::create_dir_all(app_root.join(".cache/releases"))
+            .await
+            .expect("create cache dir");
+        fs::write(app_root.join(".cache/releases/feed.json"), "[]")
+            .await
+            .expect("write cached feed");
+
+        let updater = Updater::new(&Config::default());
+        let retired = updater
+            .install_tree(&app_root, &payload)
+            .await
+            .expect("install tree");
+
+        assert_eq!(
+            fs::read_to_string(app_root.join("resources.rcc"))
+                .await
+                .expect("read resources"),
+            "2.0.0"
+        );
+        assert!(app_root.join("config.json").exists());
+        assert!(app_root.join("syncthing").exists());
+        assert!(app_root.join(".cache/releases/feed.json").exists());
+        assert_eq!(
+            fs::read_to_string(retired.join("resources.rcc"))
+                .await
+                .expect("read retired resources"),
+            "1.0.0"
+        );
+
+        updater
+            .retire_to_backup(&app_root, &retired)
+            .await
+            .expect("retire to backup");
+        let backup = backup_dir(&app_root);
+        assert!(backup.join("manifest.json").exists());
+        assert!(!backup.join("config.json").exists());
+        assert!(!backup.join(".cache").exists());
+        assert!(!retired.exists());
+    }
+
+    #[tokio::test]
+    async fn install_tree_leaves_live_tree_untouched_on_invalid_payload() {
+        let parent = tempfile::tempdir().expect("create parent dir");
+        let app_root = parent.path().join("syncthing");
+        let payload = parent.path().join("payload");
+        write_app(&app_root, "1.0.0").await;
+        fs::create_dir_all(&payload).await.expect("create payload");
+        fs::write(payload.join("manifest.json"), "{}")
+            .await
+            .expect("write manifest");
+
+        let result = Updater::new(&Config::default())
+            .install_tree(&app_root, &payload)
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            fs::read_to_string(app_root.join("resources.rcc"))
+                .await
+                .expect("read resources"),
+            "1.0.0"
+        );
+        assert!(!staging_path(&app_root).expect("staging path").exists());
+    }
+}
@@ -0,0 +1,131 @@
+//! Removal of the Syncthing service and binary installed by the installer.
+
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+use tracing::{error, info};
+
+use crate::types::MonitorError;
+use crate::utils::systemctl;
+
+use super::binary_upgrade::previous_binary_path;
+use super::hardening::HARDENING_DROPIN;
+use super::installer::Installer;
+use super::major_upgrade::snapshot_path;
+use super::service_tuning::TUNING_DROPIN;
+
+impl Installer {
+    /// Stops and disables the service. A unit that is already gone is not an error.
+    pub async fn stop_and_disable_service(&self) -> Result<(), MonitorError> {
+        let service_name = &self.config.systemd_service_name;
+        if !self.service_installed().await {
+            return Ok(());
+        }
+        systemctl::execute(self.runner.as_ref(), &["stop", service_name]).await?;
+        systemctl::execute(self.runner.as_ref(), &["disable", service_name]).await
+    }
+
+    /// Deletes the unit file and reloads systemd.
+    pub async fn remove_service_unit(&self) -> Result<(), MonitorError> {
+        let guard = self.mounts.writable().await?;
+        let removal_result = self.remove_service_unit_inner().await;
+        let restore_result = guard.release().await;
+
+        if let Err(err) = &restore_result {
+            error!(error = ?err, "Failed to restore mounts after uninstall");
+        }
+
+        removal_result.and(restore_result)
+    }
+
+    /// Deletes the Syncthing binary and the one kept from the last upgrade.
+    pub async fn remove_binary(&self) -> Result<(), MonitorError> {
+        let binary = self.binary_path()?;
+        remove_file_if_present(&previous_binary_path(&binary)).await?;
+        remove_file_if_present(&binary).await
+    }
+
+    /// Deletes Syncthing's configuration, keys and database, along with the
+    /// snapshot of a major upgrade.
+    pub async fn wipe_syncthing_state(&self) -> Result<(), MonitorError> {
+        let home = PathBuf::from(&self.config.syncthing_config_dir);
+        for dir in [snapshot_path(&home), home] {
+            match fs::remove_dir_all(&dir).await {
+                Ok(()) => info!(path = %dir.display(), "Removed Syncthing state"),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove_service_unit_inner(&self) -> Result<(), MonitorError> {
+        for dropin in [TUNING_DROPIN, HARDENING_DROPIN] {
+            self.write_dropin(dropin, None).await?;
+        }
+        remove_file_if_present(&self.unit_path()).await?;
+        systemctl::execute(self.runner.as_ref(), &["daemon-reload"]).await
+    }
+}
+
+async fn remove_file_if_present(path: &Path) -> Result<(), MonitorError> {
+    match fs::remove_file(path).await {
+        Ok(()) => {
+            info!(path = %path.display(), "Removed file");
+            Ok(())
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::utils::command::fake::{failure, success, ScriptedRunner};
+
+    #[tokio::test]
+    async fn removing_a_missing_file_succeeds() {
+        let dir = tempfile::tempdir().expect("create dir");
+        let binary = dir.path().join("syncthing");
+        std::fs::write(&binary, "binary").expect("write binary");
+
+        remove_file_if_present(&binary)
+            .await
+            .expect("remove binary");
+        assert!(!binary.exists());
+        remove_file_if_present(&binary)
+            .await
+            .expect("second removal is a no-op");
+    }
+
+    #[tokio::test]
+    async fn service_is_stopped_before_it_is_disabled() {
+        let runner = ScriptedRunner::new()
+            .expect("systemctl cat syncthing.service", success("[Unit]\n"))
+            .expect("systemctl stop syncthing.service", success(""))
+            .expect("systemctl disable syncthing.service", success(""))
+            .expect("systemctl cat syncthing.service", success("[Unit]\n"))
+            .expect(
+                "systemctl stop syncthing.service",
+                failure(1, "Job for syncthing.service canceled."),
+            );
+        let runner = runner.shared();
+        let installer = Installer::new(
+            Config::default(),
+            runner.clone(),
+            crate::utils::filesystem::RootMounts::new(runner),
+        );
+
+        installer
+            .stop_and_disable_service()
+            .await
+            .expect("stop and disable");
+        let err = installer
+            .stop_and_disable_service()
+            .await
+            .expect_err("failed stop skips disable");
+        assert!(err.to_string().contains("canceled"), "{}", err);
+    }
+}
@@ -0,0 +1,207 @@
+//! Detection and repair of drift between the installed systemd unit and the one
+//! the installer writes, e.g. an `ExecStart` still pointing at a moved binary.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use tokio::fs;
+use tracing::{error, info};
+
+use crate::deployment::UnitDifference;
+use crate::types::MonitorError;
+use crate::utils::systemctl;
+
+use super::hardening::HARDENING_DROPIN;
+use super::installer::Installer;
+use super::service_tuning::TUNING_DROPIN;
+
+impl Installer {
+    /// Compares the installed unit file and drop-ins with the expected
+    /// rendering. A unit that is not in the installer's unit directory is not
+    /// managed here and reports no drift.
+    pub async fn unit_drift(&self) -> Result<Vec<UnitDifference>, MonitorError> {
+        let Some(installed) = read_if_present(&self.unit_path()).await? else {
+            return Ok(Vec::new());
+        };
+        let binary = self.binary_path()?;
+        let expected = self.render_service_unit(&binary);
+        let mut differences = diff_units(&self.config.systemd_service_name, &expected, &installed);
+
+        let writable = self.writable_paths().await;
+        let dropins = [
+            (TUNING_DROPIN, self.render_tuning_dropin(&binary)?),
+            (HARDENING_DROPIN, self.render_hardening_dropin(&writable)),
+        ];
+        for (name, expected) in dropins {
+            let installed = read_if_present(&self.dropin_path(name)).await?;
+            differences.extend(diff_units(
+                &format!("{}.d/{}", self.config.systemd_service_name, name),
+                &expected.unwrap_or_default(),
+                &installed.unwrap_or_default(),
+            ));
+        }
+        Ok(differences)
+    }
+
+    /// Rewrites the unit file, reloads systemd and restarts the service if it
+    /// was running.
+    pub async fn repair_service(&self) -> Result<(), MonitorError> {
+        let guard = self.mounts.writable().await?;
+        let repair_result = self.repair_service_inner().await;
+        let restore_result = guard.release().await;
+
+        if let Err(err) = &restore_result {
+            error!(error = ?err, "Failed to restore mounts after service repair");
+        }
+
+        repair_result.and(restore_result)?;
+        systemctl::execute(
+            self.runner.as_ref(),
+            &["try-restart", &self.config.systemd_service_name],
+        )
+        .await
+    }
+
+    async fn repair_service_inner(&self) -> Result<(), MonitorError> {
+        self.write_service_file().await?;
+        self.write_tuning_dropin().await?;
+        self.apply_service_profile().await?;
+        info!(path = %self.unit_path().display(), "Rewrote systemd unit");
+        systemctl::execute(self.runner.as_ref(), &["daemon-reload"]).await
+    }
+}
+
+type UnitSettings = BTreeMap<(String, String), Vec<String>>;
+
+async fn read_if_present(path: &Path) -> Result<Option<String>, MonitorError> {
+    match fs::read_to_string(path).await {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Returns the settings that differ between two versions of `file`, ignoring
+/// comments, blank lines and whitespace around `=`.
+fn diff_units(file: &str, expected: &str, installed: &str) -> Vec<UnitDifference> {
+    let expected = parse_unit(expected);
+    let installed = parse_unit(installed);
+    let mut keys: Vec<&(String, String)> = expected.keys().chain(installed.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter()
+        .filter(|key| expected.get(*key) != installed.get(*key))
+        .map(|key| UnitDifference {
+            file: file.to_string(),
+            section: key.0.clone(),
+            key: key.1.clone(),
+            expected: expected.get(key).map(|values| values.join("\n")),
+            installed: installed.get(key).map(|values| values.join("\n")),
+        })
+        .collect()
+}
+
+fn parse_unit(contents: &str) -> UnitSettings {
+    let mut settings = UnitSettings::new();
+    let mut section = String::new();
+    let mut pending = String::new();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        // A trailing backslash continues the setting on the next line.
+        if let Some(continued) = line.strip_suffix('\\') {
+            pending.push_str(continued.trim_end());
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(line);
+        let line = std::mem::take(&mut pending);
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            section = name.to_string();
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            settings
+                .entry((section.clone(), key.trim().to_string()))
+                .or_default()
+                .push(value.trim().to_string());
+        }
+    }
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPECTED: &str = "[Unit]
+Description=Syncthing
+
+[Service]
+Environment=HOME=/home/root
+ExecStart=/home/root/syncthing serve --home=/home/root/.config/syncthing
+Restart=on-failure
+";
+
+    #[test]
+    fn formatting_differences_are_not_drift() {
+        let installed = "# Installed by hand\n[Unit]\nDescription = Syncthing\n\n[Service]\n\
+Environment=HOME=/home/root\nExecStart=/home/root/syncthing serve \\\n  --home=/home/root/.config/syncthing\n\
+Restart=on-failure\n";
+
+        assert!(diff_units("syncthing.service", EXPECTED, installed).is_empty());
+    }
+
+    #[test]
+    fn moved_binary_and_missing_settings_are_reported() {
+        let installed = "[Unit]
+Description=Syncthing
+
+[Service]
+Environment=HOME=/home/root
+ExecStart=/opt/syncthing serve --home=/home/root/.config/syncthing
+User=syncthing
+";
+
+        assert_eq!(
+            diff_units("syncthing.service", EXPECTED, installed),
+            vec![
+                UnitDifference {
+                    file: "syncthing.service".to_string(),
+                    section: "Service".to_string(),
+                    key: "ExecStart".to_string(),
+                    expected: Some(
+                        "/home/root/syncthing serve --home=/home/root/.config/syncthing"
+                            .to_string()
+                    ),
+                    installed: Some(
+                        "/opt/syncthing serve --home=/home/root/.config/syncthing".to_string()
+                    ),
+                },
+                UnitDifference {
+                    file: "syncthing.service".to_string(),
+                    section: "Service".to_string(),
+                    key: "Restart".to_string(),
+                    expected: Some("on-failure".to_string()),
+                    installed: None,
+                },
+                UnitDifference {
+                    file: "syncthing.service".to_string(),
+                    section: "Service".to_string(),
+                    key: "User".to_string(),
+                    expected: None,
+                    installed: Some("syncthing".to_string()),
+                },
+            ]
+        );
+    }
+}
@@ -6,30 +6,40 @@ use reqwest::Client;
 use serde_json::Value;
 use tempfile::TempDir;
 use tokio::fs;
-use tracing::info;
+use tracing::{error, info, warn};
 
-use crate::config::Config;
+use crate::config::{Config, ReleaseChannel};
 use crate::deployment::http::assets;
-use crate::deployment::http::client::{default_request_timeout, github_client};
+use crate::deployment::http::client::{default_request_timeout, release_client};
 use crate::deployment::http::download::download_to_path;
+use crate::deployment::http::sources::ReleaseFeed;
 use crate::deployment::system::architecture::{detect_architecture, Architecture};
 use crate::deployment::system::archive;
-use crate::deployment::{DownloadProgressSender, UpdateCheckResult};
+use crate::deployment::verify::app_release_keyring;
+use crate::deployment::verify::openpgp::Keyring;
+use crate::deployment::workflows::rollback::{read_manifest_version, ROLLBACK_DIR_NAME};
+use crate::deployment::workflows::sideload::{self, AppArchive};
+use crate::deployment::{
+    AvailableVersions, CancelToken, DownloadProgressSender, UpdateCheckResult,
+};
 use crate::types::MonitorError;
+use crate::utils::command::SystemRunner;
 
-const RELEASE_API_URL: &str =
-    "https://api.github.com/repos/paviro/Syncthing-for-reMarkable/releases/latest";
+const UPDATE_ARCHIVE_NAME: &str = "update.zip";
+pub(super) const SIGNATURE_EXTENSION: &str = ".asc";
 
 #[derive(Clone)]
 pub struct Updater {
     client: Client,
+    feed: ReleaseFeed,
 }
 
 impl Updater {
-    pub fn new() -> Self {
-        let client = github_client(default_request_timeout())
+    pub fn new(config: &Config) -> Self {
+        let client = release_client(default_request_timeout(), config)
             .expect("Failed to construct HTTP client for updater");
-        Self { client }
+        let feed = ReleaseFeed::new(client.clone(), config.app_release_source.clone(), config);
+        Self { client, feed }
     }
 
     pub async fn get_current_version() -> Result<String, MonitorError> {
@@ -51,24 +61,80 @@ impl Updater {
         Ok(app_root.join("manifest.json"))
     }
 
-    pub async fn check_for_updates(&self) -> Result<UpdateCheckResult, MonitorError> {
+    /// Lists the app releases offered on `channel`, plus the `pinned` version.
+    pub async fn list_versions(
+        &self,
+        channel: ReleaseChannel,
+        pinned: Option<&str>,
+    ) -> Result<AvailableVersions, MonitorError> {
+        let releases = self.feed.releases().await?;
+        Ok(assets::available_versions(&releases, channel, pinned))
+    }
+
+    /// Compares the running version against `version`, or without one against
+    /// the newest release on `channel`. An explicit version is offered even when
+    /// it is older than the running one.
+    pub async fn check_for_updates(
+        &self,
+        channel: ReleaseChannel,
+        version: Option<&str>,
+    ) -> Result<UpdateCheckResult, MonitorError> {
         let current_version = Self::get_current_version().await?;
-        let architecture = detect_architecture().await?;
-        let release = assets::fetch_release(&self.client, RELEASE_API_URL).await?;
+        let signed_only = app_release_keyring()?.is_some();
+        let architecture = detect_architecture(&SystemRunner).await?;
+        let release = self.fetch_target_release(channel, version).await?;
         let latest_version = release.tag_name.trim_start_matches('v').to_string();
-        let update_available = self.compare_versions(&current_version, &latest_version)?;
+        let update_available =
+            self.update_needed(&current_version, &latest_version, version.is_some())?;
 
-        let download_url =
-            self.select_update_download_url(&release.assets, architecture, update_available)?;
+        let urls = self.select_update_download_url(
+            &release.assets,
+            architecture,
+            update_available,
+            signed_only,
+        )?;
+        let (download_url, signature_url) = urls.unzip();
+        let signature_url = signature_url.flatten();
 
         Ok(UpdateCheckResult {
             current_version,
             latest_version,
             update_available,
             download_url,
+            signature_url,
         })
     }
 
+    async fn fetch_target_release(
+        &self,
+        channel: ReleaseChannel,
+        version: Option<&str>,
+    ) -> Result<assets::Release, MonitorError> {
+        if let Some(version) = version {
+            return self.feed.release(version).await.map_err(|err| {
+                MonitorError::Config(format!("App release {} is not available: {}", version, err))
+            });
+        }
+
+        let releases = self.feed.releases().await?;
+        assets::select_latest_release(&releases, channel)
+            .cloned()
+            .ok_or_else(|| {
+                MonitorError::Config(format!("No app release found on the {} channel", channel))
+            })
+    }
+
+    /// A newer release is an update; an explicitly requested one only has to differ.
+    fn update_needed(
+        &self,
+        current: &str,
+        target: &str,
+        explicit: bool,
+    ) -> Result<bool, MonitorError> {
+        Ok(self.compare_versions(current, target)?
+            || (explicit && self.compare_versions(target, current)?))
+    }
+
     fn compare_versions(&self, current: &str, latest: &str) -> Result<bool, MonitorError> {
         let current_semver = semver::Version::parse(current).map_err(|err| {
             MonitorError::Config(format!("Invalid current version '{}': {}", current, err))
@@ -88,47 +154,185 @@ impl Updater {
         }
     }
 
+    /// Returns the archive URL and its detached signature URL for this
+    /// architecture. The signature may only be missing unless `signed_only`.
     fn select_update_download_url(
         &self,
         release_assets: &[assets::ReleaseAsset],
         architecture: Architecture,
         update_available: bool,
-    ) -> Result<Option<String>, MonitorError> {
+        signed_only: bool,
+    ) -> Result<Option<(String, Option<String>)>, MonitorError> {
         if !update_available {
             return Ok(None);
         }
 
         let asset_name = self.get_asset_name_for_arch(architecture);
-        assets::select_asset_exact(release_assets, &asset_name)
-            .map(|asset| Some(asset.browser_download_url.clone()))
-            .ok_or_else(|| {
-                MonitorError::Config(format!(
-                    "A newer app release is available, but it does not contain the expected asset: {asset_name}"
-                ))
-            })
+        let asset = assets::select_asset_exact(release_assets, &asset_name).ok_or_else(|| {
+            MonitorError::Config(format!(
+                "A newer app release is available, but it does not contain the expected asset: {asset_name}"
+            ))
+        })?;
+
+        let signature_name = format!("{asset_name}{SIGNATURE_EXTENSION}");
+        let signature = assets::select_asset_exact(release_assets, &signature_name);
+        if signed_only && signature.is_none() {
+            return Err(MonitorError::Verification(format!(
+                "A newer app release is available, but {asset_name} is not signed"
+            )));
+        }
+
+        Ok(Some((
+            asset.browser_download_url.clone(),
+            signature.map(|signature| signature.browser_download_url.clone()),
+        )))
     }
 
     pub async fn download_and_apply_update(
         &self,
         download_url: &str,
+        signature_url: Option<&str>,
         progress_tx: Option<DownloadProgressSender>,
+        cancel: Option<CancelToken>,
     ) -> Result<(), MonitorError> {
+        // Fail before downloading anything if the update cannot be authenticated.
+        let keyring = app_release_keyring()?;
+        let signature_url = match (&keyring, signature_url) {
+            (Some(_), None) => {
+                return Err(MonitorError::Verification(
+                    "The app update is not signed".to_string(),
+                ))
+            }
+            (Some(_), Some(url)) => Some(url),
+            (None, _) => {
+                warn!("This build has no app release key; the update is installed unverified");
+                None
+            }
+        };
+
+        // Keep the archive outside the temp dir so an interrupted download can resume.
+        let app_root = Config::app_root_dir()?;
+        let zip_path = app_root.join(UPDATE_ARCHIVE_NAME);
+        download_to_path(
+            &self.client,
+            download_url,
+            &zip_path,
+            progress_tx,
+            cancel.as_ref(),
+        )
+        .await?;
+
+        if let (Some(keyring), Some(signature_url)) = (&keyring, signature_url) {
+            if let Err(err) = self
+                .verify_update_signature(keyring, &zip_path, signature_url)
+                .await
+            {
+                let _ = fs::remove_file(&zip_path).await;
+                return Err(err);
+            }
+        }
+
+        let extracted = self.extract_update(&zip_path).await;
+        let _ = fs::remove_file(&zip_path).await;
+        self.install_extracted(&app_root, &extracted?).await
+    }
+
+    /// Returns the app archive for this device waiting in `sideload_dir`. It
+    /// needs its signature next to it once this build embeds a release key.
+    pub async fn local_update(&self, sideload_dir: &Path) -> Option<AppArchive> {
+        let architecture = detect_architecture(&SystemRunner).await.ok()?;
+        let asset_name = self.get_asset_name_for_arch(architecture);
+        let local = sideload::find_app_archive(sideload_dir, &asset_name).await?;
+        let signed_only = !matches!(app_release_keyring(), Ok(None));
+        (local.signature.is_some() || !signed_only).then_some(local)
+    }
+
+    /// Applies the app archive from `sideload_dir` without network access.
+    ///
+    /// The archive is checked like a downloaded one and removed once installed.
+    pub async fn apply_local_update(&self, sideload_dir: &Path) -> Result<(), MonitorError> {
+        let keyring = app_release_keyring()?;
+        let local = self.local_update(sideload_dir).await.ok_or_else(|| {
+            MonitorError::Config(format!(
+                "No app archive for this device found in {}",
+                sideload_dir.display()
+            ))
+        })?;
+        match (&keyring, &local.signature) {
+            (Some(keyring), Some(signature)) => {
+                let signature = fs::read_to_string(signature).await?;
+                let archive = fs::read(&local.archive).await?;
+                keyring.verify_detached(&archive, &signature)?;
+                info!(archive = %local.file_name(), "Sideloaded update archive signature verified");
+            }
+            (Some(_), None) => {
+                return Err(MonitorError::Verification(format!(
+                    "{} is not signed",
+                    local.file_name()
+                )))
+            }
+            (None, _) => {
+                warn!("This build has no app release key; the update is installed unverified")
+            }
+        }
+
+        let app_root = Config::app_root_dir()?;
+        let extracted = self.extract_update(&local.archive).await?;
+        self.install_extracted(&app_root, &extracted).await?;
+        sideload::remove_consumed(&local.archive).await;
+        if let Some(signature) = &local.signature {
+            sideload::remove_consumed(signature).await;
+        }
+        Ok(())
+    }
+
+    async fn extract_update(&self, zip_path: &Path) -> Result<TempDir, MonitorError> {
         let temp_dir = TempDir::new().map_err(|err| {
             MonitorError::Config(format!("Failed to create temporary directory: {}", err))
         })?;
+        archive::extract_zip_archive(zip_path, temp_dir.path()).await?;
+        Ok(temp_dir)
+    }
 
-        let zip_path = temp_dir.path().join("update.zip");
-        download_to_path(&self.client, download_url, &zip_path, progress_tx).await?;
+    /// Switches to an extracted update and keeps the replaced version for rollback.
+    async fn install_extracted(
+        &self,
+        app_root: &Path,
+        extracted: &TempDir,
+    ) -> Result<(), MonitorError> {
+        let previous_version = read_manifest_version(app_root).await;
+        let retired = self.install_tree(app_root, extracted.path()).await?;
+        // The update is live now; without a backup it just cannot be rolled back.
+        if let Err(err) = self.retire_to_backup(app_root, &retired).await {
+            error!(error = ?err, "Failed to keep previous version for rollback");
+            let _ = fs::remove_dir_all(&retired).await;
+            return Ok(());
+        }
 
-        let extract_dir = temp_dir.path().join("extracted");
-        fs::create_dir_all(&extract_dir).await?;
-        archive::extract_zip_archive(&zip_path, &extract_dir).await?;
+        self.mark_boot_pending(app_root, previous_version).await
+    }
 
-        let app_root = Config::app_root_dir()?;
-        self.copy_update_files(&extract_dir, &app_root).await
+    async fn verify_update_signature(
+        &self,
+        keyring: &Keyring,
+        zip_path: &Path,
+        signature_url: &str,
+    ) -> Result<(), MonitorError> {
+        let signature = self
+            .client
+            .get(signature_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let archive = fs::read(zip_path).await?;
+        keyring.verify_detached(&archive, &signature)?;
+        info!("Update archive signature verified");
+        Ok(())
     }
 
-    async fn copy_update_files(
+    pub(super) async fn copy_update_files(
         &self,
         source_dir: &Path,
         dest_dir: &Path,
@@ -160,7 +364,10 @@ impl Updater {
         Ok(())
     }
 
-    async fn validate_update_payload(&self, payload_root: &Path) -> Result<(), MonitorError> {
+    pub(super) async fn validate_update_payload(
+        &self,
+        payload_root: &Path,
+    ) -> Result<(), MonitorError> {
         for relative_path in ["manifest.json", "resources.rcc", "icon.png"] {
             let path = payload_root.join(relative_path);
             let metadata = fs::metadata(&path).await.map_err(|err| {
@@ -234,12 +441,14 @@ impl Updater {
     fn should_skip_entry(name: &str) -> bool {
         name == "config.json"
             || name == "syncthing"
+            || name == "syncthing.prev"
             || name.starts_with("__MACOSX")
             || name.starts_with("._")
             || name == ".DS_Store"
+            || name == ROLLBACK_DIR_NAME
     }
 
-    fn copy_dir_recursive<'a>(
+    pub(super) fn copy_dir_recursive<'a>(
         &'a self,
         source: &'a Path,
         dest: &'a Path,
@@ -317,7 +526,7 @@ mod tests {
     use super::*;
 
     fn updater() -> Updater {
-        Updater::new()
+        Updater::new(&Config::default())
     }
 
     #[test]
@@ -328,22 +537,79 @@ mod tests {
             browser_download_url: "https://example.com/armv7.zip".to_string(),
         }];
 
-        let result = updater.select_update_download_url(&assets, Architecture::Arm64, true);
+        let result = updater.select_update_download_url(&assets, Architecture::Arm64, true, true);
 
         assert!(result.is_err());
     }
 
+    #[test]
+    fn update_check_errors_when_release_asset_is_unsigned() {
+        let updater = updater();
+        let assets = vec![assets::ReleaseAsset {
+            name: "syncthing-rm-appload-aarch64.zip".to_string(),
+            browser_download_url: "https://example.com/aarch64.zip".to_string(),
+        }];
+
+        let result = updater.select_update_download_url(&assets, Architecture::Arm64, true, true);
+        assert!(matches!(result, Err(MonitorError::Verification(_))));
+
+        // Builds without a release key still update to the release that ships one.
+        let result = updater
+            .select_update_download_url(&assets, Architecture::Arm64, true, false)
+            .expect("unsigned asset without a key");
+        assert_eq!(
+            result,
+            Some(("https://example.com/aarch64.zip".to_string(), None))
+        );
+    }
+
+    #[test]
+    fn update_check_returns_archive_and_signature_urls() {
+        let updater = updater();
+        let assets = vec![
+            assets::ReleaseAsset {
+                name: "syncthing-rm-appload-aarch64.zip".to_string(),
+                browser_download_url: "https://example.com/aarch64.zip".to_string(),
+            },
+            assets::ReleaseAsset {
+                name: "syncthing-rm-appload-aarch64.zip.asc".to_string(),
+                browser_download_url: "https://example.com/aarch64.zip.asc".to_string(),
+            },
+        ];
+
+        let result = updater
+            .select_update_download_url(&assets, Architecture::Arm64, true, true)
+            .expect("select signed asset");
+
+        assert_eq!(
+            result,
+            Some((
+                "https://example.com/aarch64.zip".to_string(),
+                Some("https://example.com/aarch64.zip.asc".to_string())
+            ))
+        );
+    }
+
     #[test]
     fn update_check_omits_download_url_when_no_update_is_available() {
         let updater = updater();
 
         let result = updater
-            .select_update_download_url(&[], Architecture::Arm64, false)
+            .select_update_download_url(&[], Architecture::Arm64, false, true)
             .expect("no update needs no asset");
 
         assert_eq!(result, None);
     }
 
+    #[test]
+    fn explicit_version_is_offered_even_when_older() {
+        let updater = updater();
+
+        assert!(!updater.update_needed("1.4.0", "1.3.0", false).unwrap());
+        assert!(updater.update_needed("1.4.0", "1.3.0", true).unwrap());
+        assert!(!updater.update_needed("1.4.0", "1.4.0", true).unwrap());
+    }
+
     #[tokio::test]
     async fn validate_update_payload_requires_core_files() {
         let updater = updater();
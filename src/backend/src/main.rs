@@ -7,16 +7,27 @@ mod types;
 mod utils;
 
 use appload_client::AppLoad;
-use tracing::error;
+use tracing::{error, warn};
 use tracing_subscriber::EnvFilter;
 
 use crate::app::Backend;
 use crate::config::Config;
+use crate::deployment::rollback::LaunchOutcome;
+use crate::deployment::Updater;
 
 #[tokio::main]
 async fn main() {
     init_tracing();
     let config = Config::load().await;
+    match Updater::new(&config).record_launch().await {
+        Ok(LaunchOutcome::Normal) => {}
+        Ok(LaunchOutcome::RolledBack(notice)) => {
+            // The files on disk belong to the restored version now; let it start fresh.
+            warn!("{}", notice.describe());
+            std::process::exit(0);
+        }
+        Err(err) => error!(error = ?err, "Failed to check pending app update"),
+    }
     let monitor = Backend::new(config).await;
     match AppLoad::new(monitor) {
         Ok(mut app) => {
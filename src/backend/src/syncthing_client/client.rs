@@ -114,6 +114,28 @@ impl SyncthingClient {
         })
     }
 
+    /// Returns this instance's device ID.
+    pub async fn my_id(&mut self) -> Result<String, MonitorError> {
+        let status: Value = self.http.get_json("/rest/system/status").await?;
+        status
+            .get("myID")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| {
+                MonitorError::Syncthing("Device ID missing from /rest/system/status".to_string())
+            })
+    }
+
+    /// Fetches the complete Syncthing configuration.
+    pub async fn get_config(&mut self) -> Result<Value, MonitorError> {
+        self.http.get_json("/rest/config").await
+    }
+
+    /// Replaces the complete Syncthing configuration.
+    pub async fn put_config(&mut self, config: &Value) -> Result<(), MonitorError> {
+        self.http.put_json("/rest/config", config).await
+    }
+
     /// Fetches the GUI address from Syncthing configuration.
     pub async fn get_gui_address(&mut self) -> Result<String, MonitorError> {
         let config: Value = self.http.get_json("/rest/config").await?;
@@ -154,6 +176,18 @@ impl SyncthingClient {
         self.http.post("/rest/system/restart").await
     }
 
+    /// Returns the running Syncthing version without the leading `v`.
+    pub async fn version(&mut self) -> Result<String, MonitorError> {
+        let version: Value = self.http.get_json("/rest/system/version").await?;
+        version
+            .get("version")
+            .and_then(Value::as_str)
+            .map(|version| version.trim_start_matches('v').to_string())
+            .ok_or_else(|| {
+                MonitorError::Syncthing("Version missing from /rest/system/version".to_string())
+            })
+    }
+
     /// Checks whether Syncthing can upgrade itself.
     pub async fn check_upgrade(&mut self) -> Result<SyncthingUpgradeCheck, MonitorError> {
         self.http.get_json("/rest/system/upgrade").await
@@ -77,6 +77,50 @@ fn extract_api_key(contents: &str) -> Result<Option<String>, MonitorError> {
     Ok(None)
 }
 
+/// Paths of the folders in `config.xml`, with a leading `~` expanded.
+pub async fn load_folder_paths(config: &Config) -> Result<Vec<String>, MonitorError> {
+    let contents = fs::read_to_string(config.syncthing_config_xml_path())
+        .await
+        .map_err(MonitorError::Io)?;
+    let home = env::var("HOME").unwrap_or_else(|_| "/home/root".to_string());
+    Ok(extract_folder_paths(&contents)?
+        .into_iter()
+        .map(|path| match path.strip_prefix('~') {
+            Some(rest) => format!("{home}{rest}"),
+            None => path,
+        })
+        .collect())
+}
+
+fn extract_folder_paths(contents: &str) -> Result<Vec<String>, MonitorError> {
+    let parser = EventReader::new(contents.as_bytes());
+    let mut depth = 0;
+    let mut paths = Vec::new();
+
+    for event in parser {
+        match event.map_err(|err| MonitorError::Config(format!("Invalid Syncthing XML: {err}")))? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                depth += 1;
+                if depth == 2 && name.local_name == "folder" {
+                    paths.extend(
+                        attributes
+                            .into_iter()
+                            .find(|attribute| attribute.name.local_name == "path")
+                            .map(|attribute| attribute.value)
+                            .filter(|path| !path.is_empty()),
+                    );
+                }
+            }
+            XmlEvent::EndElement { .. } => depth -= 1,
+            _ => {}
+        }
+    }
+
+    Ok(paths)
+}
+
 fn is_gui_api_key_path(path: &[String]) -> bool {
     path.len() == 3 && path[0] == "configuration" && path[1] == "gui" && path[2] == "apikey"
 }
@@ -115,6 +159,26 @@ mod tests {
         assert_eq!(extract_api_key(xml).expect("parse xml"), None);
     }
 
+    #[test]
+    fn extracts_paths_of_top_level_folders() {
+        let xml = r#"
+            <configuration>
+                <folder id="default" path="~/Sync"></folder>
+                <folder id="notes" path="/home/root/notes">
+                    <device id="PEER"></device>
+                </folder>
+                <defaults>
+                    <folder id="" path="~"></folder>
+                </defaults>
+            </configuration>
+        "#;
+
+        assert_eq!(
+            extract_folder_paths(xml).expect("parse xml"),
+            vec!["~/Sync".to_string(), "/home/root/notes".to_string()]
+        );
+    }
+
     #[test]
     fn rejects_invalid_xml() {
         assert!(extract_api_key("<configuration><gui>").is_err());
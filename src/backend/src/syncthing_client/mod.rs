@@ -3,9 +3,14 @@ mod client;
 mod core;
 mod helpers;
 mod models;
+mod upgrade_watch;
 
 pub use api::SyncthingUpgradeCheck;
 pub use client::SyncthingClient;
+pub use helpers::load_folder_paths;
+pub use upgrade_watch::{
+    probe_version, wait_for_version, UpgradeWatch, WatchState, UPGRADE_POLL_INTERVAL,
+};
 
 // Re-export data types at root for convenience
 pub use models::{FolderPayload, PeerPayload, SyncthingOverview};
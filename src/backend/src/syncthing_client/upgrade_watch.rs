@@ -0,0 +1,129 @@
+//! Following a Syncthing restart until the API reports the expected version.
+
+use std::time::Duration;
+
+use tokio::time::{sleep, Instant};
+
+use crate::config::Config;
+use crate::types::MonitorError;
+
+use super::client::SyncthingClient;
+
+pub const UPGRADE_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchState {
+    /// The old version is still answering.
+    WaitingForRestart,
+    /// The API went away, so the service is restarting.
+    Restarting,
+    /// The API answers with the expected version.
+    Confirmed,
+}
+
+/// Tracks API observations after an upgrade was triggered.
+#[derive(Debug)]
+pub struct UpgradeWatch {
+    expected: String,
+    restart_seen: bool,
+    last_observation: Option<String>,
+}
+
+impl UpgradeWatch {
+    pub fn new(expected: &str) -> Self {
+        Self {
+            expected: normalize_version(expected).to_string(),
+            restart_seen: false,
+            last_observation: None,
+        }
+    }
+
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+
+    /// Records one poll of `/rest/system/version`.
+    pub fn observe(&mut self, running: Result<&str, &MonitorError>) -> WatchState {
+        match running {
+            Ok(running) if normalize_version(running) == self.expected => WatchState::Confirmed,
+            Ok(running) => {
+                self.last_observation =
+                    Some(format!("still reports v{}", normalize_version(running)));
+                if self.restart_seen {
+                    WatchState::Restarting
+                } else {
+                    WatchState::WaitingForRestart
+                }
+            }
+            Err(err) => {
+                self.restart_seen = true;
+                self.last_observation = Some(err.to_string());
+                WatchState::Restarting
+            }
+        }
+    }
+
+    /// The error reported when `timeout` passed without confirmation.
+    pub fn timed_out(&self, timeout: Duration) -> MonitorError {
+        let phase = if self.restart_seen {
+            "did not come back"
+        } else {
+            "did not restart"
+        };
+        MonitorError::Syncthing(format!(
+            "Syncthing {} with v{} within {}s ({})",
+            phase,
+            self.expected,
+            timeout.as_secs(),
+            self.last_observation.as_deref().unwrap_or("no response")
+        ))
+    }
+}
+
+/// Asks the local Syncthing API for its version, discovering it afresh so a
+/// restarted instance is picked up.
+pub async fn probe_version(config: &Config) -> Result<String, MonitorError> {
+    SyncthingClient::discover(config).await?.version().await
+}
+
+/// Polls the API until it reports `expected` or `timeout` passes.
+pub async fn wait_for_version(
+    config: &Config,
+    expected: &str,
+    timeout: Duration,
+) -> Result<(), MonitorError> {
+    let mut watch = UpgradeWatch::new(expected);
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        sleep(UPGRADE_POLL_INTERVAL).await;
+        let running = probe_version(config).await;
+        if watch.observe(running.as_deref()) == WatchState::Confirmed {
+            return Ok(());
+        }
+    }
+    Err(watch.timed_out(timeout))
+}
+
+fn normalize_version(version: &str) -> &str {
+    version.trim().trim_start_matches('v')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_is_detected_before_new_version_is_confirmed() {
+        let mut watch = UpgradeWatch::new("v1.30.0");
+        let offline = MonitorError::Syncthing("connection refused".to_string());
+
+        assert_eq!(watch.observe(Ok("v1.29.2")), WatchState::WaitingForRestart);
+        assert_eq!(watch.observe(Err(&offline)), WatchState::Restarting);
+        assert_eq!(watch.observe(Ok("v1.29.2")), WatchState::Restarting);
+        assert!(watch
+            .timed_out(Duration::from_secs(180))
+            .to_string()
+            .contains("did not come back with v1.30.0"));
+        assert_eq!(watch.observe(Ok("1.30.0")), WatchState::Confirmed);
+    }
+}
@@ -1,36 +1,57 @@
 use std::collections::HashMap;
 
-use tokio::process::Command;
+use std::sync::Arc;
+
 use tracing::{error, warn};
 
 use crate::config::Config;
 use crate::types::MonitorError;
-use crate::utils::{filesystem, systemctl};
+use crate::utils::filesystem::RootMounts;
+use crate::utils::{systemctl, CommandRunner};
 
 use super::actions::ServiceAction;
+use super::dbus::DbusSystemd;
 use super::types::SystemdStatus;
 
 pub struct SystemdClient<'a> {
     service_name: &'a str,
+    runner: &'a dyn CommandRunner,
+    bus: Option<DbusSystemd>,
 }
 
 impl<'a> SystemdClient<'a> {
-    pub fn new(service_name: &'a str) -> Self {
-        Self { service_name }
+    /// A client that runs `systemctl` through `runner`
+    pub fn new(service_name: &'a str, runner: &'a dyn CommandRunner) -> Self {
+        Self {
+            service_name,
+            runner,
+            bus: None,
+        }
+    }
+
+    /// Prefer systemd's D-Bus API when the system bus is available
+    pub async fn with_system_bus(mut self) -> Self {
+        self.bus = DbusSystemd::system().await;
+        self
     }
 
     /// Query the current status of the systemd service
     pub async fn query_status(&self) -> SystemdStatus {
+        if let Some(dbus) = &self.bus {
+            match dbus.status(self.service_name).await {
+                Ok(status) => return status,
+                Err(err) => warn!(error = ?err, "D-Bus status query failed, using systemctl"),
+            }
+        }
+
         let mut status = SystemdStatus {
             name: self.service_name.to_string(),
             ..Default::default()
         };
 
-        match Command::new("systemctl")
-            .arg("show")
-            .arg(self.service_name)
-            .arg("--no-page")
-            .output()
+        match self
+            .runner
+            .output("systemctl", &["show", self.service_name, "--no-page"])
             .await
         {
             Ok(output) if output.status.success() => {
@@ -45,6 +66,11 @@ impl<'a> SystemdClient<'a> {
                     status.pid = map
                         .get("ExecMainPID")
                         .and_then(|pid| pid.parse::<u32>().ok());
+                    status.restarts = map.get("NRestarts").and_then(|n| n.parse().ok());
+                    status.exec_main_status =
+                        map.get("ExecMainStatus").and_then(|code| code.parse().ok());
+                    status.exec_main_code =
+                        map.get("ExecMainCode").and_then(|code| code.parse().ok());
                 }
             }
             Ok(output) => {
@@ -60,12 +86,12 @@ impl<'a> SystemdClient<'a> {
         }
 
         if status.raw_excerpt.is_none() {
-            if let Ok(output) = Command::new("systemctl")
-                .arg("status")
-                .arg(self.service_name)
-                .arg("--no-pager")
-                .arg("--lines=5")
-                .output()
+            if let Ok(output) = self
+                .runner
+                .output(
+                    "systemctl",
+                    &["status", self.service_name, "--no-pager", "--lines=5"],
+                )
                 .await
             {
                 if output.status.success() {
@@ -79,52 +105,76 @@ impl<'a> SystemdClient<'a> {
     }
 
     /// Control the systemd service (start, stop, restart, enable, disable)
-    pub async fn control_service(&self, action: ServiceAction) -> Result<String, MonitorError> {
+    pub async fn control_service(
+        &self,
+        mounts: &Arc<RootMounts>,
+        action: ServiceAction,
+    ) -> Result<String, MonitorError> {
         let success_message = format!("{} {}", self.service_name, action.past_tense());
 
         if action.needs_remount() {
-            // Remount filesystem as read-write, track if it was read-only before
-            let was_readonly = filesystem::remount_root_rw().await?;
+            let guard = mounts.writable().await?;
+            let result = self.run_action(action, success_message).await;
 
-            // Try to unmount /etc overlay if it exists
-            if let Err(err) = filesystem::unmount_etc_if_needed().await {
-                warn!(error = ?err, "Failed to unmount /etc before systemctl operation");
-                // Continue anyway, might not be critical
-            }
-
-            // Execute the systemctl command
-            let result = systemctl::execute_with_message(
-                &[action.as_str(), self.service_name],
-                success_message,
-            )
-            .await;
-
-            // Restore read-only mount only if it was read-only before
-            if let Err(restore_err) = filesystem::restore_mounts_if_needed(was_readonly).await {
+            if let Err(restore_err) = guard.release().await {
                 error!(error = ?restore_err, "Failed to restore mounts after systemctl operation");
             }
 
             result
         } else {
-            systemctl::execute_with_message(&[action.as_str(), self.service_name], success_message)
+            self.run_action(action, success_message).await
+        }
+    }
+
+    async fn run_action(
+        &self,
+        action: ServiceAction,
+        success_message: String,
+    ) -> Result<String, MonitorError> {
+        match &self.bus {
+            Some(dbus) => dbus
+                .control(self.service_name, action)
                 .await
+                .map(|()| success_message),
+            None => {
+                if matches!(action, ServiceAction::Start | ServiceAction::Restart) {
+                    // A unit that hit its start limit refuses to start until reset.
+                    if let Err(err) =
+                        systemctl::execute(self.runner, &["reset-failed", self.service_name]).await
+                    {
+                        warn!(error = ?err, "Failed to reset the unit's failed state");
+                    }
+                }
+                systemctl::execute_with_message(
+                    self.runner,
+                    &[action.as_str(), self.service_name],
+                    success_message,
+                )
+                .await
+            }
         }
     }
 }
 
 /// Convenience function to query systemd status using config
-pub async fn query_status(config: &Config) -> SystemdStatus {
-    let client = SystemdClient::new(&config.systemd_service_name);
+pub async fn query_status(config: &Config, runner: &dyn CommandRunner) -> SystemdStatus {
+    let client = SystemdClient::new(&config.systemd_service_name, runner)
+        .with_system_bus()
+        .await;
     client.query_status().await
 }
 
 /// Convenience function to control systemd service using config
 pub async fn control_service(
     config: &Config,
+    runner: &dyn CommandRunner,
+    mounts: &Arc<RootMounts>,
     action: ServiceAction,
 ) -> Result<String, MonitorError> {
-    let client = SystemdClient::new(&config.systemd_service_name);
-    client.control_service(action).await
+    let client = SystemdClient::new(&config.systemd_service_name, runner)
+        .with_system_bus()
+        .await;
+    client.control_service(mounts, action).await
 }
 
 fn parse_systemctl_show(bytes: &[u8]) -> Result<HashMap<String, String>, MonitorError> {
@@ -138,3 +188,52 @@ fn parse_systemctl_show(bytes: &[u8]) -> Result<HashMap<String, String>, Monitor
     }
     Ok(map)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::command::fake::{failure, success, ScriptedRunner};
+    use crate::utils::filesystem::fake::{FakeMounts, ETC_OVERLAY_MOUNT, READ_ONLY_ROOT};
+    use crate::utils::SharedRunner;
+
+    fn remount_around(
+        systemctl: &str,
+        result: std::io::Result<std::process::Output>,
+    ) -> (SharedRunner, FakeMounts) {
+        let runner = ScriptedRunner::new()
+            .expect("mount -o remount,rw /", success(""))
+            .expect("umount -R /etc", success(""))
+            .expect(systemctl, result)
+            .expect("mount -o remount,ro /", success(""))
+            .expect(ETC_OVERLAY_MOUNT, success(""))
+            .shared();
+        let mounts = FakeMounts::new(runner.clone(), READ_ONLY_ROOT);
+        (runner, mounts)
+    }
+
+    #[tokio::test]
+    async fn enable_runs_on_a_writable_root() {
+        let (runner, fake) = remount_around("systemctl enable syncthing.service", success(""));
+
+        let message = SystemdClient::new("syncthing.service", runner.as_ref())
+            .control_service(&fake.mounts, ServiceAction::Enable)
+            .await
+            .expect("enable service");
+        assert_eq!(message, "syncthing.service enabled");
+    }
+
+    #[tokio::test]
+    async fn failed_disable_still_restores_mounts() {
+        let (runner, fake) = remount_around(
+            "systemctl disable syncthing.service",
+            failure(1, "Failed to disable unit: Access denied"),
+        );
+
+        let err = SystemdClient::new("syncthing.service", runner.as_ref())
+            .control_service(&fake.mounts, ServiceAction::Disable)
+            .await
+            .expect_err("disable fails");
+        assert!(err.to_string().contains("Access denied"), "{}", err);
+        assert!(!fake.marker_present());
+    }
+}
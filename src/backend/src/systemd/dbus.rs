@@ -0,0 +1,604 @@
+//! systemd over the system bus (`org.freedesktop.systemd1`), so status queries
+//! and unit changes do not fork `systemctl` every few seconds.
+
+use std::time::Duration;
+
+use chrono::{Local, TimeZone};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use tokio::sync::OnceCell;
+use tokio::time::timeout;
+use tracing::{info, warn};
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{proxy, Connection};
+
+use crate::types::MonitorError;
+
+use super::actions::ServiceAction;
+use super::types::SystemdStatus;
+
+/// How long a start, stop or restart job may take, like `systemctl`'s default.
+const JOB_TIMEOUT: Duration = Duration::from_secs(90);
+
+static SYSTEM_BUS: OnceCell<Option<Connection>> = OnceCell::const_new();
+
+#[proxy(
+    interface = "org.freedesktop.systemd1.Manager",
+    default_service = "org.freedesktop.systemd1",
+    default_path = "/org/freedesktop/systemd1",
+    gen_blocking = false
+)]
+trait Manager {
+    fn load_unit(&self, name: &str) -> zbus::Result<OwnedObjectPath>;
+    fn start_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+    fn stop_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+    fn restart_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+    fn enable_unit_files(
+        &self,
+        files: &[&str],
+        runtime: bool,
+        force: bool,
+    ) -> zbus::Result<(bool, Vec<(String, String, String)>)>;
+    fn disable_unit_files(
+        &self,
+        files: &[&str],
+        runtime: bool,
+    ) -> zbus::Result<Vec<(String, String, String)>>;
+    fn reload(&self) -> zbus::Result<()>;
+    fn reset_failed_unit(&self, name: &str) -> zbus::Result<()>;
+    /// Without a subscriber, systemd does not emit unit signals.
+    fn subscribe(&self) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn job_removed(
+        &self,
+        id: u32,
+        job: OwnedObjectPath,
+        unit: String,
+        result: String,
+    ) -> zbus::Result<()>;
+    #[zbus(signal)]
+    fn unit_files_changed(&self) -> zbus::Result<()>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.systemd1.Unit",
+    default_service = "org.freedesktop.systemd1",
+    gen_blocking = false
+)]
+trait Unit {
+    #[zbus(property)]
+    fn active_state(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn sub_state(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn unit_file_state(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn description(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn active_enter_timestamp(&self) -> zbus::Result<u64>;
+    #[zbus(property)]
+    fn inactive_enter_timestamp(&self) -> zbus::Result<u64>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.systemd1.Service",
+    default_service = "org.freedesktop.systemd1",
+    gen_blocking = false
+)]
+trait Service {
+    #[zbus(property)]
+    fn result(&self) -> zbus::Result<String>;
+    #[zbus(property, name = "ExecMainPID")]
+    fn exec_main_pid(&self) -> zbus::Result<u32>;
+    #[zbus(property, name = "NRestarts")]
+    fn n_restarts(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn exec_main_status(&self) -> zbus::Result<i32>;
+    #[zbus(property)]
+    fn exec_main_code(&self) -> zbus::Result<i32>;
+}
+
+/// systemd's manager on a bus connection.
+#[derive(Clone)]
+pub struct DbusSystemd {
+    connection: Connection,
+}
+
+impl DbusSystemd {
+    pub fn new(connection: Connection) -> Self {
+        Self { connection }
+    }
+
+    /// The system bus, connected once. `None` when it is unavailable, in which
+    /// case callers use `systemctl`.
+    pub async fn system() -> Option<Self> {
+        SYSTEM_BUS
+            .get_or_init(|| async {
+                match Connection::system().await {
+                    Ok(connection) => {
+                        info!("Connected to systemd over D-Bus");
+                        Some(connection)
+                    }
+                    Err(err) => {
+                        warn!(error = ?err, "D-Bus unavailable, using systemctl");
+                        None
+                    }
+                }
+            })
+            .await
+            .clone()
+            .map(Self::new)
+    }
+
+    pub async fn status(&self, name: &str) -> Result<SystemdStatus, MonitorError> {
+        let path = self
+            .manager()
+            .await?
+            .load_unit(name)
+            .await
+            .map_err(dbus_error)?;
+        let unit = UnitProxy::builder(&self.connection)
+            .path(path.clone())
+            .map_err(dbus_error)?
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await
+            .map_err(dbus_error)?;
+        let service = ServiceProxy::builder(&self.connection)
+            .path(path)
+            .map_err(dbus_error)?
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await
+            .map_err(dbus_error)?;
+
+        Ok(SystemdStatus {
+            name: name.to_string(),
+            active_state: unit.active_state().await.ok(),
+            sub_state: unit.sub_state().await.ok(),
+            unit_file_state: unit.unit_file_state().await.ok(),
+            result: service.result().await.ok(),
+            // systemd reports 0 when no main process runs.
+            pid: service.exec_main_pid().await.ok().filter(|pid| *pid != 0),
+            restarts: service.n_restarts().await.ok(),
+            exec_main_status: service.exec_main_status().await.ok(),
+            exec_main_code: service.exec_main_code().await.ok(),
+            active_enter_timestamp: unit
+                .active_enter_timestamp()
+                .await
+                .ok()
+                .and_then(format_timestamp),
+            inactive_enter_timestamp: unit
+                .inactive_enter_timestamp()
+                .await
+                .ok()
+                .and_then(format_timestamp),
+            description: unit.description().await.ok(),
+            raw_excerpt: None,
+            error: None,
+            diagnosis: None,
+        })
+    }
+
+    /// Runs `action` and, like `systemctl`, waits for the job to finish.
+    pub async fn control(&self, name: &str, action: ServiceAction) -> Result<(), MonitorError> {
+        let manager = self.manager().await?;
+        match action {
+            ServiceAction::Enable => {
+                manager
+                    .enable_unit_files(&[name], false, false)
+                    .await
+                    .map_err(dbus_error)?;
+                return manager.reload().await.map_err(dbus_error);
+            }
+            ServiceAction::Disable => {
+                manager
+                    .disable_unit_files(&[name], false)
+                    .await
+                    .map_err(dbus_error)?;
+                return manager.reload().await.map_err(dbus_error);
+            }
+            _ => {}
+        }
+
+        if !matches!(action, ServiceAction::Stop) {
+            // A unit that hit its start limit refuses to start until reset.
+            if let Err(err) = manager.reset_failed_unit(name).await {
+                warn!(error = ?err, unit = name, "Failed to reset the unit's failed state");
+            }
+        }
+        // Listen before queuing the job so its removal cannot be missed.
+        manager.subscribe().await.map_err(dbus_error)?;
+        let mut removed = manager.receive_job_removed().await.map_err(dbus_error)?;
+        let job = match action {
+            ServiceAction::Start => manager.start_unit(name, "replace").await,
+            ServiceAction::Stop => manager.stop_unit(name, "replace").await,
+            _ => manager.restart_unit(name, "replace").await,
+        }
+        .map_err(dbus_error)?;
+
+        let wait = async {
+            while let Some(signal) = removed.next().await {
+                let args = signal.args().map_err(dbus_error)?;
+                if args.job == job {
+                    return Ok(args.result);
+                }
+            }
+            Err(MonitorError::Systemd(
+                "D-Bus connection closed while waiting for systemd".to_string(),
+            ))
+        };
+        let result = timeout(JOB_TIMEOUT, wait).await.map_err(|_| {
+            MonitorError::Systemd(format!(
+                "{} of {} did not finish within {}s",
+                action.as_str(),
+                name,
+                JOB_TIMEOUT.as_secs()
+            ))
+        })??;
+        if result == "done" {
+            Ok(())
+        } else {
+            Err(MonitorError::Systemd(format!(
+                "Job to {} {} finished with result '{}'",
+                action.as_str(),
+                name,
+                result
+            )))
+        }
+    }
+
+    /// Yields whenever the unit's state, main process or unit file state changes.
+    pub async fn watch(&self, name: &str) -> Result<BoxStream<'static, ()>, MonitorError> {
+        let manager = self.manager().await?;
+        manager.subscribe().await.map_err(dbus_error)?;
+        let path = manager.load_unit(name).await.map_err(dbus_error)?;
+        let unit = UnitProxy::builder(&self.connection)
+            .path(path.clone())
+            .map_err(dbus_error)?
+            .build()
+            .await
+            .map_err(dbus_error)?;
+        let service = ServiceProxy::builder(&self.connection)
+            .path(path)
+            .map_err(dbus_error)?
+            .build()
+            .await
+            .map_err(dbus_error)?;
+
+        let changes: Vec<BoxStream<'static, ()>> = vec![
+            unit.receive_active_state_changed()
+                .await
+                .map(|_| ())
+                .boxed(),
+            unit.receive_sub_state_changed().await.map(|_| ()).boxed(),
+            service.receive_result_changed().await.map(|_| ()).boxed(),
+            service
+                .receive_exec_main_pid_changed()
+                .await
+                .map(|_| ())
+                .boxed(),
+            service
+                .receive_n_restarts_changed()
+                .await
+                .map(|_| ())
+                .boxed(),
+            manager
+                .receive_unit_files_changed()
+                .await
+                .map_err(dbus_error)?
+                .map(|_| ())
+                .boxed(),
+        ];
+        Ok(stream::select_all(changes).boxed())
+    }
+
+    async fn manager(&self) -> Result<ManagerProxy<'static>, MonitorError> {
+        ManagerProxy::new(&self.connection)
+            .await
+            .map_err(dbus_error)
+    }
+}
+
+fn dbus_error(err: zbus::Error) -> MonitorError {
+    MonitorError::Systemd(format!("D-Bus: {err}"))
+}
+
+/// Formats microseconds since the epoch the way `systemctl show` does.
+fn format_timestamp(micros: u64) -> Option<String> {
+    if micros == 0 {
+        return None;
+    }
+    let micros = i64::try_from(micros).ok()?;
+    Local
+        .timestamp_micros(micros)
+        .single()
+        .map(|time| time.format("%a %Y-%m-%d %H:%M:%S %Z").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+
+    use zbus::object_server::SignalEmitter;
+    use zbus::zvariant::ObjectPath;
+
+    use super::*;
+
+    const UNIT_PATH: &str = "/org/freedesktop/systemd1/unit/syncthing_2eservice";
+    const JOB_PATH: &str = "/org/freedesktop/systemd1/job/1";
+
+    /// A private bus, so the tests do not need systemd or the system bus.
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+        _dir: tempfile::TempDir,
+    }
+
+    impl PrivateBus {
+        fn start() -> Option<Self> {
+            let dir = tempfile::tempdir().expect("create bus dir");
+            let config = dir.path().join("bus.conf");
+            std::fs::write(
+                &config,
+                format!(
+                    "<busconfig><type>session</type><listen>unix:path={}</listen>\
+                     <auth>EXTERNAL</auth><policy context=\"default\">\
+                     <allow send_destination=\"*\" eavesdrop=\"true\"/><allow eavesdrop=\"true\"/>\
+                     <allow own=\"*\"/></policy></busconfig>",
+                    dir.path().join("bus").display()
+                ),
+            )
+            .expect("write bus config");
+            let mut daemon = match Command::new("dbus-daemon")
+                .arg(format!("--config-file={}", config.display()))
+                .args(["--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+            {
+                Ok(daemon) => daemon,
+                Err(err) => {
+                    eprintln!("skipping D-Bus test, dbus-daemon unavailable: {err}");
+                    return None;
+                }
+            };
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().expect("daemon stdout"))
+                .read_line(&mut address)
+                .expect("read bus address");
+            Some(Self {
+                daemon,
+                address: address.trim().to_string(),
+                _dir: dir,
+            })
+        }
+
+        async fn connect(&self) -> Connection {
+            zbus::connection::Builder::address(self.address.as_str())
+                .expect("parse bus address")
+                .build()
+                .await
+                .expect("connect to private bus")
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    struct FakeManager {
+        job_result: String,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.systemd1.Manager")]
+    impl FakeManager {
+        fn load_unit(&self, _name: &str) -> OwnedObjectPath {
+            ObjectPath::try_from(UNIT_PATH).expect("unit path").into()
+        }
+
+        async fn start_unit(
+            &self,
+            name: &str,
+            _mode: &str,
+            #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        ) -> OwnedObjectPath {
+            let job = ObjectPath::try_from(JOB_PATH).expect("job path");
+            let result = self.job_result.clone();
+            let name = name.to_string();
+            let emitter = emitter.to_owned();
+            // The reply goes out before the job finishes, as with systemd.
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                let _ = Self::job_removed(&emitter, 1, job, &name, &result).await;
+            });
+            ObjectPath::try_from(JOB_PATH).expect("job path").into()
+        }
+
+        fn subscribe(&self) {}
+
+        fn reset_failed_unit(&self, _name: &str) {}
+
+        #[zbus(signal)]
+        async fn job_removed(
+            emitter: &SignalEmitter<'_>,
+            id: u32,
+            job: ObjectPath<'_>,
+            unit: &str,
+            result: &str,
+        ) -> zbus::Result<()>;
+
+        #[zbus(signal)]
+        async fn unit_files_changed(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+    }
+
+    struct FakeUnit {
+        active_state: String,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.systemd1.Unit")]
+    impl FakeUnit {
+        #[zbus(property)]
+        fn active_state(&self) -> String {
+            self.active_state.clone()
+        }
+
+        #[zbus(property)]
+        fn sub_state(&self) -> String {
+            if self.active_state == "active" {
+                "running".to_string()
+            } else {
+                "dead".to_string()
+            }
+        }
+
+        #[zbus(property)]
+        fn unit_file_state(&self) -> String {
+            "enabled".to_string()
+        }
+
+        #[zbus(property)]
+        fn description(&self) -> String {
+            "Syncthing".to_string()
+        }
+
+        #[zbus(property)]
+        fn active_enter_timestamp(&self) -> u64 {
+            1_700_000_000_000_000
+        }
+
+        #[zbus(property)]
+        fn inactive_enter_timestamp(&self) -> u64 {
+            0
+        }
+    }
+
+    struct FakeService;
+
+    #[zbus::interface(name = "org.freedesktop.systemd1.Service")]
+    impl FakeService {
+        #[zbus(property)]
+        fn result(&self) -> String {
+            "success".to_string()
+        }
+
+        #[zbus(property, name = "ExecMainPID")]
+        fn exec_main_pid(&self) -> u32 {
+            4242
+        }
+
+        #[zbus(property, name = "NRestarts")]
+        fn n_restarts(&self) -> u32 {
+            0
+        }
+
+        #[zbus(property)]
+        fn exec_main_status(&self) -> i32 {
+            0
+        }
+
+        #[zbus(property)]
+        fn exec_main_code(&self) -> i32 {
+            0
+        }
+    }
+
+    async fn serve_fake_systemd(bus: &PrivateBus, job_result: &str) -> Connection {
+        zbus::connection::Builder::address(bus.address.as_str())
+            .expect("parse bus address")
+            .name("org.freedesktop.systemd1")
+            .expect("bus name")
+            .serve_at(
+                "/org/freedesktop/systemd1",
+                FakeManager {
+                    job_result: job_result.to_string(),
+                },
+            )
+            .expect("serve manager")
+            .serve_at(
+                UNIT_PATH,
+                FakeUnit {
+                    active_state: "inactive".to_string(),
+                },
+            )
+            .expect("serve unit")
+            .serve_at(UNIT_PATH, FakeService)
+            .expect("serve service")
+            .build()
+            .await
+            .expect("start fake systemd")
+    }
+
+    #[tokio::test]
+    async fn status_is_read_from_unit_and_service_properties() {
+        let Some(bus) = PrivateBus::start() else {
+            return;
+        };
+        let _systemd = serve_fake_systemd(&bus, "done").await;
+        let client = DbusSystemd::new(bus.connect().await);
+
+        let status = client
+            .status("syncthing.service")
+            .await
+            .expect("query status");
+
+        assert_eq!(status.active_state.as_deref(), Some("inactive"));
+        assert_eq!(status.sub_state.as_deref(), Some("dead"));
+        assert_eq!(status.unit_file_state.as_deref(), Some("enabled"));
+        assert_eq!(status.result.as_deref(), Some("success"));
+        assert_eq!(status.pid, Some(4242));
+        assert!(status.active_enter_timestamp.is_some());
+        assert_eq!(status.inactive_enter_timestamp, None);
+    }
+
+    #[tokio::test]
+    async fn control_waits_for_the_job_result() {
+        let Some(bus) = PrivateBus::start() else {
+            return;
+        };
+        let _systemd = serve_fake_systemd(&bus, "failed").await;
+        let client = DbusSystemd::new(bus.connect().await);
+
+        let err = client
+            .control("syncthing.service", ServiceAction::Start)
+            .await
+            .expect_err("failed job must be reported");
+
+        assert!(err.to_string().contains("result 'failed'"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn watch_reports_state_changes() {
+        let Some(bus) = PrivateBus::start() else {
+            return;
+        };
+        let systemd = serve_fake_systemd(&bus, "done").await;
+        let client = DbusSystemd::new(bus.connect().await);
+        let mut changes = client.watch("syncthing.service").await.expect("watch unit");
+
+        let unit = systemd
+            .object_server()
+            .interface::<_, FakeUnit>(UNIT_PATH)
+            .await
+            .expect("fake unit");
+        unit.get_mut().await.active_state = "active".to_string();
+        unit.get()
+            .await
+            .active_state_changed(unit.signal_emitter())
+            .await
+            .expect("emit change");
+
+        timeout(Duration::from_secs(5), changes.next())
+            .await
+            .expect("change within timeout")
+            .expect("stream open");
+        let status = client
+            .status("syncthing.service")
+            .await
+            .expect("query status");
+        assert_eq!(status.active_state.as_deref(), Some("active"));
+    }
+}
@@ -0,0 +1,222 @@
+//! Explains why the service fails or keeps restarting, from its state and the
+//! last lines of its journal.
+
+use serde::Serialize;
+use tracing::warn;
+
+use crate::utils::CommandRunner;
+
+use super::journal::{read_journal_page, JournalFilter};
+use super::types::SystemdStatus;
+
+/// Journal lines searched for a known cause.
+const DIAGNOSIS_LINES: usize = 50;
+/// Automatic restarts after which a unit still activating counts as looping.
+/// The installed unit gives up after `StartLimitBurst=4` starts.
+const CRASH_LOOP_RESTARTS: u32 = 2;
+/// systemd's exit status when it could not execute `ExecStart`.
+const EXIT_EXEC: i32 = 203;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosisKind {
+    PortInUse,
+    ConfigNotWritable,
+    DatabaseLocked,
+    WrongArchitecture,
+    /// Failing for a reason not recognised in the journal.
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct ServiceDiagnosis {
+    pub kind: DiagnosisKind,
+    /// Whether systemd restarts the service over and over or gave up on it.
+    pub crash_loop: bool,
+    pub summary: String,
+    pub suggestion: String,
+    /// The journal line the cause was recognised from.
+    pub evidence: Option<String>,
+}
+
+impl SystemdStatus {
+    pub fn start_limit_hit(&self) -> bool {
+        self.result.as_deref() == Some("start-limit-hit")
+    }
+
+    pub fn crash_looping(&self) -> bool {
+        self.start_limit_hit()
+            || (self.sub_state.as_deref() == Some("auto-restart")
+                && self.restarts.unwrap_or(0) >= CRASH_LOOP_RESTARTS)
+    }
+
+    fn needs_diagnosis(&self) -> bool {
+        self.active_state.as_deref() == Some("failed") || self.crash_looping()
+    }
+}
+
+/// Diagnoses a failed or crash-looping service; `None` while it is fine.
+pub async fn diagnose(
+    runner: &dyn CommandRunner,
+    status: &SystemdStatus,
+) -> Option<ServiceDiagnosis> {
+    if !status.needs_diagnosis() {
+        return None;
+    }
+    let lines = match read_journal_page(
+        runner,
+        &status.name,
+        None,
+        &JournalFilter::default(),
+        DIAGNOSIS_LINES,
+    )
+    .await
+    {
+        Ok(page) => page
+            .entries
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect(),
+        Err(err) => {
+            warn!(error = ?err, "Failed to read the journal for a diagnosis");
+            Vec::new()
+        }
+    };
+    Some(diagnosis_from(status, &lines))
+}
+
+fn diagnosis_from(status: &SystemdStatus, lines: &[String]) -> ServiceDiagnosis {
+    let crash_loop = status.crash_looping();
+    let (kind, evidence) = match recognise_cause(lines) {
+        Some((kind, line)) => (kind, Some(line.to_string())),
+        None if status.exec_main_status == Some(EXIT_EXEC) => {
+            (DiagnosisKind::WrongArchitecture, None)
+        }
+        None => (DiagnosisKind::Unknown, None),
+    };
+
+    let (cause, suggestion) = match kind {
+        DiagnosisKind::PortInUse => (
+            "the GUI port 8384 is already in use",
+            "Stop the other program or Syncthing instance using port 8384, e.g. one started by hand, then start the service.",
+        ),
+        DiagnosisKind::ConfigNotWritable => (
+            "it cannot write its configuration directory",
+            "Check that the Syncthing directories are writable by the service user (Settings offers a repair when they are not) and that the disk is not full or read-only.",
+        ),
+        DiagnosisKind::DatabaseLocked => (
+            "its database is locked by another Syncthing process",
+            "Stop any other Syncthing using the same home directory, or restart the device, then start the service.",
+        ),
+        DiagnosisKind::WrongArchitecture => (
+            "its binary cannot run on this device",
+            "Reinstall Syncthing from the installer, which picks the build for this device.",
+        ),
+        DiagnosisKind::Unknown => (
+            "of an error not recognised here",
+            "Open the service log to see the last messages before it stopped.",
+        ),
+    };
+    let summary = if status.start_limit_hit() {
+        format!("systemd stopped restarting Syncthing after repeated failures because {cause}.")
+    } else if crash_loop {
+        format!(
+            "Syncthing keeps crashing ({} restarts) because {cause}.",
+            status.restarts.unwrap_or(0)
+        )
+    } else {
+        format!("Syncthing failed because {cause}.")
+    };
+
+    ServiceDiagnosis {
+        kind,
+        crash_loop,
+        summary,
+        suggestion: suggestion.to_string(),
+        evidence,
+    }
+}
+
+/// Finds the newest journal line with a known cause.
+fn recognise_cause(lines: &[String]) -> Option<(DiagnosisKind, &str)> {
+    lines.iter().rev().find_map(|line| {
+        let lower = line.to_lowercase();
+        let kind = if lower.contains("address already in use") {
+            DiagnosisKind::PortInUse
+        } else if lower.contains("exec format error") {
+            DiagnosisKind::WrongArchitecture
+        } else if lower.contains("database is locked")
+            || (lower.contains("lock") && lower.contains("resource temporarily unavailable"))
+        {
+            DiagnosisKind::DatabaseLocked
+        } else if lower.contains("permission denied") || lower.contains("read-only file system") {
+            DiagnosisKind::ConfigNotWritable
+        } else {
+            return None;
+        };
+        Some((kind, line.as_str()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed(result: &str) -> SystemdStatus {
+        SystemdStatus {
+            name: "syncthing.service".to_string(),
+            active_state: Some("failed".to_string()),
+            sub_state: Some("failed".to_string()),
+            result: Some(result.to_string()),
+            ..SystemdStatus::default()
+        }
+    }
+
+    #[test]
+    fn known_causes_are_recognised_from_the_newest_line() {
+        let lines = [
+            "Starting Syncthing".to_string(),
+            "open /home/root/.config/syncthing/config.xml: permission denied".to_string(),
+            "Error: listen tcp 127.0.0.1:8384: bind: address already in use".to_string(),
+        ];
+
+        let diagnosis = diagnosis_from(&failed("start-limit-hit"), &lines);
+
+        assert_eq!(diagnosis.kind, DiagnosisKind::PortInUse);
+        assert!(diagnosis.crash_loop);
+        assert!(diagnosis.summary.starts_with("systemd stopped restarting"));
+        assert_eq!(diagnosis.evidence.as_deref(), Some(lines[2].as_str()));
+    }
+
+    #[test]
+    fn exec_failure_without_a_log_line_points_at_the_binary() {
+        let status = SystemdStatus {
+            exec_main_status: Some(EXIT_EXEC),
+            ..failed("exit-code")
+        };
+
+        let diagnosis = diagnosis_from(&status, &[]);
+
+        assert_eq!(diagnosis.kind, DiagnosisKind::WrongArchitecture);
+        assert!(!diagnosis.crash_loop);
+        assert_eq!(
+            diagnosis.summary,
+            "Syncthing failed because its binary cannot run on this device."
+        );
+    }
+
+    #[test]
+    fn restarting_unit_is_a_crash_loop_after_repeated_restarts() {
+        let mut status = SystemdStatus {
+            active_state: Some("activating".to_string()),
+            sub_state: Some("auto-restart".to_string()),
+            restarts: Some(1),
+            ..SystemdStatus::default()
+        };
+        assert!(!status.needs_diagnosis());
+
+        status.restarts = Some(3);
+        assert!(status.crash_looping());
+        assert!(status.needs_diagnosis());
+    }
+}
@@ -0,0 +1,306 @@
+//! Reads the service's journal with `journalctl -o json`, newest first, in
+//! pages addressed by journal cursors.
+
+use std::process::Stdio;
+
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+use crate::types::MonitorError;
+use crate::utils::CommandRunner;
+
+pub const JOURNAL_PAGE_SIZE: usize = 100;
+/// Entries read per `journalctl` call while looking for search matches.
+const SCAN_CHUNK: usize = 500;
+/// A search stops after this many entries per page, so a rare term cannot
+/// make one request read the whole journal.
+const MAX_SCANNED: usize = 5000;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct JournalFilter {
+    /// Highest syslog priority to include, 0 (emerg) through 7 (debug).
+    #[serde(default)]
+    pub priority: Option<u8>,
+    /// Case-insensitive text the message must contain.
+    #[serde(default)]
+    pub search: Option<String>,
+}
+
+impl JournalFilter {
+    fn journalctl_args(&self) -> Vec<String> {
+        self.priority
+            .map(|priority| vec![format!("--priority={}", priority.min(7))])
+            .unwrap_or_default()
+    }
+
+    fn search(&self) -> Option<&str> {
+        self.search
+            .as_deref()
+            .map(str::trim)
+            .filter(|search| !search.is_empty())
+    }
+
+    fn matches(&self, entry: &JournalEntry) -> bool {
+        match self.search() {
+            Some(search) => entry
+                .message
+                .to_lowercase()
+                .contains(&search.to_lowercase()),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub cursor: String,
+    pub timestamp: Option<String>,
+    pub priority: Option<u8>,
+    pub pid: Option<u32>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JournalPage {
+    /// The cursor the page was read before; `None` for the newest entries.
+    pub before: Option<String>,
+    /// Matching entries, oldest first.
+    pub entries: Vec<JournalEntry>,
+    /// Pass as `before` to read the next older page.
+    pub older_cursor: Option<String>,
+    pub has_older: bool,
+    /// The newest entry on the first page, where following starts.
+    pub newest_cursor: Option<String>,
+}
+
+/// Reads up to `limit` matching entries older than `before`, or the newest
+/// ones without a cursor.
+pub async fn read_journal_page(
+    runner: &dyn CommandRunner,
+    service_name: &str,
+    before: Option<&str>,
+    filter: &JournalFilter,
+    limit: usize,
+) -> Result<JournalPage, MonitorError> {
+    let mut entries = Vec::new();
+    let mut cursor = before.map(str::to_string);
+    let mut newest_cursor = None;
+    let mut scanned = 0;
+    let mut has_older = true;
+    // Without a search every entry matches, so `limit` lines fill the page.
+    let chunk_size = match filter.search() {
+        Some(_) => SCAN_CHUNK,
+        None => limit.min(SCAN_CHUNK),
+    };
+
+    while entries.len() < limit && scanned < MAX_SCANNED {
+        let mut args = vec![
+            "-u".to_string(),
+            service_name.to_string(),
+            "--output=json".to_string(),
+            "--no-pager".to_string(),
+            // With --reverse, --after-cursor continues towards older entries.
+            "--reverse".to_string(),
+            format!("--lines={chunk_size}"),
+        ];
+        args.extend(filter.journalctl_args());
+        if let Some(cursor) = &cursor {
+            args.push(format!("--after-cursor={cursor}"));
+        }
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = runner.output("journalctl", &args).await?;
+        if !output.status.success() {
+            return Err(MonitorError::Systemd(format!(
+                "journalctl failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let last_chunk = stdout.lines().count() < chunk_size;
+        let chunk: Vec<JournalEntry> = stdout.lines().filter_map(parse_entry).collect();
+        if before.is_none() && newest_cursor.is_none() {
+            newest_cursor = chunk.first().map(|entry| entry.cursor.clone());
+        }
+        let mut unread = chunk.len();
+        for entry in chunk {
+            unread -= 1;
+            scanned += 1;
+            cursor = Some(entry.cursor.clone());
+            if filter.matches(&entry) {
+                entries.push(entry);
+                if entries.len() == limit {
+                    break;
+                }
+            }
+        }
+        if last_chunk {
+            has_older = unread > 0;
+            break;
+        }
+    }
+
+    entries.reverse();
+    Ok(JournalPage {
+        before: before.map(str::to_string),
+        entries,
+        older_cursor: cursor,
+        has_older,
+        newest_cursor,
+    })
+}
+
+/// Runs `journalctl --follow` and hands each new matching entry to `on_entry`
+/// until the process exits. It keeps running, so unlike the paged reads it is
+/// spawned directly rather than through a `CommandRunner`.
+pub async fn follow_journal<F>(
+    service_name: &str,
+    after: Option<&str>,
+    filter: &JournalFilter,
+    mut on_entry: F,
+) -> Result<(), MonitorError>
+where
+    F: FnMut(JournalEntry),
+{
+    let mut command = Command::new("journalctl");
+    command
+        .args([
+            "-u",
+            service_name,
+            "--output=json",
+            "--no-pager",
+            "--follow",
+        ])
+        .args(filter.journalctl_args());
+    match after {
+        Some(cursor) => command.arg(format!("--after-cursor={cursor}")),
+        None => command.arg("--lines=0"),
+    };
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| MonitorError::Systemd("journalctl has no output".to_string()))?;
+
+    let mut lines = BufReader::new(stdout).lines();
+    while let Some(line) = lines.next_line().await? {
+        if let Some(entry) = parse_entry(&line) {
+            if filter.matches(&entry) {
+                on_entry(entry);
+            }
+        }
+    }
+    child.wait().await?;
+    Ok(())
+}
+
+fn parse_entry(line: &str) -> Option<JournalEntry> {
+    let value: Value = serde_json::from_str(line).ok()?;
+    let field = |name: &str| value.get(name).and_then(Value::as_str);
+    Some(JournalEntry {
+        cursor: field("__CURSOR")?.to_string(),
+        timestamp: field("__REALTIME_TIMESTAMP")
+            .and_then(|micros| micros.parse::<i64>().ok())
+            .and_then(|micros| Local.timestamp_micros(micros).single())
+            .map(|time| time.format("%b %d %H:%M:%S").to_string()),
+        priority: field("PRIORITY").and_then(|priority| priority.parse().ok()),
+        pid: field("_PID").and_then(|pid| pid.parse().ok()),
+        message: message_text(value.get("MESSAGE")?),
+    })
+}
+
+/// journalctl writes messages that are not valid UTF-8 as byte arrays.
+fn message_text(message: &Value) -> String {
+    match message {
+        Value::String(text) => text.clone(),
+        Value::Array(bytes) => {
+            let bytes: Vec<u8> = bytes
+                .iter()
+                .filter_map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+                .collect();
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::command::fake::{success, ScriptedRunner};
+
+    const READ_NEWEST: &str = "journalctl -u syncthing.service --output=json --no-pager \
+        --reverse --lines=2 --priority=4";
+
+    fn line(cursor: &str, message: &str) -> String {
+        format!(
+            "{{\"__CURSOR\":\"{cursor}\",\"__REALTIME_TIMESTAMP\":\"1700000000000000\",\
+             \"PRIORITY\":\"4\",\"_PID\":\"812\",\"MESSAGE\":\"{message}\"}}\n"
+        )
+    }
+
+    #[tokio::test]
+    async fn pages_walk_back_through_the_journal() {
+        let newest = [line("c3", "Ready"), line("c2", "Loading")].concat();
+        let runner = ScriptedRunner::new()
+            .expect(READ_NEWEST, success(&newest))
+            .expect(
+                &format!("{READ_NEWEST} --after-cursor=c2"),
+                success(&line("c1", "Starting")),
+            );
+        let filter = JournalFilter {
+            priority: Some(4),
+            search: None,
+        };
+
+        let page = read_journal_page(&runner, "syncthing.service", None, &filter, 2)
+            .await
+            .expect("read newest page");
+        let messages: Vec<&str> = page.entries.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["Loading", "Ready"]);
+        assert_eq!(page.newest_cursor.as_deref(), Some("c3"));
+        assert!(page.has_older);
+        assert_eq!(page.entries[0].pid, Some(812));
+
+        let older = read_journal_page(
+            &runner,
+            "syncthing.service",
+            page.older_cursor.as_deref(),
+            &filter,
+            2,
+        )
+        .await
+        .expect("read older page");
+        assert_eq!(older.entries.len(), 1);
+        assert_eq!(older.entries[0].message, "Starting");
+        assert!(!older.has_older);
+        assert_eq!(older.newest_cursor, None);
+    }
+
+    #[test]
+    fn search_is_case_insensitive_and_reads_binary_messages() {
+        let entry = parse_entry(
+            r#"{"__CURSOR":"c1","MESSAGE":[70,65,73,76,69,68,32,116,111,32,115,116,97,114,116]}"#,
+        )
+        .expect("parse entry");
+        assert_eq!(entry.message, "FAILED to start");
+
+        let filter = JournalFilter {
+            priority: None,
+            search: Some(" failed ".to_string()),
+        };
+        assert!(filter.matches(&entry));
+        assert!(!JournalFilter {
+            search: Some("database".to_string()),
+            ..filter
+        }
+        .matches(&entry));
+    }
+}
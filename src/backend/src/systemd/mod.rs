@@ -1,9 +1,14 @@
 mod actions;
 mod client;
+mod dbus;
+mod diagnosis;
+mod journal;
 mod monitor;
 mod types;
 
 pub use actions::ServiceAction;
 pub use client::{control_service, query_status};
+pub use diagnosis::diagnose;
+pub use journal::{follow_journal, read_journal_page, JournalFilter, JOURNAL_PAGE_SIZE};
 pub use monitor::monitor_service;
 pub use types::SystemdStatus;
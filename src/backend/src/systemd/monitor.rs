@@ -1,8 +1,12 @@
+use futures_util::StreamExt;
 use tokio::time::Duration;
+use tracing::warn;
 
 use super::client::query_status;
+use super::dbus::DbusSystemd;
 use super::types::SystemdStatus;
 use crate::config::Config;
+use crate::utils::{CommandRunner, SharedRunner};
 
 /// Check if systemd state has changed between two status snapshots
 pub fn state_changed(previous: &SystemdStatus, current: &SystemdStatus) -> bool {
@@ -11,29 +15,61 @@ pub fn state_changed(previous: &SystemdStatus, current: &SystemdStatus) -> bool
         || previous.result != current.result
         || previous.unit_file_state != current.unit_file_state
         || previous.pid != current.pid
+        || previous.restarts != current.restarts
 }
 
 /// Monitor a systemd service continuously
-/// This function runs continuously and polls systemd status at regular intervals
-pub async fn monitor_service<F>(config: Config, interval_secs: u64, mut on_change: F)
-where
+/// This function follows the unit's D-Bus signals and falls back to polling
+/// systemd status at regular intervals when the system bus is unavailable
+pub async fn monitor_service<F>(
+    config: Config,
+    runner: SharedRunner,
+    interval_secs: u64,
+    mut on_change: F,
+) where
     F: FnMut() + Send + 'static,
 {
-    let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
     let mut last_status: Option<SystemdStatus> = None;
 
+    if let Some(dbus) = DbusSystemd::system().await {
+        match dbus.watch(&config.systemd_service_name).await {
+            Ok(mut changes) => {
+                check_status(&config, runner.as_ref(), &mut last_status, &mut on_change).await;
+                while changes.next().await.is_some() {
+                    check_status(&config, runner.as_ref(), &mut last_status, &mut on_change).await;
+                }
+                warn!("systemd signal stream ended, polling instead");
+            }
+            Err(err) => warn!(error = ?err, "Failed to watch unit over D-Bus, polling instead"),
+        }
+    }
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
     loop {
         ticker.tick().await;
-        let status = query_status(&config).await;
-        let changed = match &last_status {
-            None => true,
-            Some(previous) => state_changed(previous, &status),
-        };
-
-        if changed {
-            on_change();
-        }
+        check_status(&config, runner.as_ref(), &mut last_status, &mut on_change).await;
+    }
+}
 
-        last_status = Some(status);
+/// Queries the status and calls `on_change` when it differs from the last one;
+/// several properties usually change together.
+async fn check_status<F>(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    last_status: &mut Option<SystemdStatus>,
+    on_change: &mut F,
+) where
+    F: FnMut(),
+{
+    let status = query_status(config, runner).await;
+    let changed = match last_status {
+        None => true,
+        Some(previous) => state_changed(previous, &status),
+    };
+
+    if changed {
+        on_change();
     }
+
+    *last_status = Some(status);
 }
@@ -1,5 +1,7 @@
 use serde::Serialize;
 
+use super::diagnosis::ServiceDiagnosis;
+
 #[derive(Debug, Serialize, Default, Clone, PartialEq, Eq)]
 pub struct SystemdStatus {
     pub name: String,
@@ -8,9 +10,16 @@ pub struct SystemdStatus {
     pub unit_file_state: Option<String>,
     pub result: Option<String>,
     pub pid: Option<u32>,
+    /// Automatic restarts since the unit was last started by hand.
+    pub restarts: Option<u32>,
+    /// Exit code or signal of the main process, as told apart by `exec_main_code`.
+    pub exec_main_status: Option<i32>,
+    /// `CLD_EXITED` (1), `CLD_KILLED` (2) or `CLD_DUMPED` (3).
+    pub exec_main_code: Option<i32>,
     pub active_enter_timestamp: Option<String>,
     pub inactive_enter_timestamp: Option<String>,
     pub description: Option<String>,
     pub raw_excerpt: Option<String>,
     pub error: Option<String>,
+    pub diagnosis: Option<ServiceDiagnosis>,
 }
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Local};
 use serde::Serialize;
 use thiserror::Error;
 
@@ -21,6 +22,43 @@ pub enum MonitorError {
     MissingApiKey,
     #[error("config error: {0}")]
     Config(String),
+    #[error("verification failed: {0}")]
+    Verification(String),
+    #[error("operation cancelled")]
+    Cancelled,
+    #[error("{0}")]
+    RateLimited(RateLimit),
+}
+
+/// A release server refused further requests for now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub reset_at: Option<DateTime<Local>>,
+}
+
+impl RateLimit {
+    /// Local wall-clock time at which requests are accepted again, as `HH:MM`.
+    pub fn until_label(&self) -> Option<String> {
+        self.reset_at.map(|reset| reset.format("%H:%M").to_string())
+    }
+}
+
+impl std::fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.until_label() {
+            Some(until) => write!(f, "rate limited until {}", until),
+            None => f.write_str("rate limited, try again later"),
+        }
+    }
+}
+
+impl MonitorError {
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        match self {
+            MonitorError::RateLimited(limit) => Some(*limit),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
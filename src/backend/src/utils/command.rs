@@ -0,0 +1,169 @@
+//! Runs external programs behind a trait, so flows that shell out to `mount`,
+//! `systemctl`, `useradd` or `syncthing` can be tested with a scripted fake.
+
+use std::io;
+use std::process::{Output, Stdio};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+#[async_trait]
+pub trait CommandRunner: Send + Sync {
+    /// Runs `program` with `args` and collects its output, like
+    /// `Command::output`.
+    async fn output(&self, program: &str, args: &[&str]) -> io::Result<Output>;
+
+    /// Like [`CommandRunner::output`], with `input` written to stdin, e.g. a
+    /// secret that must not appear in `/proc/<pid>/cmdline`.
+    async fn output_with_input(
+        &self,
+        program: &str,
+        args: &[&str],
+        input: &[u8],
+    ) -> io::Result<Output>;
+}
+
+/// Runs commands on the device.
+pub struct SystemRunner;
+
+#[async_trait]
+impl CommandRunner for SystemRunner {
+    async fn output(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+        Command::new(program).args(args).output().await
+    }
+
+    async fn output_with_input(
+        &self,
+        program: &str,
+        args: &[&str],
+        input: &[u8],
+    ) -> io::Result<Output> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input).await?;
+            // Dropping stdin closes it, ending the input.
+        }
+        child.wait_with_output().await
+    }
+}
+
+pub type SharedRunner = Arc<dyn CommandRunner>;
+
+pub fn system_runner() -> SharedRunner {
+    Arc::new(SystemRunner)
+}
+
+#[cfg(test)]
+pub mod fake {
+    use std::collections::VecDeque;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Replays scripted results in order and fails the test on any command it
+    /// does not expect.
+    #[derive(Default)]
+    pub struct ScriptedRunner {
+        script: Mutex<VecDeque<(String, io::Result<Output>)>>,
+        inputs: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl ScriptedRunner {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Expects `command` (program and arguments separated by spaces) next.
+        pub fn expect(self, command: &str, result: io::Result<Output>) -> Self {
+            self.script
+                .lock()
+                .expect("script lock")
+                .push_back((command.to_string(), result));
+            self
+        }
+
+        pub fn shared(self) -> SharedRunner {
+            Arc::new(self)
+        }
+
+        /// Everything written to stdin so far, one entry per command.
+        pub fn inputs(&self) -> Vec<Vec<u8>> {
+            self.inputs.lock().expect("inputs lock").clone()
+        }
+
+        pub fn assert_done(&self) {
+            let script = self.script.lock().expect("script lock");
+            let remaining: Vec<&String> = script.iter().map(|(command, _)| command).collect();
+            assert!(remaining.is_empty(), "commands never run: {remaining:?}");
+        }
+    }
+
+    impl Drop for ScriptedRunner {
+        fn drop(&mut self) {
+            if !std::thread::panicking() {
+                self.assert_done();
+            }
+        }
+    }
+
+    #[async_trait]
+    impl CommandRunner for ScriptedRunner {
+        async fn output(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+            let command = std::iter::once(program)
+                .chain(args.iter().copied())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let next = self.script.lock().expect("script lock").pop_front();
+            match next {
+                Some((expected, result)) => {
+                    assert_eq!(command, expected, "unexpected command");
+                    result
+                }
+                None => panic!("unexpected command after the script ended: {command}"),
+            }
+        }
+
+        async fn output_with_input(
+            &self,
+            program: &str,
+            args: &[&str],
+            input: &[u8],
+        ) -> io::Result<Output> {
+            self.inputs
+                .lock()
+                .expect("inputs lock")
+                .push(input.to_vec());
+            self.output(program, args).await
+        }
+    }
+
+    pub fn success(stdout: &str) -> io::Result<Output> {
+        Ok(Output {
+            status: ExitStatus::from_raw(0),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: Vec::new(),
+        })
+    }
+
+    pub fn failure(code: i32, stderr: &str) -> io::Result<Output> {
+        Ok(Output {
+            // Wait statuses keep the exit code in the second byte.
+            status: ExitStatus::from_raw(code << 8),
+            stdout: Vec::new(),
+            stderr: stderr.as_bytes().to_vec(),
+        })
+    }
+
+    pub fn not_found() -> io::Result<Output> {
+        Err(io::Error::from(io::ErrorKind::NotFound))
+    }
+}
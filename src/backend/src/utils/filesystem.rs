@@ -1,140 +1,493 @@
-use tokio::process::Command;
-use tracing::info;
+//! Write access to the root filesystem, which reMarkable mounts read-only with
+//! a volatile overlay on `/etc`.
+//!
+//! Every operation that writes below `/` holds a [`RootWriteGuard`]. The first
+//! guard makes root writable and takes the overlay off `/etc`, so units land
+//! on the real filesystem; the last one to go puts both back exactly as they
+//! were found. Overlapping operations therefore never see root turn read-only
+//! under them.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
 
 use crate::types::MonitorError;
 
-/// Check if root filesystem is mounted read-only
-async fn is_root_readonly() -> Result<bool, MonitorError> {
-    let output = Command::new("mount").output().await?;
-
-    if !output.status.success() {
-        return Err(MonitorError::Io(std::io::Error::other(
-            "Failed to query mount status",
-        )));
-    }
-
-    let mount_output = String::from_utf8_lossy(&output.stdout);
-    // Look for root mount and check if it has 'ro' option
-    for line in mount_output.lines() {
-        if line.contains(" on / ") || line.starts_with("/ ") {
-            // Check if mount options contain 'ro'
-            if let Some(opts_start) = line.rfind('(') {
-                if let Some(opts_end) = line.rfind(')') {
-                    let options = &line[opts_start + 1..opts_end];
-                    // Check for 'ro' as a standalone option (not part of another word)
-                    for opt in options.split(',') {
-                        if opt.trim() == "ro" {
-                            return Ok(true);
-                        }
-                    }
+use super::command::SharedRunner;
+
+const MOUNTINFO_PATH: &str = "/proc/self/mountinfo";
+/// Records what a guard changed until it is restored. `/run` is cleared on
+/// boot, which also resets the mounts, so a marker left there means the
+/// backend exited while holding a guard.
+const MARKER_PATH: &str = "/run/syncthing-appload-remount.json";
+
+/// The mounts a guard changed, recorded so they can be put back.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct FoundMounts {
+    root_read_only: bool,
+    etc_overlay: Option<OverlayMount>,
+}
+
+impl FoundMounts {
+    fn changes_anything(&self) -> bool {
+        self.root_read_only || self.etc_overlay.is_some()
+    }
+}
+
+/// The overlay on `/etc` as listed in mountinfo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct OverlayMount {
+    source: String,
+    /// Superblock options, including `lowerdir`, `upperdir` and `workdir`.
+    options: String,
+}
+
+#[derive(Default)]
+struct GuardState {
+    holders: usize,
+    found: Option<FoundMounts>,
+}
+
+/// Shared by everything that remounts root, so the guards count together.
+pub struct RootMounts {
+    runner: SharedRunner,
+    mountinfo_path: PathBuf,
+    marker_path: PathBuf,
+    state: Mutex<GuardState>,
+}
+
+/// Keeps root writable while alive. Prefer [`RootWriteGuard::release`], which
+/// reports restore errors; dropping the guard restores in the background.
+#[must_use = "root becomes read-only again when the guard is released"]
+pub struct RootWriteGuard {
+    mounts: Option<Arc<RootMounts>>,
+}
+
+impl RootMounts {
+    pub fn new(runner: SharedRunner) -> Arc<Self> {
+        Self::with_paths(
+            runner,
+            PathBuf::from(MOUNTINFO_PATH),
+            PathBuf::from(MARKER_PATH),
+        )
+    }
+
+    fn with_paths(
+        runner: SharedRunner,
+        mountinfo_path: PathBuf,
+        marker_path: PathBuf,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            runner,
+            mountinfo_path,
+            marker_path,
+            state: Mutex::new(GuardState::default()),
+        })
+    }
+
+    /// Makes root writable, with `/etc` off its overlay, until the returned
+    /// guard and every other one are released.
+    pub async fn writable(self: &Arc<Self>) -> Result<RootWriteGuard, MonitorError> {
+        let mut state = self.state.lock().await;
+        if state.holders == 0 {
+            state.found = Some(self.make_writable().await?);
+        }
+        state.holders += 1;
+        Ok(RootWriteGuard {
+            mounts: Some(Arc::clone(self)),
+        })
+    }
+
+    /// Puts back mounts a previous run changed and did not restore, e.g. a
+    /// root left writable when the backend was killed mid-install.
+    pub async fn recover_after_crash(&self) {
+        let Ok(marker) = fs::read_to_string(&self.marker_path).await else {
+            return;
+        };
+        let state = self.state.lock().await;
+        if state.holders > 0 {
+            return;
+        }
+        let result = async {
+            let found: FoundMounts = serde_json::from_str(&marker)?;
+            let current = self.read_mount_table().await?;
+            // Only what is still different; the device may have been fixed by hand.
+            let pending = FoundMounts {
+                root_read_only: found.root_read_only && !root_read_only(&current)?,
+                etc_overlay: found
+                    .etc_overlay
+                    .filter(|_| etc_overlay(&current).is_none()),
+            };
+            if pending.changes_anything() {
+                warn!(?pending, "Restoring mounts left changed by a previous run");
+            }
+            self.restore(&pending).await
+        }
+        .await;
+        match result {
+            Ok(()) => self.remove_marker().await,
+            Err(err) => error!(error = ?err, "Failed to restore mounts after a crash"),
+        }
+    }
+
+    async fn release(&self) -> Result<(), MonitorError> {
+        let mut state = self.state.lock().await;
+        state.holders = state.holders.saturating_sub(1);
+        if state.holders > 0 {
+            return Ok(());
+        }
+        let Some(found) = state.found.take() else {
+            return Ok(());
+        };
+        self.restore(&found).await?;
+        // Kept after a failed restore, so the next start tries again.
+        self.remove_marker().await;
+        Ok(())
+    }
+
+    async fn make_writable(&self) -> Result<FoundMounts, MonitorError> {
+        let table = self.read_mount_table().await?;
+        let mut found = FoundMounts {
+            root_read_only: root_read_only(&table)?,
+            etc_overlay: etc_overlay(&table),
+        };
+        if !found.changes_anything() {
+            return Ok(found);
+        }
+        // Written first, so a crash during the remount is recovered as well.
+        self.write_marker(&found).await?;
+
+        if found.root_read_only {
+            if let Err(err) = self
+                .mount(&["-o", "remount,rw", "/"], "remount / as rw")
+                .await
+            {
+                self.remove_marker().await;
+                return Err(err);
+            }
+        }
+        if found.etc_overlay.is_some() {
+            match self
+                .mount_command("umount", &["-R", "/etc"], "unmount /etc")
+                .await
+            {
+                Ok(()) => {}
+                Err(err) => {
+                    // Writes then go to the volatile overlay, as before this guard existed.
+                    warn!(error = ?err, "Continuing with the /etc overlay mounted");
+                    found.etc_overlay = None;
+                    self.write_marker(&found).await?;
                 }
             }
         }
+        Ok(found)
     }
-    Ok(false)
-}
 
-/// Remount root filesystem as read-write, returns whether it was read-only before
-pub async fn remount_root_rw() -> Result<bool, MonitorError> {
-    let was_readonly = is_root_readonly().await?;
+    async fn restore(&self, found: &FoundMounts) -> Result<(), MonitorError> {
+        let root_result = if found.root_read_only {
+            self.mount(&["-o", "remount,ro", "/"], "remount / as ro")
+                .await
+        } else {
+            Ok(())
+        };
+        let overlay_result = match &found.etc_overlay {
+            Some(overlay) => {
+                let result = self
+                    .mount(
+                        &[
+                            "-t",
+                            "overlay",
+                            &overlay.source,
+                            "-o",
+                            &overlay.options,
+                            "/etc",
+                        ],
+                        "remount /etc overlay",
+                    )
+                    .await;
+                match result {
+                    Err(MonitorError::Io(err))
+                        if err.to_string().contains("busy")
+                            || err.to_string().contains("already mounted") =>
+                    {
+                        info!("/etc overlay already mounted, skipping remount");
+                        Ok(())
+                    }
+                    other => other,
+                }
+            }
+            None => Ok(()),
+        };
+        root_result.and(overlay_result)
+    }
 
-    if was_readonly {
-        let output = Command::new("mount")
-            .args(["-o", "remount,rw", "/"])
-            .output()
-            .await?;
+    async fn mount(&self, args: &[&str], what: &str) -> Result<(), MonitorError> {
+        self.mount_command("mount", args, what).await
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-            return Err(MonitorError::Io(std::io::Error::other(format!(
-                "Failed to remount / as rw: {}",
-                stderr
-            ))));
+    async fn mount_command(
+        &self,
+        program: &str,
+        args: &[&str],
+        what: &str,
+    ) -> Result<(), MonitorError> {
+        let output = self.runner.output(program, args).await?;
+        if output.status.success() {
+            return Ok(());
         }
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(MonitorError::Io(std::io::Error::other(format!(
+            "Failed to {what}: {stderr}"
+        ))))
     }
 
-    Ok(was_readonly)
-}
+    async fn read_mount_table(&self) -> Result<Vec<MountEntry>, MonitorError> {
+        let mountinfo = fs::read_to_string(&self.mountinfo_path).await?;
+        Ok(mountinfo.lines().filter_map(parse_mountinfo_line).collect())
+    }
 
-/// Restore root filesystem to read-only and reapply /etc overlay (only if should_restore is true)
-pub async fn restore_mounts_if_needed(should_restore: bool) -> Result<(), MonitorError> {
-    if !should_restore {
-        return Ok(());
+    async fn write_marker(&self, found: &FoundMounts) -> Result<(), MonitorError> {
+        fs::write(&self.marker_path, serde_json::to_vec(found)?).await?;
+        Ok(())
     }
 
-    {
-        let output = Command::new("mount")
-            .args(["-o", "remount,ro", "/"])
-            .output()
-            .await;
+    async fn remove_marker(&self) {
+        if let Err(err) = fs::remove_file(&self.marker_path).await {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                warn!(error = ?err, "Failed to remove remount marker");
+            }
+        }
+    }
+}
+
+impl RootWriteGuard {
+    /// Releases the guard; the last one restores the mounts.
+    pub async fn release(mut self) -> Result<(), MonitorError> {
+        match self.mounts.take() {
+            Some(mounts) => mounts.release().await,
+            None => Ok(()),
+        }
+    }
+}
 
-        match output {
-            Ok(out) if out.status.success() => Ok(()),
-            Ok(out) => {
-                let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
-                Err(MonitorError::Io(std::io::Error::other(format!(
-                    "Failed to remount / as ro: {}",
-                    stderr
-                ))))
+impl Drop for RootWriteGuard {
+    fn drop(&mut self) {
+        let Some(mounts) = self.mounts.take() else {
+            return;
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    if let Err(err) = mounts.release().await {
+                        error!(error = ?err, "Failed to restore mounts");
+                    }
+                });
             }
-            Err(err) => Err(MonitorError::Io(err)),
+            Err(_) => error!("Root write guard dropped outside the runtime; mounts not restored"),
         }
-    }?;
+    }
+}
 
-    remount_etc_overlay().await
+/// One line of `/proc/self/mountinfo`.
+#[derive(Debug, PartialEq, Eq)]
+struct MountEntry {
+    mount_point: String,
+    mount_options: String,
+    fs_type: String,
+    source: String,
+    super_options: String,
 }
 
-/// Reapply the /etc overlay mount used on reMarkable devices
-async fn remount_etc_overlay() -> Result<(), MonitorError> {
-    let output = Command::new("mount")
-        .args([
-            "-t",
-            "overlay",
-            "overlay",
-            "-o",
-            "lowerdir=/etc,upperdir=/var/volatile/etc,workdir=/var/volatile/.etc-work",
-            "/etc",
-        ])
-        .output()
-        .await;
+fn root_read_only(table: &[MountEntry]) -> Result<bool, MonitorError> {
+    // The last mount on a path is the visible one.
+    let root = table
+        .iter()
+        .rev()
+        .find(|entry| entry.mount_point == "/")
+        .ok_or_else(|| MonitorError::Io(std::io::Error::other("No root mount in mountinfo")))?;
+    Ok(root.mount_options.split(',').any(|option| option == "ro"))
+}
+
+fn etc_overlay(table: &[MountEntry]) -> Option<OverlayMount> {
+    table
+        .iter()
+        .rev()
+        .find(|entry| entry.mount_point == "/etc")
+        .filter(|entry| entry.fs_type == "overlay")
+        .map(|entry| OverlayMount {
+            source: entry.source.clone(),
+            options: entry.super_options.clone(),
+        })
+}
 
-    match output {
-        Ok(out) if out.status.success() => Ok(()),
-        Ok(out) => {
-            let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
-            // If the overlay is already mounted, treat it as success
-            if stderr.contains("busy") || stderr.contains("already mounted") {
-                info!("/etc overlay already mounted, skipping remount");
-                return Ok(());
+/// Parses `id parent major:minor root mount-point options [optional...] - type
+/// source super-options`.
+fn parse_mountinfo_line(line: &str) -> Option<MountEntry> {
+    let (mount, filesystem) = line.split_once(" - ")?;
+    let mut mount = mount.split(' ');
+    let mount_point = unescape(mount.nth(4)?);
+    let mount_options = mount.next()?.to_string();
+    let mut filesystem = filesystem.split(' ');
+    Some(MountEntry {
+        mount_point,
+        mount_options,
+        fs_type: filesystem.next()?.to_string(),
+        source: unescape(filesystem.next()?),
+        super_options: filesystem.next().unwrap_or_default().to_string(),
+    })
+}
+
+/// Decodes the octal escapes (`\040` for a space) mountinfo uses in paths.
+fn unescape(field: &str) -> String {
+    let mut bytes = Vec::with_capacity(field.len());
+    let raw = field.as_bytes();
+    let mut index = 0;
+    while index < raw.len() {
+        let escaped = raw.get(index + 1..index + 4).filter(|digits| {
+            raw[index] == b'\\' && digits.iter().all(|digit| (b'0'..=b'7').contains(digit))
+        });
+        match escaped {
+            Some(digits) => {
+                let value = digits
+                    .iter()
+                    .fold(0u32, |value, digit| value * 8 + u32::from(digit - b'0'));
+                bytes.push(value as u8);
+                index += 4;
             }
-            Err(MonitorError::Io(std::io::Error::other(format!(
-                "Failed to remount /etc overlay: {}",
-                stderr
-            ))))
-        }
-        Err(err) => Err(MonitorError::Io(err)),
-    }
-}
-
-/// Unmount /etc overlay if needed (reMarkable specific)
-pub async fn unmount_etc_if_needed() -> Result<(), MonitorError> {
-    let output = Command::new("umount").args(["-R", "/etc"]).output().await;
-
-    match output {
-        Ok(out) if out.status.success() => Ok(()),
-        Ok(out) => {
-            let stderr = String::from_utf8_lossy(&out.stderr);
-            // Check if already unmounted
-            if stderr.contains("not mounted") || stderr.contains("isn't mounted") {
-                info!("/etc already unmounted, continuing");
-                Ok(())
-            } else {
-                Err(MonitorError::Io(std::io::Error::other(format!(
-                    "Failed to unmount /etc: {}",
-                    stderr
-                ))))
+            None => {
+                bytes.push(raw[index]);
+                index += 1;
             }
         }
-        Err(err) => Err(MonitorError::Io(err)),
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+pub mod fake {
+    use super::*;
+
+    /// mountinfo of a device with a read-only root and the `/etc` overlay.
+    pub const READ_ONLY_ROOT: &str = "\
+21 1 179:2 / / ro,relatime shared:1 - ext4 /dev/mmcblk2p2 ro
+27 21 0:24 / /var/volatile rw,relatime shared:9 - tmpfs tmpfs rw
+31 21 0:26 / /etc rw,relatime shared:13 - overlay overlay \
+rw,lowerdir=/etc,upperdir=/var/volatile/etc,workdir=/var/volatile/.etc-work
+";
+    pub const ETC_OVERLAY_MOUNT: &str = "mount -t overlay overlay -o \
+        rw,lowerdir=/etc,upperdir=/var/volatile/etc,workdir=/var/volatile/.etc-work /etc";
+
+    /// [`RootMounts`] reading `mountinfo` from a temporary file.
+    pub struct FakeMounts {
+        pub mounts: Arc<RootMounts>,
+        pub dir: tempfile::TempDir,
+    }
+
+    impl FakeMounts {
+        pub fn new(runner: SharedRunner, mountinfo: &str) -> Self {
+            let dir = tempfile::tempdir().expect("create mounts dir");
+            let mountinfo_path = dir.path().join("mountinfo");
+            std::fs::write(&mountinfo_path, mountinfo).expect("write mountinfo");
+            let mounts = RootMounts::with_paths(runner, mountinfo_path, dir.path().join("marker"));
+            Self { mounts, dir }
+        }
+
+        pub fn marker_present(&self) -> bool {
+            self.dir.path().join("marker").exists()
+        }
+
+        pub fn set_mountinfo(&self, mountinfo: &str) {
+            std::fs::write(self.dir.path().join("mountinfo"), mountinfo).expect("write mountinfo");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake::{FakeMounts, ETC_OVERLAY_MOUNT, READ_ONLY_ROOT};
+    use super::*;
+    use crate::utils::command::fake::{failure, success, ScriptedRunner};
+
+    #[tokio::test]
+    async fn overlapping_guards_restore_once_the_last_is_released() {
+        let runner = ScriptedRunner::new()
+            .expect("mount -o remount,rw /", success(""))
+            .expect("umount -R /etc", success(""))
+            .expect("mount -o remount,ro /", success(""))
+            .expect(ETC_OVERLAY_MOUNT, success(""))
+            .shared();
+        let fake = FakeMounts::new(runner, READ_ONLY_ROOT);
+
+        let first = fake.mounts.writable().await.expect("first guard");
+        let second = fake.mounts.writable().await.expect("second guard");
+        assert!(fake.marker_present());
+        first.release().await.expect("release first");
+        second.release().await.expect("release second");
+        assert!(!fake.marker_present());
+    }
+
+    #[tokio::test]
+    async fn writable_root_without_overlay_is_left_alone() {
+        let fake = FakeMounts::new(
+            ScriptedRunner::new().shared(),
+            "21 1 179:2 / / rw,relatime shared:1 - ext4 /dev/mmcblk2p2 rw\n",
+        );
+
+        let guard = fake.mounts.writable().await.expect("guard");
+        assert!(!fake.marker_present());
+        guard.release().await.expect("nothing to restore");
+    }
+
+    #[tokio::test]
+    async fn failed_remount_is_reported() {
+        let runner = ScriptedRunner::new()
+            .expect(
+                "mount -o remount,rw /",
+                failure(32, "mount: /: mount point is busy"),
+            )
+            .shared();
+        let fake = FakeMounts::new(runner, READ_ONLY_ROOT);
+
+        let err = fake.mounts.writable().await.err().expect("remount fails");
+        assert!(err.to_string().contains("mount point is busy"), "{}", err);
+        assert!(!fake.marker_present());
+    }
+
+    #[tokio::test]
+    async fn mounts_left_writable_by_a_crash_are_restored_on_start() {
+        let runner = ScriptedRunner::new()
+            .expect("mount -o remount,ro /", success(""))
+            .expect(ETC_OVERLAY_MOUNT, success(""))
+            .shared();
+        let fake = FakeMounts::new(runner, READ_ONLY_ROOT);
+        let found = FoundMounts {
+            root_read_only: true,
+            etc_overlay: etc_overlay(&fake.mounts.read_mount_table().await.expect("table")),
+        };
+        fake.mounts
+            .write_marker(&found)
+            .await
+            .expect("write marker");
+        fake.set_mountinfo("21 1 179:2 / / rw,relatime shared:1 - ext4 /dev/mmcblk2p2 rw\n");
+
+        fake.mounts.recover_after_crash().await;
+        assert!(!fake.marker_present());
+    }
+
+    #[test]
+    fn mountinfo_paths_are_unescaped() {
+        let entry = parse_mountinfo_line(
+            "40 21 0:30 / /home/root/My\\040Notes rw,nosuid shared:20 master:1 - vfat /dev/sda1 rw",
+        )
+        .expect("parse line");
+
+        assert_eq!(entry.mount_point, "/home/root/My Notes");
+        assert_eq!(entry.mount_options, "rw,nosuid");
+        assert_eq!(entry.fs_type, "vfat");
+        assert_eq!(entry.super_options, "rw");
     }
 }
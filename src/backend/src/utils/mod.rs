@@ -1,5 +1,7 @@
+pub mod command;
 pub mod filesystem;
 pub mod formatting;
 pub mod systemctl;
 
+pub use command::{system_runner, CommandRunner, SharedRunner};
 pub use formatting::format_bytes;
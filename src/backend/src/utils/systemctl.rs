@@ -1,13 +1,13 @@
 //! Shared utilities for executing systemctl commands
 
-use tokio::process::Command;
-
 use crate::types::MonitorError;
 
+use super::command::CommandRunner;
+
 /// Execute a systemctl command with the given arguments
 /// Returns Ok(()) on success, or an error with stderr details on failure
-pub async fn execute(args: &[&str]) -> Result<(), MonitorError> {
-    let output = Command::new("systemctl").args(args).output().await?;
+pub async fn execute(runner: &dyn CommandRunner, args: &[&str]) -> Result<(), MonitorError> {
+    let output = runner.output("systemctl", args).await?;
 
     if output.status.success() {
         return Ok(());
@@ -28,9 +28,10 @@ pub async fn execute(args: &[&str]) -> Result<(), MonitorError> {
 /// Execute a systemctl command with the given arguments
 /// Returns a success message on success, or an error with stderr details on failure
 pub async fn execute_with_message(
+    runner: &dyn CommandRunner,
     args: &[&str],
     success_message: String,
 ) -> Result<String, MonitorError> {
-    execute(args).await?;
+    execute(runner, args).await?;
     Ok(success_message)
 }
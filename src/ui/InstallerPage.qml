@@ -11,9 +11,15 @@ Item {
     property var installerStatus: null
     property bool dismissable: true
     property bool isInstalling: installerStatus && installerStatus.in_progress
+    property bool isCancellable: installerStatus && installerStatus.cancellable
+    property var availableVersions: null
+    property int selectedVersionIndex: defaultVersionIndex()
 
-    signal installRequested()
+    signal installRequested(string version)
+    signal localInstallRequested()
     signal dismissRequested()
+    signal cancelRequested()
+    signal restoreRequested()
 
     anchors.fill: parent
 
@@ -28,7 +34,13 @@ Item {
         return { binaryReady: binaryReady, serviceReady: serviceReady }
     }
 
+    function serviceLost() {
+        return !!(installerStatus && installerStatus.service_lost)
+    }
+
     function installerPrimaryText() {
+        if (serviceLost())
+            return "An OS update removed the Syncthing service."
         const state = installerState()
         if (!state.binaryReady && !state.serviceReady)
             return "Syncthing is not ready yet."
@@ -40,9 +52,11 @@ Item {
     }
 
     function installerSecondaryText() {
+        if (serviceLost())
+            return "The reMarkable update replaced the system partition. Your Syncthing binary, configuration and keys are still on the device, so the service can be restored without downloading anything."
         const state = installerState()
         if (!state.binaryReady && !state.serviceReady)
-            return "We can download the latest Syncthing release from GitHub and install a systemd service for you."
+            return "We can download a Syncthing release from GitHub and install a systemd service for you."
         if (state.binaryReady && !state.serviceReady)
             return "We detected the Syncthing binary on disk, but the systemd service was removed—perhaps by an OS update. Do you want to install the systemd service?"
         if (!state.binaryReady && state.serviceReady)
@@ -50,11 +64,81 @@ Item {
         return ""
     }
 
+    function versionList() {
+        return (availableVersions && availableVersions.versions) || []
+    }
+
+    function defaultVersionIndex() {
+        const versions = versionList()
+        const wanted = availableVersions ? availableVersions.default_version : null
+        for (let i = 0; i < versions.length; ++i) {
+            if (versions[i].version === wanted)
+                return i
+        }
+        return versions.length > 0 ? 0 : -1
+    }
+
+    function selectedVersion() {
+        const versions = versionList()
+        if (selectedVersionIndex < 0 || selectedVersionIndex >= versions.length)
+            return null
+        return versions[selectedVersionIndex]
+    }
+
+    function selectedVersionLabel() {
+        const entry = selectedVersion()
+        if (!entry)
+            return "Latest release"
+        let label = "v" + entry.version
+        if (availableVersions && entry.version === availableVersions.pinned_version)
+            label += " (pinned)"
+        else if (selectedVersionIndex === 0)
+            label += " (latest)"
+        if (entry.prerelease)
+            label += " · release candidate"
+        return label
+    }
+
+    // Only an explicit choice is sent; the default follows the pin or channel.
+    function requestedVersion() {
+        if (selectedVersionIndex === defaultVersionIndex())
+            return ""
+        const entry = selectedVersion()
+        return entry ? entry.version : ""
+    }
+
+    function localArchive() {
+        return (installerStatus && installerStatus.local_archive) || ""
+    }
+
     function progressMessage() {
         return (installerStatus && installerStatus.progress_message) || ""
     }
 
+    function checkpoints() {
+        return (installerStatus && installerStatus.checkpoints) || []
+    }
+
+    function checkpointMark(state) {
+        switch (state) {
+        case "passed":
+            return "✓"
+        case "failed":
+            return "✗"
+        case "running":
+            return "…"
+        default:
+            return "○"
+        }
+    }
+
     function errorMessage() {
+        if (installerStatus && installerStatus.rate_limited) {
+            const until = installerStatus.rate_limited_until
+            return until
+                ? "The release server is rate limiting this network. Try again after " + until + ", or set github_token in config.json."
+                : "The release server is rate limiting this network. Try again later, or set github_token in config.json."
+        }
         return (installerStatus && installerStatus.error) || ""
     }
 
@@ -284,6 +368,46 @@ Item {
                 }
             }
 
+            RowLayout {
+                width: parent.width
+                spacing: 16
+                visible: installerPage.versionList().length > 0 && !installerPage.serviceLost()
+
+                Text {
+                    text: "Version"
+                    font.pointSize: installerPage.fs(18)
+                    font.bold: true
+                    color: Theme.text
+                }
+
+                AppButton {
+                    Layout.preferredWidth: 72
+                    Layout.preferredHeight: 60
+                    text: "‹"
+                    fontScale: installerPage.fontScale
+                    enabled: !installerPage.isInstalling && installerPage.selectedVersionIndex < installerPage.versionList().length - 1
+                    onClicked: installerPage.selectedVersionIndex += 1
+                }
+
+                Text {
+                    Layout.fillWidth: true
+                    text: installerPage.selectedVersionLabel()
+                    font.pointSize: installerPage.fs(18)
+                    color: Theme.text
+                    horizontalAlignment: Text.AlignHCenter
+                    elide: Text.ElideRight
+                }
+
+                AppButton {
+                    Layout.preferredWidth: 72
+                    Layout.preferredHeight: 60
+                    text: "›"
+                    fontScale: installerPage.fontScale
+                    enabled: !installerPage.isInstalling && installerPage.selectedVersionIndex > 0
+                    onClicked: installerPage.selectedVersionIndex -= 1
+                }
+            }
+
             Row {
                 width: parent.width
                 spacing: 20
@@ -292,26 +416,66 @@ Item {
                 AppButton {
                     width: Math.max(220, Math.min(card.width * 0.45, 420))
                     height: 72
-                    text: installerPage.isInstalling ? "Installing..." : "Install now"
+                    text: installerPage.isInstalling
+                        ? (installerPage.serviceLost() ? "Restoring..." : "Installing...")
+                        : (installerPage.serviceLost() ? "Restore service" : "Install now")
                     fontScale: installerPage.fontScale
                     fillColor: Theme.accent
                     pressedColor: Theme.accentPressed
                     disabledFillColor: Theme.mutedBg
                     buttonRadius: 10
                     enabled: !installerPage.isInstalling
-                    onClicked: installerPage.installRequested()
+                    onClicked: {
+                        if (installerPage.serviceLost())
+                            installerPage.restoreRequested()
+                        else
+                            installerPage.installRequested(installerPage.requestedVersion())
+                    }
                 }
 
                 AppButton {
                     width: Math.max(220, Math.min(card.width * 0.45, 420))
                     height: 72
-                    text: "Not now"
+                    text: installerPage.isCancellable ? "Cancel download" : "Not now"
                     fontScale: installerPage.fontScale
                     fillColor: Theme.errorBorder
                     pressedColor: Theme.errorPressed
                     buttonRadius: 10
-                    visible: installerPage.dismissable
-                    onClicked: installerPage.dismissRequested()
+                    visible: installerPage.dismissable || installerPage.isCancellable
+                    onClicked: {
+                        if (installerPage.isCancellable)
+                            installerPage.cancelRequested()
+                        else
+                            installerPage.dismissRequested()
+                    }
+                }
+            }
+
+            Column {
+                width: parent.width
+                spacing: 12
+                visible: installerPage.localArchive().length > 0
+
+                Text {
+                    text: "Found " + installerPage.localArchive() + " in the sideload folder. It can be installed without internet access."
+                    font.pointSize: installerPage.fs(16)
+                    color: Theme.textMuted
+                    wrapMode: Text.WordWrap
+                    width: parent.width
+                }
+
+                AppButton {
+                    width: Math.max(220, Math.min(card.width * 0.45, 420))
+                    height: 72
+                    text: "Install from local file"
+                    fontScale: installerPage.fontScale
+                    fillColor: Theme.accentSoft
+                    pressedColor: Theme.surfacePressed
+                    textColor: Theme.accent
+                    outlineColor: Theme.accent
+                    buttonRadius: 10
+                    enabled: !installerPage.isInstalling
+                    onClicked: installerPage.localInstallRequested()
                 }
             }
         }
@@ -319,7 +483,7 @@ Item {
 
     Rectangle {
         id: progressBox
-        visible: installerPage.progressMessage().length > 0
+        visible: installerPage.progressMessage().length > 0 || installerPage.checkpoints().length > 0
         width: Math.min(parent.width - 32, 1024)
         anchors.horizontalCenter: parent.horizontalCenter
         anchors.top: card.bottom
@@ -328,18 +492,38 @@ Item {
         color: Theme.accentSoft
         border.color: Theme.borderSoft
         border.width: 1
-        height: progressText.implicitHeight + 36
+        height: progressColumn.implicitHeight + 36
 
-        Text {
-            id: progressText
+        Column {
+            id: progressColumn
             anchors.left: parent.left
             anchors.right: parent.right
             anchors.top: parent.top
             anchors.margins: 18
-            text: installerPage.progressMessage()
-            font.pointSize: installerPage.fs(16)
-            color: Theme.text
-            wrapMode: Text.WordWrap
+            spacing: 8
+
+            Text {
+                id: progressText
+                width: parent.width
+                visible: text.length > 0
+                text: installerPage.progressMessage()
+                font.pointSize: installerPage.fs(16)
+                color: Theme.text
+                wrapMode: Text.WordWrap
+            }
+
+            Repeater {
+                model: installerPage.checkpoints()
+
+                Text {
+                    width: progressColumn.width
+                    text: installerPage.checkpointMark(modelData.state) + "  " + modelData.label
+                          + (modelData.detail ? " (" + modelData.detail + ")" : "")
+                    font.pointSize: installerPage.fs(14)
+                    color: modelData.state === "pending" ? Theme.textMuted : Theme.text
+                    wrapMode: Text.WordWrap
+                }
+            }
         }
     }
 
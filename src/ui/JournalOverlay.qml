@@ -0,0 +1,317 @@
+pragma ComponentBehavior: Bound
+
+import QtQuick
+import QtQuick.Controls
+import QtQuick.Layouts
+import "Theme.js" as Theme
+
+Rectangle {
+    id: overlay
+    anchors.fill: parent
+    color: visible ? Theme.overlay : "transparent"
+    visible: false
+    z: 1000
+
+    property real fontScale: 1.0
+    property color accentColor: Theme.accent
+    property var entries: []
+    property var olderCursor: null
+    property var newestCursor: null
+    property bool hasOlder: false
+    property bool loading: false
+    property bool following: false
+    // Highest syslog priority shown; -1 shows everything.
+    property int priority: -1
+    property string search: ""
+    // Cursor of the page in flight, so a page for an older filter is dropped.
+    property var pendingBefore: null
+    readonly property int maxEntries: 2000
+
+    signal pageRequested(var request)
+    signal followRequested(var request)
+
+    function fs(value) {
+        return value * fontScale
+    }
+
+    function show() {
+        visible = true
+        reload()
+    }
+
+    function hide() {
+        if (following)
+            followRequested({ enabled: false })
+        following = false
+        visible = false
+    }
+
+    function filter() {
+        const request = {}
+        if (priority >= 0)
+            request.priority = priority
+        if (search.trim().length > 0)
+            request.search = search.trim()
+        return request
+    }
+
+    function reload() {
+        entries = []
+        olderCursor = null
+        newestCursor = null
+        hasOlder = false
+        loading = true
+        pendingBefore = null
+        pageRequested(filter())
+    }
+
+    function loadOlder() {
+        if (loading || !hasOlder)
+            return
+        loading = true
+        pendingBefore = olderCursor
+        const request = filter()
+        request.before = olderCursor
+        pageRequested(request)
+    }
+
+    function setFollowing(enabled) {
+        following = enabled
+        if (!enabled) {
+            followRequested({ enabled: false })
+            return
+        }
+        const request = filter()
+        request.enabled = true
+        request.after = newestCursor
+        followRequested(request)
+    }
+
+    function applyFilter(newPriority, newSearch) {
+        priority = newPriority
+        search = newSearch
+        if (following)
+            followRequested({ enabled: false })
+        reload()
+    }
+
+    function applyPage(page) {
+        if (!visible || (page.before || null) !== pendingBefore)
+            return
+        loading = false
+        olderCursor = page.older_cursor || null
+        hasOlder = !!page.has_older
+        if (page.before) {
+            const scrollFromEnd = logList.contentHeight - logList.contentY
+            entries = (page.entries || []).concat(entries)
+            logList.contentY = logList.contentHeight - scrollFromEnd
+            return
+        }
+        entries = page.entries || []
+        newestCursor = page.newest_cursor || null
+        logList.positionViewAtEnd()
+        if (following)
+            setFollowing(true)
+    }
+
+    function appendEntry(entry) {
+        if (!visible || !following)
+            return
+        const atEnd = logList.atYEnd
+        let updated = entries.concat([entry])
+        if (updated.length > maxEntries) {
+            updated = updated.slice(updated.length - maxEntries)
+            olderCursor = updated[0].cursor
+            hasOlder = true
+        }
+        entries = updated
+        newestCursor = entry.cursor
+        if (atEnd)
+            logList.positionViewAtEnd()
+    }
+
+    function entryColor(entry) {
+        if (entry.priority !== undefined && entry.priority !== null) {
+            if (entry.priority <= 3)
+                return Theme.errorBorder
+            if (entry.priority === 4)
+                return Theme.warningBorder
+        }
+        return Theme.text
+    }
+
+    MouseArea {
+        anchors.fill: parent
+        onClicked: overlay.hide()
+    }
+
+    Rectangle {
+        anchors.centerIn: parent
+        width: parent.width - 48
+        height: parent.height - 48
+        color: Theme.surface
+        radius: 14
+        border.color: Theme.border
+        border.width: 1
+
+        MouseArea {
+            anchors.fill: parent
+            onClicked: {} // Prevent clicks from propagating
+        }
+
+        ColumnLayout {
+            anchors.fill: parent
+            anchors.margins: 28
+            spacing: 18
+
+            RowLayout {
+                Layout.fillWidth: true
+                spacing: 14
+
+                Rectangle {
+                    radius: 8
+                    color: overlay.accentColor
+                    Layout.preferredHeight: 34
+                    Layout.preferredWidth: 18
+                    Layout.alignment: Qt.AlignVCenter
+                }
+
+                Text {
+                    text: "Service log"
+                    font.pointSize: overlay.fs(26)
+                    font.bold: true
+                    color: Theme.text
+                }
+
+                Item {
+                    Layout.fillWidth: true
+                }
+
+                Rectangle {
+                    Layout.preferredWidth: 64
+                    Layout.preferredHeight: 64
+                    radius: 12
+                    color: closeTap.pressed ? Theme.accentPressed : overlay.accentColor
+                    border.width: 1
+                    border.color: overlay.accentColor
+
+                    Text {
+                        anchors.centerIn: parent
+                        text: "×"
+                        font.pointSize: overlay.fs(38)
+                        font.bold: true
+                        color: Theme.onAccent
+                    }
+
+                    MouseArea {
+                        id: closeTap
+                        anchors.fill: parent
+                        onClicked: overlay.hide()
+                    }
+                }
+            }
+
+            RowLayout {
+                Layout.fillWidth: true
+                spacing: 12
+
+                Repeater {
+                    model: [
+                        { label: "All", priority: -1 },
+                        { label: "Warnings", priority: 4 },
+                        { label: "Errors", priority: 3 }
+                    ]
+
+                    delegate: AppButton {
+                        required property var modelData
+                        text: modelData.label
+                        fontScale: overlay.fontScale
+                        fillColor: overlay.priority === modelData.priority ? overlay.accentColor : Theme.mutedBg
+                        textColor: overlay.priority === modelData.priority ? Theme.onAccent : Theme.text
+                        outlineColor: overlay.priority === modelData.priority ? overlay.accentColor : Theme.borderSoft
+                        buttonRadius: 10
+                        Layout.preferredWidth: 150
+                        Layout.preferredHeight: 60
+                        onClicked: overlay.applyFilter(modelData.priority, overlay.search)
+                    }
+                }
+
+                TextField {
+                    id: searchField
+                    Layout.fillWidth: true
+                    Layout.preferredHeight: 60
+                    placeholderText: "Search"
+                    font.pointSize: overlay.fs(16)
+                    color: Theme.text
+                    background: Rectangle {
+                        radius: 10
+                        color: Theme.listSurface
+                        border.width: 1
+                        border.color: searchField.activeFocus ? overlay.accentColor : Theme.borderSoft
+                    }
+                    onAccepted: overlay.applyFilter(overlay.priority, text)
+                }
+
+                AppButton {
+                    text: overlay.following ? "Following" : "Follow"
+                    fontScale: overlay.fontScale
+                    fillColor: overlay.following ? Theme.successBorder : overlay.accentColor
+                    pressedColor: overlay.following ? Theme.successPressed : Theme.accentPressed
+                    buttonRadius: 10
+                    Layout.preferredWidth: 170
+                    Layout.preferredHeight: 60
+                    onClicked: overlay.setFollowing(!overlay.following)
+                }
+            }
+
+            Rectangle {
+                Layout.fillWidth: true
+                Layout.fillHeight: true
+                radius: 12
+                color: Theme.listSurface
+                border.width: 1
+                border.color: Theme.borderSoft
+                clip: true
+
+                ListView {
+                    id: logList
+                    anchors.fill: parent
+                    anchors.margins: 16
+                    spacing: 6
+                    model: overlay.entries
+
+                    header: AppButton {
+                        visible: overlay.hasOlder
+                        width: logList.width
+                        height: overlay.hasOlder ? 56 : 0
+                        text: overlay.loading ? "Loading..." : "Load older"
+                        fontScale: overlay.fontScale
+                        enabled: !overlay.loading
+                        buttonRadius: 10
+                        onClicked: overlay.loadOlder()
+                    }
+
+                    delegate: Text {
+                        required property var modelData
+                        width: logList.width
+                        text: `${modelData.timestamp || ""}  ${modelData.message}`
+                        font.pointSize: overlay.fs(13)
+                        font.family: "monospace"
+                        color: overlay.entryColor(modelData)
+                        wrapMode: Text.WrapAnywhere
+                    }
+
+                    ScrollBar.vertical: ScrollBar {}
+                }
+
+                Text {
+                    anchors.centerIn: parent
+                    visible: overlay.entries.length === 0
+                    text: overlay.loading ? "Reading the journal..." : "No log entries match"
+                    font.pointSize: overlay.fs(16)
+                    color: Theme.textMuted
+                }
+            }
+        }
+    }
+}
@@ -17,6 +17,7 @@ Rectangle {
 
     signal controlRequested(string action)
     signal settingsRequested()
+    signal logsRequested()
 
     Layout.fillWidth: true
     Layout.preferredHeight: contentColumn.implicitHeight + 40
@@ -40,7 +41,14 @@ Rectangle {
         return text.charAt(0).toUpperCase() + text.slice(1)
     }
 
+    function diagnosis() {
+        return serviceStatus.diagnosis || null
+    }
+
     function friendlyServiceState() {
+        const problem = diagnosis()
+        if (problem && problem.crash_loop)
+            return "Crash loop"
         const active = (serviceStatus.active_state || "").toLowerCase()
         const sub = (serviceStatus.sub_state || "").toLowerCase()
         const primary = active ? capitalize(active) : "Unknown"
@@ -189,6 +197,17 @@ Rectangle {
 
             Item { Layout.fillWidth: true }
 
+            AppButton {
+                text: "Logs"
+                fontScale: card.fontScale
+                fillColor: card.accentColor
+                pressedColor: Theme.accentPressed
+                buttonRadius: 10
+                Layout.preferredWidth: 150
+                Layout.preferredHeight: 64
+                onClicked: card.logsRequested()
+            }
+
             AppButton {
                 text: "Settings"
                 fontScale: card.fontScale
@@ -203,6 +222,55 @@ Rectangle {
             }
         }
 
+        Rectangle {
+            Layout.fillWidth: true
+            visible: card.diagnosis() !== null
+            implicitHeight: diagnosisColumn.implicitHeight + 28
+            radius: 12
+            color: Theme.errorBg
+            border.width: 1
+            border.color: Theme.errorBorder
+
+            ColumnLayout {
+                id: diagnosisColumn
+                anchors.left: parent.left
+                anchors.right: parent.right
+                anchors.verticalCenter: parent.verticalCenter
+                anchors.leftMargin: 20
+                anchors.rightMargin: 20
+                spacing: 8
+
+                Text {
+                    Layout.fillWidth: true
+                    text: card.diagnosis() ? card.diagnosis().summary : ""
+                    font.pointSize: card.fs(17)
+                    font.bold: true
+                    color: Theme.text
+                    wrapMode: Text.WordWrap
+                }
+
+                Text {
+                    Layout.fillWidth: true
+                    text: card.diagnosis() ? card.diagnosis().suggestion : ""
+                    font.pointSize: card.fs(15)
+                    color: Theme.text
+                    wrapMode: Text.WordWrap
+                }
+
+                Text {
+                    Layout.fillWidth: true
+                    visible: !!(card.diagnosis() && card.diagnosis().evidence)
+                    text: card.diagnosis() && card.diagnosis().evidence ? card.diagnosis().evidence : ""
+                    font.pointSize: card.fs(13)
+                    font.family: "monospace"
+                    color: Theme.textMuted
+                    wrapMode: Text.WrapAnywhere
+                    maximumLineCount: 3
+                    elide: Text.ElideRight
+                }
+            }
+        }
+
         Text {
             Layout.fillWidth: true
             visible: (card.installerStatus && card.installerStatus.installer_disabled) && card.installerAttentionRequired
@@ -17,10 +17,19 @@ Rectangle {
     property bool controlBusy: false
     property string guiAddress: ""
     property var updateCheckResult: null
+    property var updateVersions: null
+    property int selectedAppVersionIndex: defaultAppVersionIndex()
     property var updateStatus: null
     property int updateRestartCountdown: 0
     property var syncthingUpdateCheckResult: null
     property var syncthingUpdateStatus: null
+    // Set after the first tap on a major upgrade; the second tap confirms it.
+    property bool syncthingMajorConfirmPending: false
+
+    onSyncthingUpdateCheckResultChanged: syncthingMajorConfirmPending = false
+    property var installerStatus: null
+    // 0: idle, 1: confirming removal, 2: asking whether to delete the configuration too.
+    property int uninstallStage: 0
     property color accentColor: Theme.accent
 
     signal closeRequested()
@@ -28,9 +37,16 @@ Rectangle {
     signal guiAddressToggleRequested(string address)
     signal checkForUpdatesRequested()
     signal downloadUpdateRequested()
+    signal updateVersionsRequested()
+    signal installAppVersionRequested(string version)
     signal restartRequested()
+    signal cancelUpdateRequested()
+    signal rollbackUpdateRequested()
+    signal localUpdateRequested()
     signal checkSyncthingUpdateRequested()
-    signal installSyncthingUpdateRequested()
+    signal installSyncthingUpdateRequested(bool confirmMajor)
+    signal uninstallRequested(bool wipeConfig)
+    signal repairServiceRequested()
 
     function fs(value) {
         return value * fontScale
@@ -51,8 +67,16 @@ Rectangle {
 
     function getUpdateStatusText() {
         if (isRestartPending()) {
+            if (updateStatus.rolled_back) {
+                return "Previous version restored. Close this app, then press Reload in AppLoad (top-right) to load it."
+            }
             return "Update installed. Close this app, then press Reload in AppLoad (top-right) to load the new version."
         }
+        if (updateStatus && updateStatus.rate_limited) {
+            return updateStatus.rate_limited_until
+                ? `Release server is rate limiting this network. Try again after ${updateStatus.rate_limited_until}.`
+                : "Release server is rate limiting this network. Try again later."
+        }
         if (updateStatus && updateStatus.error) {
             return updateStatus.error
         }
@@ -73,6 +97,20 @@ Rectangle {
         return updateStatus && updateStatus.in_progress
     }
 
+    function isUpdateCancellable() {
+        return updateStatus && updateStatus.cancellable
+    }
+
+    function canRollbackUpdate() {
+        return updateStatus && updateStatus.rollback_available
+            && !isUpdateInProgress() && !isRestartPending() && !isSyncthingUpdateInProgress()
+    }
+
+    function canInstallLocalUpdate() {
+        return updateStatus && !!updateStatus.local_update_archive
+            && !isUpdateInProgress() && !isRestartPending() && !isSyncthingUpdateInProgress()
+    }
+
     function isUpdateAvailable() {
         return updateCheckResult && updateCheckResult.update_available
     }
@@ -82,6 +120,9 @@ Rectangle {
             const secs = Math.max(0, updateRestartCountdown || 0)
             return secs > 0 ? `Close (${secs})` : "Closing..."
         }
+        if (isUpdateCancellable()) {
+            return "Cancel"
+        }
         if (isUpdateAvailable()) {
             return "Install"
         }
@@ -89,7 +130,7 @@ Rectangle {
     }
 
     function isUpdateButtonEnabled() {
-        if (isRestartPending()) {
+        if (isRestartPending() || isUpdateCancellable()) {
             return true
         }
         return !isUpdateInProgress() && !isSyncthingUpdateInProgress()
@@ -98,6 +139,8 @@ Rectangle {
     function handleUpdateButtonClick() {
         if (isRestartPending()) {
             overlay.restartRequested()
+        } else if (isUpdateCancellable()) {
+            overlay.cancelUpdateRequested()
         } else if (isUpdateAvailable()) {
             overlay.downloadUpdateRequested()
         } else {
@@ -105,8 +148,118 @@ Rectangle {
         }
     }
 
+    function appVersionList() {
+        return (updateVersions && updateVersions.versions) || []
+    }
+
+    function defaultAppVersionIndex() {
+        const versions = appVersionList()
+        const wanted = updateVersions ? updateVersions.default_version : null
+        for (let i = 0; i < versions.length; ++i) {
+            if (versions[i].version === wanted)
+                return i
+        }
+        return versions.length > 0 ? 0 : -1
+    }
+
+    function selectedAppVersion() {
+        const versions = appVersionList()
+        if (selectedAppVersionIndex < 0 || selectedAppVersionIndex >= versions.length)
+            return null
+        return versions[selectedAppVersionIndex]
+    }
+
+    function selectedAppVersionLabel() {
+        const entry = selectedAppVersion()
+        if (!entry)
+            return ""
+        let label = "v" + entry.version
+        if (updateVersions && entry.version === updateVersions.pinned_version)
+            label += " (pinned)"
+        else if (selectedAppVersionIndex === 0)
+            label += " (latest)"
+        if (entry.prerelease)
+            label += " · release candidate"
+        return label
+    }
+
+    function canInstallAppVersion() {
+        return selectedAppVersion() !== null
+            && !isUpdateInProgress() && !isRestartPending() && !isSyncthingUpdateInProgress()
+    }
+
     function canCloseOverlay() {
-        return !isUpdateInProgress() && !isRestartPending() && !isSyncthingUpdateInProgress()
+        return !isUpdateInProgress() && !isRestartPending() && !isSyncthingUpdateInProgress() && !isUninstallInProgress()
+    }
+
+    function unitDrift() {
+        return (installerStatus && installerStatus.unit_drift) || []
+    }
+
+    function profileIssues() {
+        return (installerStatus && installerStatus.profile_issues) || []
+    }
+
+    function serviceNeedsRepair() {
+        return unitDrift().length > 0 || profileIssues().length > 0
+    }
+
+    function describeServiceProblems() {
+        const drift = unitDrift().map(function(difference) {
+            const dropIn = difference.file.indexOf(".d/") >= 0 ? difference.file + " " : ""
+            const key = dropIn + "[" + difference.section + "] " + difference.key
+            if (difference.installed === null)
+                return key + " is missing"
+            if (difference.expected === null)
+                return key + " is not set by the installer"
+            return key + " is " + difference.installed + ", expected " + difference.expected
+        })
+        return drift.concat(profileIssues()).join("\n")
+    }
+
+    function isUninstallInProgress() {
+        return installerStatus && installerStatus.in_progress
+    }
+
+    function canUninstall() {
+        return installerStatus && (installerStatus.binary_present || installerStatus.service_installed)
+    }
+
+    function getUninstallStatusText() {
+        if (uninstallStage === 1)
+            return "Stops the service and deletes the Syncthing binary."
+        if (uninstallStage === 2)
+            return "Also delete Syncthing's configuration, keys and database? This cannot be undone."
+        if (installerStatus && installerStatus.error)
+            return installerStatus.error
+        if (installerStatus && installerStatus.progress_message)
+            return installerStatus.progress_message
+        return "Removes the Syncthing service and binary"
+    }
+
+    function getUninstallPrimaryLabel() {
+        if (uninstallStage === 1)
+            return "Continue"
+        if (uninstallStage === 2)
+            return "Keep config"
+        return "Uninstall"
+    }
+
+    function handleUninstallPrimaryClick() {
+        if (uninstallStage === 0) {
+            uninstallStage = 1
+        } else if (uninstallStage === 1) {
+            uninstallStage = 2
+        } else {
+            uninstallStage = 0
+            overlay.uninstallRequested(false)
+        }
+    }
+
+    function handleUninstallSecondaryClick() {
+        if (uninstallStage === 2)
+            overlay.uninstallRequested(true)
+        uninstallStage = 0
     }
 
     function getSyncthingUpdateStatusText() {
@@ -116,6 +269,9 @@ Rectangle {
         if (syncthingUpdateStatus && syncthingUpdateStatus.progress_message) {
             return syncthingUpdateStatus.progress_message
         }
+        if (syncthingMajorConfirmPending && isSyncthingUpdateAvailable()) {
+            return `${syncthingUpdateCheckResult.latest} is a major upgrade that migrates the database. A backup is kept for rollback.`
+        }
         if (syncthingUpdateCheckResult) {
             const running = syncthingUpdateCheckResult.running || "unknown"
             const latest = syncthingUpdateCheckResult.latest || "unknown"
@@ -135,7 +291,14 @@ Rectangle {
         return syncthingUpdateCheckResult && syncthingUpdateCheckResult.newer
     }
 
+    function isMajorSyncthingUpdate() {
+        return isSyncthingUpdateAvailable() && !!syncthingUpdateCheckResult.majorNewer
+    }
+
     function getSyncthingUpdateButtonLabel() {
+        if (syncthingMajorConfirmPending && isMajorSyncthingUpdate()) {
+            return "Confirm"
+        }
         if (isSyncthingUpdateAvailable()) {
             return "Install"
         }
@@ -143,8 +306,11 @@ Rectangle {
     }
 
     function handleSyncthingUpdateButtonClick() {
-        if (isSyncthingUpdateAvailable()) {
-            overlay.installSyncthingUpdateRequested()
+        if (isMajorSyncthingUpdate() && !syncthingMajorConfirmPending) {
+            syncthingMajorConfirmPending = true
+        } else if (isSyncthingUpdateAvailable()) {
+            overlay.installSyncthingUpdateRequested(syncthingMajorConfirmPending)
+            syncthingMajorConfirmPending = false
         } else {
             overlay.checkSyncthingUpdateRequested()
         }
@@ -500,6 +666,115 @@ Rectangle {
                             border.width: 2
                             onClicked: overlay.handleUpdateButtonClick()
                         }
+
+                        AppButton {
+                            text: "Roll back"
+                            visible: overlay.canRollbackUpdate()
+                            fontScale: overlay.fontScale
+                            fillColor: Theme.accentSoft
+                            pressedColor: Theme.surfacePressed
+                            textColor: overlay.accentColor
+                            Layout.alignment: Qt.AlignVCenter
+                            Layout.preferredWidth: 160
+                            Layout.preferredHeight: 60
+                            outlineColor: overlay.accentColor
+                            border.width: 2
+                            onClicked: overlay.rollbackUpdateRequested()
+                        }
+
+                        AppButton {
+                            text: "From file"
+                            visible: overlay.canInstallLocalUpdate()
+                            fontScale: overlay.fontScale
+                            fillColor: Theme.accentSoft
+                            pressedColor: Theme.surfacePressed
+                            textColor: overlay.accentColor
+                            Layout.alignment: Qt.AlignVCenter
+                            Layout.preferredWidth: 160
+                            Layout.preferredHeight: 60
+                            outlineColor: overlay.accentColor
+                            border.width: 2
+                            onClicked: overlay.localUpdateRequested()
+                        }
+                    }
+                }
+
+                Rectangle {
+                    Layout.fillWidth: true
+                    visible: overlay.appVersionList().length > 0
+                    implicitHeight: appVersionRow.implicitHeight + 32
+                    radius: 12
+                    color: Theme.listSurface
+                    border.width: 1
+                    border.color: Theme.borderSoft
+
+                    Rectangle {
+                        width: 12
+                        radius: 6
+                        anchors.left: parent.left
+                        anchors.top: parent.top
+                        anchors.bottom: parent.bottom
+                        anchors.margins: 10
+                        color: overlay.accentColor
+                    }
+
+                    RowLayout {
+                        id: appVersionRow
+                        anchors.left: parent.left
+                        anchors.right: parent.right
+                        anchors.verticalCenter: parent.verticalCenter
+                        anchors.leftMargin: 34
+                        anchors.rightMargin: 20
+                        spacing: 16
+
+                        Text {
+                            text: "UI version"
+                            font.pointSize: overlay.fs(20)
+                            font.bold: true
+                            color: Theme.text
+                        }
+
+                        AppButton {
+                            Layout.preferredWidth: 72
+                            Layout.preferredHeight: 60
+                            text: "‹"
+                            fontScale: overlay.fontScale
+                            enabled: !overlay.isUpdateInProgress() && overlay.selectedAppVersionIndex < overlay.appVersionList().length - 1
+                            onClicked: overlay.selectedAppVersionIndex += 1
+                        }
+
+                        Text {
+                            Layout.fillWidth: true
+                            text: overlay.selectedAppVersionLabel()
+                            font.pointSize: overlay.fs(17)
+                            color: Theme.text
+                            horizontalAlignment: Text.AlignHCenter
+                            elide: Text.ElideRight
+                        }
+
+                        AppButton {
+                            Layout.preferredWidth: 72
+                            Layout.preferredHeight: 60
+                            text: "›"
+                            fontScale: overlay.fontScale
+                            enabled: !overlay.isUpdateInProgress() && overlay.selectedAppVersionIndex > 0
+                            onClicked: overlay.selectedAppVersionIndex -= 1
+                        }
+
+                        AppButton {
+                            text: "Install"
+                            fontScale: overlay.fontScale
+                            fillColor: Theme.accentSoft
+                            pressedColor: Theme.surfacePressed
+                            textColor: overlay.accentColor
+                            enabled: overlay.canInstallAppVersion()
+                            Layout.alignment: Qt.AlignVCenter
+                            Layout.preferredWidth: 160
+                            Layout.preferredHeight: 60
+                            outlineColor: enabled ? overlay.accentColor : Theme.borderSoft
+                            border.width: 2
+                            onClicked: overlay.installAppVersionRequested(overlay.selectedAppVersion().version)
+                        }
                     }
                 }
 
@@ -567,6 +842,152 @@ Rectangle {
                         }
                     }
                 }
+
+                Rectangle {
+                    Layout.fillWidth: true
+                    visible: overlay.serviceNeedsRepair()
+                    implicitHeight: repairRow.implicitHeight + 32
+                    radius: 12
+                    color: Theme.listSurface
+                    border.width: 1
+                    border.color: Theme.warningBorder
+
+                    Rectangle {
+                        width: 12
+                        radius: 6
+                        anchors.left: parent.left
+                        anchors.top: parent.top
+                        anchors.bottom: parent.bottom
+                        anchors.margins: 10
+                        color: Theme.warningBorder
+                    }
+
+                    RowLayout {
+                        id: repairRow
+                        anchors.left: parent.left
+                        anchors.right: parent.right
+                        anchors.verticalCenter: parent.verticalCenter
+                        anchors.leftMargin: 34
+                        anchors.rightMargin: 20
+                        spacing: 20
+
+                        ColumnLayout {
+                            Layout.fillWidth: true
+                            spacing: 8
+
+                            Text {
+                                text: overlay.unitDrift().length > 0 ? "Service file changed" : "Service cannot write its folders"
+                                font.pointSize: overlay.fs(20)
+                                font.bold: true
+                                color: Theme.text
+                            }
+
+                            Text {
+                                text: overlay.describeServiceProblems()
+                                font.pointSize: overlay.fs(15)
+                                color: Theme.textMuted
+                                wrapMode: Text.WrapAnywhere
+                                Layout.fillWidth: true
+                            }
+                        }
+
+                        AppButton {
+                            text: "Repair"
+                            fontScale: overlay.fontScale
+                            fillColor: Theme.warningBorder
+                            pressedColor: Theme.warningPressed
+                            disabledFillColor: Theme.mutedBg
+                            disabledTextColor: Theme.textSubtle
+                            enabled: !overlay.isUninstallInProgress() && !overlay.isSyncthingUpdateInProgress()
+                            Layout.alignment: Qt.AlignVCenter
+                            Layout.preferredWidth: 160
+                            Layout.preferredHeight: 60
+                            outlineColor: enabled ? Theme.warningBorder : Theme.borderSoft
+                            border.width: 2
+                            onClicked: overlay.repairServiceRequested()
+                        }
+                    }
+                }
+
+                Rectangle {
+                    Layout.fillWidth: true
+                    implicitHeight: uninstallRow.implicitHeight + 32
+                    radius: 12
+                    color: Theme.listSurface
+                    border.width: 1
+                    border.color: overlay.uninstallStage > 0 ? Theme.errorBorder : Theme.borderSoft
+
+                    Rectangle {
+                        width: 12
+                        radius: 6
+                        anchors.left: parent.left
+                        anchors.top: parent.top
+                        anchors.bottom: parent.bottom
+                        anchors.margins: 10
+                        color: Theme.errorBorder
+                    }
+
+                    RowLayout {
+                        id: uninstallRow
+                        anchors.left: parent.left
+                        anchors.right: parent.right
+                        anchors.verticalCenter: parent.verticalCenter
+                        anchors.leftMargin: 34
+                        anchors.rightMargin: 20
+                        spacing: 20
+
+                        ColumnLayout {
+                            Layout.fillWidth: true
+                            spacing: 8
+
+                            Text {
+                                text: "Uninstall Syncthing"
+                                font.pointSize: overlay.fs(20)
+                                font.bold: true
+                                color: Theme.text
+                            }
+
+                            Text {
+                                text: overlay.getUninstallStatusText()
+                                font.pointSize: overlay.fs(15)
+                                color: (overlay.installerStatus && overlay.installerStatus.error) ? Theme.errorBorder : Theme.textMuted
+                                wrapMode: Text.WordWrap
+                                Layout.fillWidth: true
+                            }
+                        }
+
+                        AppButton {
+                            text: overlay.getUninstallPrimaryLabel()
+                            fontScale: overlay.fontScale
+                            fillColor: Theme.errorBorder
+                            pressedColor: Theme.errorPressed
+                            disabledFillColor: Theme.mutedBg
+                            disabledTextColor: Theme.textSubtle
+                            enabled: overlay.canUninstall() && !overlay.isUninstallInProgress() && !overlay.isSyncthingUpdateInProgress()
+                            Layout.alignment: Qt.AlignVCenter
+                            Layout.preferredWidth: 160
+                            Layout.preferredHeight: 60
+                            outlineColor: enabled ? Theme.errorBorder : Theme.borderSoft
+                            border.width: 2
+                            onClicked: overlay.handleUninstallPrimaryClick()
+                        }
+
+                        AppButton {
+                            text: overlay.uninstallStage === 2 ? "Delete all" : "Cancel"
+                            visible: overlay.uninstallStage > 0
+                            fontScale: overlay.fontScale
+                            fillColor: Theme.accentSoft
+                            pressedColor: Theme.surfacePressed
+                            textColor: overlay.uninstallStage === 2 ? Theme.errorBorder : overlay.accentColor
+                            Layout.alignment: Qt.AlignVCenter
+                            Layout.preferredWidth: 160
+                            Layout.preferredHeight: 60
+                            outlineColor: overlay.uninstallStage === 2 ? Theme.errorBorder : overlay.accentColor
+                            border.width: 2
+                            onClicked: overlay.handleUninstallSecondaryClick()
+                        }
+                    }
+                }
             }
 
             Item {
@@ -576,7 +997,9 @@ Rectangle {
     }
 
     function show() {
+        uninstallStage = 0
         visible = true
+        updateVersionsRequested()
     }
 
     function hide() {
@@ -25,6 +25,15 @@ Rectangle {
     readonly property int msgUpdateRestartRequest: 6
     readonly property int msgSyncthingUpdateCheckRequest: 7
     readonly property int msgSyncthingUpdateInstallRequest: 8
+    readonly property int msgInstallCancelRequest: 9
+    readonly property int msgUpdateCancelRequest: 10
+    readonly property int msgUpdateRollbackRequest: 11
+    readonly property int msgInstallVersionsRequest: 12
+    readonly property int msgUninstallRequest: 13
+    readonly property int msgRepairServiceRequest: 14
+    readonly property int msgJournalRequest: 15
+    readonly property int msgJournalFollowRequest: 16
+    readonly property int msgUpdateVersionsRequest: 17
     readonly property int msgStatusUpdate: 100
     readonly property int msgControlResult: 101
     readonly property int msgInstallStatus: 102
@@ -33,6 +42,10 @@ Rectangle {
     readonly property int msgUpdateDownloadStatus: 105
     readonly property int msgSyncthingUpdateCheckResult: 106
     readonly property int msgSyncthingUpdateStatus: 107
+    readonly property int msgInstallVersionsResult: 108
+    readonly property int msgJournalPage: 109
+    readonly property int msgJournalEntry: 110
+    readonly property int msgUpdateVersionsResult: 111
     readonly property int msgError: 500
 
     property var serviceStatus: ({})
@@ -41,10 +54,13 @@ Rectangle {
     property var peers: []
     property bool controlBusy: false
     property var installerStatus: null
+    property var installVersions: null
+    property bool installVersionsRequested: false
     property real fontScale: 1.25
     property bool installerPromptDismissed: false
     property string guiAddress: ""
     property var updateCheckResult: null
+    property var updateVersions: null
     property var updateStatus: null
     property int updateRestartCountdown: 0
     property var syncthingUpdateCheckResult: null
@@ -89,10 +105,19 @@ Rectangle {
             case root.msgInstallStatus:
                 try {
                     installerStatus = JSON.parse(contents)
+                    if (canShowInstallerPrompt() && !installVersionsRequested)
+                        requestInstallVersions()
                 } catch (errInstaller) {
                     console.warn("Installer status error", errInstaller)
                 }
                 break
+            case root.msgInstallVersionsResult:
+                try {
+                    installVersions = JSON.parse(contents)
+                } catch (errInstallVersions) {
+                    console.warn("Install versions error", errInstallVersions)
+                }
+                break
             case root.msgGuiAddressResult:
                 try {
                     const guiAddressResult = JSON.parse(contents)
@@ -108,6 +133,13 @@ Rectangle {
                     console.warn("Update check error", errUpdate)
                 }
                 break
+            case root.msgUpdateVersionsResult:
+                try {
+                    updateVersions = JSON.parse(contents)
+                } catch (errUpdateVersions) {
+                    console.warn("Update versions error", errUpdateVersions)
+                }
+                break
             case root.msgUpdateDownloadStatus:
                 try {
                     updateStatus = JSON.parse(contents)
@@ -144,6 +176,20 @@ Rectangle {
                     console.warn("Syncthing update status error", errSyncthingUpdateStatus)
                 }
                 break
+            case root.msgJournalPage:
+                try {
+                    journalOverlay.applyPage(JSON.parse(contents))
+                } catch (errJournalPage) {
+                    console.warn("Journal page error", errJournalPage)
+                }
+                break
+            case root.msgJournalEntry:
+                try {
+                    journalOverlay.appendEntry(JSON.parse(contents))
+                } catch (errJournalEntry) {
+                    console.warn("Journal entry error", errJournalEntry)
+                }
+                break
             case root.msgError:
                 try {
                     const errorPayload = JSON.parse(contents)
@@ -151,6 +197,7 @@ Rectangle {
                     console.warn("Backend error payload parse issue", errBackend)
                 }
                 controlBusy = false
+                journalOverlay.loading = false
                 break
             default:
                 console.warn("Unhandled backend message", type, contents)
@@ -169,6 +216,12 @@ Rectangle {
     function installerNeedsAttention() {
         if (!installerStatus)
             return false
+        // Keep the installer up while the install is verified and when a check failed.
+        if (installerStatus.in_progress)
+            return true
+        const checkpoints = installerStatus.checkpoints || []
+        if (checkpoints.some(checkpoint => checkpoint.state === "failed"))
+            return true
         const binaryReady = !!installerStatus.binary_present
         const serviceReady = !!installerStatus.service_installed
         return !(binaryReady && serviceReady)
@@ -180,10 +233,40 @@ Rectangle {
         return installerNeedsAttention()
     }
 
-    function triggerInstaller() {
+    function triggerInstaller(version) {
+        if (!installerStatus || installerStatus.in_progress)
+            return
+        const payload = version ? { version: version } : {}
+        backend.sendMessage(msgInstallTrigger, JSON.stringify(payload))
+    }
+
+    function triggerLocalInstaller() {
+        if (!installerStatus || installerStatus.in_progress)
+            return
+        backend.sendMessage(msgInstallTrigger, JSON.stringify({ local: true }))
+    }
+
+    function requestInstallVersions() {
+        installVersionsRequested = true
+        backend.sendMessage(msgInstallVersionsRequest, JSON.stringify({}))
+    }
+
+    function uninstallSyncthing(wipeConfig) {
         if (!installerStatus || installerStatus.in_progress)
             return
-        backend.sendMessage(msgInstallTrigger, JSON.stringify({}))
+        backend.sendMessage(msgUninstallRequest, JSON.stringify({ wipe_config: wipeConfig }))
+    }
+
+    function repairService() {
+        if (!installerStatus || installerStatus.in_progress)
+            return
+        backend.sendMessage(msgRepairServiceRequest, JSON.stringify({}))
+    }
+
+    function cancelInstaller() {
+        if (!installerStatus || !installerStatus.cancellable)
+            return
+        backend.sendMessage(msgInstallCancelRequest, JSON.stringify({}))
     }
 
     function toggleGuiAddress(address) {
@@ -197,20 +280,37 @@ Rectangle {
         backend.sendMessage(msgUpdateCheckRequest, JSON.stringify({}))
     }
 
-    function downloadUpdate() {
-        backend.sendMessage(msgUpdateDownloadRequest, JSON.stringify({}))
+    function downloadUpdate(version) {
+        const payload = version ? { version: version } : {}
+        backend.sendMessage(msgUpdateDownloadRequest, JSON.stringify(payload))
+    }
+
+    function requestUpdateVersions() {
+        backend.sendMessage(msgUpdateVersionsRequest, JSON.stringify({}))
     }
 
     function requestRestart() {
         backend.sendMessage(msgUpdateRestartRequest, JSON.stringify({}))
     }
 
+    function cancelUpdate() {
+        backend.sendMessage(msgUpdateCancelRequest, JSON.stringify({}))
+    }
+
+    function rollbackUpdate() {
+        backend.sendMessage(msgUpdateRollbackRequest, JSON.stringify({}))
+    }
+
+    function installLocalUpdate() {
+        backend.sendMessage(msgUpdateDownloadRequest, JSON.stringify({ local: true }))
+    }
+
     function checkSyncthingUpdate() {
         backend.sendMessage(msgSyncthingUpdateCheckRequest, JSON.stringify({}))
     }
 
-    function installSyncthingUpdate() {
-        backend.sendMessage(msgSyncthingUpdateInstallRequest, JSON.stringify({}))
+    function installSyncthingUpdate(confirmMajor) {
+        backend.sendMessage(msgSyncthingUpdateInstallRequest, JSON.stringify({ confirm_major: confirmMajor }))
     }
 
     Timer {
@@ -264,6 +364,7 @@ Rectangle {
 
                     onControlRequested: controlService(action)
                     onSettingsRequested: settingsOverlay.show()
+                    onLogsRequested: journalOverlay.show()
                 }
 
                 FolderPeersPanel {
@@ -284,9 +385,15 @@ Rectangle {
             Layout.fillHeight: true
             fontScale: root.fontScale
             installerStatus: root.installerStatus
+            availableVersions: root.installVersions
             dismissable: true
 
-            onInstallRequested: triggerInstaller()
+            onInstallRequested: function(version) {
+                triggerInstaller(version)
+            }
+            onLocalInstallRequested: triggerLocalInstaller()
+            onCancelRequested: cancelInstaller()
+            onRestoreRequested: repairService()
             onDismissRequested: {
                 root.unloading()
                 root.close()
@@ -303,10 +410,12 @@ Rectangle {
         controlBusy: root.controlBusy
         guiAddress: root.guiAddress
         updateCheckResult: root.updateCheckResult
+        updateVersions: root.updateVersions
         updateStatus: root.updateStatus
         updateRestartCountdown: root.updateRestartCountdown
         syncthingUpdateCheckResult: root.syncthingUpdateCheckResult
         syncthingUpdateStatus: root.syncthingUpdateStatus
+        installerStatus: root.installerStatus
 
         onCloseRequested: settingsOverlay.hide()
         
@@ -326,16 +435,57 @@ Rectangle {
             downloadUpdate()
         }
 
+        onUpdateVersionsRequested: function() {
+            requestUpdateVersions()
+        }
+
+        onInstallAppVersionRequested: function(version) {
+            downloadUpdate(version)
+        }
+
         onRestartRequested: function() {
             requestRestart()
         }
 
+        onCancelUpdateRequested: function() {
+            cancelUpdate()
+        }
+
+        onRollbackUpdateRequested: function() {
+            rollbackUpdate()
+        }
+
+        onLocalUpdateRequested: function() {
+            installLocalUpdate()
+        }
+
         onCheckSyncthingUpdateRequested: function() {
             checkSyncthingUpdate()
         }
 
-        onInstallSyncthingUpdateRequested: function() {
-            installSyncthingUpdate()
+        onInstallSyncthingUpdateRequested: function(confirmMajor) {
+            installSyncthingUpdate(confirmMajor)
+        }
+
+        onUninstallRequested: function(wipeConfig) {
+            uninstallSyncthing(wipeConfig)
+        }
+
+        onRepairServiceRequested: repairService()
+    }
+
+    JournalOverlay {
+        id: journalOverlay
+        anchors.fill: parent
+        fontScale: root.fontScale
+        accentColor: root.accentColor
+
+        onPageRequested: function(request) {
+            backend.sendMessage(msgJournalRequest, JSON.stringify(request))
+        }
+
+        onFollowRequested: function(request) {
+            backend.sendMessage(msgJournalFollowRequest, JSON.stringify(request))
         }
     }
 }